@@ -0,0 +1,105 @@
+//! Times fuzzy queries against an index of a large generated file
+//!
+//! Run with `cargo bench --bench fuzzy`. It fails if a query misses
+//! [`TARGET`], the time a launcher has to refresh its results as the user
+//! types.
+
+use sbm::fuzzy::Index;
+use sbm::{Bookmark, Category, Header, Sbm};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const CATEGORIES: usize = 100;
+const BOOKMARKS: usize = 250;
+const RUNS: u32 = 20;
+const TARGET: Duration = Duration::from_millis(1);
+
+const WORDS: [&str; 40] = [
+    "rust",
+    "docs",
+    "kernel",
+    "recipes",
+    "dashboard",
+    "issues",
+    "wiki",
+    "music",
+    "maps",
+    "weather",
+    "news",
+    "travel",
+    "python",
+    "grafana",
+    "jira",
+    "banking",
+    "taxes",
+    "cinema",
+    "podcasts",
+    "forum",
+    "blog",
+    "shop",
+    "garden",
+    "climbing",
+    "chess",
+    "linux",
+    "vim",
+    "photos",
+    "calendar",
+    "mail",
+    "jobs",
+    "books",
+    "cooking",
+    "fitness",
+    "crypto",
+    "cloud",
+    "security",
+    "design",
+    "fonts",
+    "zig",
+];
+
+fn generate() -> Sbm {
+    let word = |i: usize| WORDS[i % WORDS.len()];
+    Sbm((0..CATEGORIES)
+        .map(|c| Category {
+            bookmarks: (0..BOOKMARKS)
+                .map(|b| {
+                    Bookmark::new(
+                        &format!("{} {} {}", word(b), word(b / 7 + c), b),
+                        &format!("All about {} and {}", word(b * 3 + c), word(b / 3)),
+                        &format!("https://{}.example.com/{}/{}", word(c + b), word(b), b),
+                    )
+                })
+                .collect(),
+            ..Category::new(Header::new(&format!("{} {}", word(c), c), None))
+        })
+        .collect())
+}
+
+fn time<T>(name: &str, f: impl Fn() -> T) -> Duration {
+    black_box(f());
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        black_box(f());
+        best = best.min(start.elapsed());
+    }
+    println!("{:<24} {:>10.2?}", name, best);
+    best
+}
+
+fn main() {
+    let sbm = generate();
+    println!(
+        "{} bookmarks, best of {} runs",
+        CATEGORIES * BOOKMARKS,
+        RUNS
+    );
+    time("Index::new", || Index::new(&sbm).len());
+    let index = Index::new(&sbm);
+    for query in ["zq", "wthr", "rust kern 12", "dshbrd wiki", "gfna", "e"] {
+        let best = time(&format!("search_top({:?}, 20)", query), || {
+            index.search_top(query, 20).len()
+        });
+        assert!(best < TARGET, "{:?} took {:?}", query, best);
+    }
+}
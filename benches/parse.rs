@@ -0,0 +1,61 @@
+//! Compares the owned and zero-copy parse paths on a large generated file
+//!
+//! Run with `cargo bench`.
+
+use sbm::parser;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const CATEGORIES: usize = 500;
+const BOOKMARKS: usize = 200;
+const RUNS: u32 = 10;
+
+fn generate() -> String {
+    let mut data = String::new();
+    for c in 0..CATEGORIES {
+        data.push_str(&format!("# Category {} | 📁\n", c));
+        for b in 0..BOOKMARKS {
+            data.push_str(&format!(
+                "Bookmark {b} | Description of bookmark {b} in category {c} | https://example.com/{c}/{b}\n"
+            ));
+        }
+        data.push('\n');
+    }
+    data
+}
+
+/// Time `parse`, which returns the number of bookmarks it found
+fn time(name: &str, data: &str, parse: impl Fn(&str) -> usize) {
+    black_box(parse(data));
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        black_box(parse(black_box(data)));
+        best = best.min(start.elapsed());
+    }
+    let mib = data.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "{:<22} {:>10.2?}  {:>8.1} MiB/s",
+        name,
+        best,
+        mib / best.as_secs_f64()
+    );
+}
+
+fn main() {
+    let data = generate();
+    println!(
+        "{} bookmarks, {:.1} MiB, best of {} runs",
+        CATEGORIES * BOOKMARKS,
+        data.len() as f64 / (1024.0 * 1024.0),
+        RUNS
+    );
+    time("parse_categories", &data, |d| {
+        let categories = black_box(parser::parse_categories(d).unwrap());
+        categories.iter().map(|c| c.bookmarks.len()).sum()
+    });
+    time("parse_categories_ref", &data, |d| {
+        let categories = black_box(parser::parse_categories_ref(d).unwrap());
+        categories.iter().map(|c| c.bookmarks.len()).sum()
+    });
+}
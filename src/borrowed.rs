@@ -0,0 +1,283 @@
+//! Borrowed model
+//!
+//! These types mirror [`Bookmark`], [`Header`] and [`Category`] but borrow
+//! their text from the input where possible. They are produced by the
+//! zero-copy parse path ([`parser::parse_categories_ref`]) and can be turned
+//! into the owned types with `into_owned`.
+//!
+//! [`parser::parse_categories_ref`]: crate::parser::parse_categories_ref
+
+use crate::{parser, Bookmark, Category, Header};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Borrowed metadata of a [`BookmarkRef`] or [`HeaderRef`]
+pub type MetadataRef<'a> = BTreeMap<Cow<'a, str>, Cow<'a, str>>;
+
+fn borrow_metadata(metadata: &BTreeMap<String, String>) -> MetadataRef<'_> {
+    metadata
+        .iter()
+        .map(|(k, v)| (Cow::Borrowed(k.as_str()), Cow::Borrowed(v.as_str())))
+        .collect()
+}
+
+fn own_metadata(metadata: MetadataRef<'_>) -> BTreeMap<String, String> {
+    metadata
+        .into_iter()
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect()
+}
+
+fn metadata_eq(borrowed: &MetadataRef<'_>, owned: &BTreeMap<String, String>) -> bool {
+    borrowed.len() == owned.len()
+        && borrowed
+            .iter()
+            .zip(owned)
+            .all(|((k1, v1), (k2, v2))| k1 == k2 && v1 == v2)
+}
+
+/// Write the `//@ key: value` lines that go above an entry
+fn fmt_annotations(f: &mut std::fmt::Formatter, metadata: &MetadataRef<'_>) -> std::fmt::Result {
+    for (key, value) in metadata {
+        writeln!(f, "{}", parser::annotation(key, value))?;
+    }
+    Ok(())
+}
+
+/// Borrowed [`Bookmark`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct BookmarkRef<'a> {
+    pub name: Cow<'a, str>,
+    pub description: Cow<'a, str>,
+    pub url: Cow<'a, str>,
+    pub tags: Vec<Cow<'a, str>>,
+    pub metadata: MetadataRef<'a>,
+}
+
+impl<'a> BookmarkRef<'a> {
+    pub fn new(name: &'a str, description: &'a str, url: &'a str) -> BookmarkRef<'a> {
+        BookmarkRef {
+            name: Cow::Borrowed(name),
+            description: Cow::Borrowed(description),
+            url: Cow::Borrowed(url),
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn into_owned(self) -> Bookmark {
+        Bookmark {
+            name: self.name.into_owned(),
+            description: self.description.into_owned(),
+            url: self.url.into_owned(),
+            tags: self.tags.into_iter().map(Cow::into_owned).collect(),
+            metadata: own_metadata(self.metadata),
+        }
+    }
+}
+
+impl<'a> From<&'a Bookmark> for BookmarkRef<'a> {
+    fn from(bookmark: &'a Bookmark) -> BookmarkRef<'a> {
+        BookmarkRef {
+            tags: bookmark
+                .tags
+                .iter()
+                .map(|t| Cow::Borrowed(t.as_str()))
+                .collect(),
+            metadata: borrow_metadata(&bookmark.metadata),
+            ..BookmarkRef::new(&bookmark.name, &bookmark.description, &bookmark.url)
+        }
+    }
+}
+
+impl PartialEq<Bookmark> for BookmarkRef<'_> {
+    fn eq(&self, other: &Bookmark) -> bool {
+        self.name == other.name
+            && self.description == other.description
+            && self.url == other.url
+            && self.tags == other.tags
+            && metadata_eq(&self.metadata, &other.metadata)
+    }
+}
+
+impl std::fmt::Display for BookmarkRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fmt_annotations(f, &self.metadata)?;
+        write!(
+            f,
+            "{}|{}|{}",
+            parser::escape_first(&self.name),
+            parser::escape(&self.description),
+            parser::escape(&self.url)
+        )?;
+        for (i, tag) in self.tags.iter().enumerate() {
+            let separator = if i == 0 { '|' } else { ',' };
+            write!(f, "{}{}", separator, parser::escape_tag(tag))?;
+        }
+        Ok(())
+    }
+}
+
+/// Borrowed [`Header`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct HeaderRef<'a> {
+    pub name: Cow<'a, str>,
+    pub icon: Option<Cow<'a, str>>,
+    pub metadata: MetadataRef<'a>,
+}
+
+impl<'a> HeaderRef<'a> {
+    pub fn new(name: &'a str, icon: Option<&'a str>) -> HeaderRef<'a> {
+        HeaderRef {
+            name: Cow::Borrowed(name),
+            icon: icon.map(Cow::Borrowed),
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    pub fn into_owned(self) -> Header {
+        Header {
+            name: self.name.into_owned(),
+            icon: self.icon.map(Cow::into_owned),
+            metadata: own_metadata(self.metadata),
+        }
+    }
+
+    /// Write the header with `depth` `#`s, after its annotations
+    pub(crate) fn fmt_at(&self, f: &mut std::fmt::Formatter, depth: usize) -> std::fmt::Result {
+        fmt_annotations(f, &self.metadata)?;
+        write!(
+            f,
+            "{}{}",
+            "#".repeat(depth),
+            parser::escape_first(&self.name)
+        )?;
+        if let Some(icon) = &self.icon {
+            write!(f, "|{}", parser::escape(icon))?;
+        }
+        Ok(())
+    }
+}
+
+/// A header written with a given number of `#`s, see [`HeaderRef::fmt_at`]
+pub(crate) struct HeaderAt<'h, 'a>(pub &'h HeaderRef<'a>, pub usize);
+
+impl std::fmt::Display for HeaderAt<'_, '_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.0.fmt_at(f, self.1)
+    }
+}
+
+impl<'a> From<&'a Header> for HeaderRef<'a> {
+    fn from(header: &'a Header) -> HeaderRef<'a> {
+        HeaderRef {
+            metadata: borrow_metadata(&header.metadata),
+            ..HeaderRef::new(&header.name, header.icon.as_deref())
+        }
+    }
+}
+
+impl PartialEq<Header> for HeaderRef<'_> {
+    fn eq(&self, other: &Header) -> bool {
+        self.name == other.name
+            && self.icon.as_deref() == other.icon.as_deref()
+            && metadata_eq(&self.metadata, &other.metadata)
+    }
+}
+
+impl std::fmt::Display for HeaderRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.fmt_at(f, 1)
+    }
+}
+
+/// Borrowed [`Category`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct CategoryRef<'a> {
+    pub header: HeaderRef<'a>,
+    pub bookmarks: Vec<BookmarkRef<'a>>,
+    pub children: Vec<CategoryRef<'a>>,
+}
+
+impl<'a> CategoryRef<'a> {
+    pub fn new(header: HeaderRef<'a>) -> CategoryRef<'a> {
+        CategoryRef {
+            header,
+            bookmarks: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn into_owned(self) -> Category {
+        Category {
+            header: self.header.into_owned(),
+            bookmarks: self
+                .bookmarks
+                .into_iter()
+                .map(BookmarkRef::into_owned)
+                .collect(),
+            children: self
+                .children
+                .into_iter()
+                .map(CategoryRef::into_owned)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> From<&'a Category> for CategoryRef<'a> {
+    fn from(category: &'a Category) -> CategoryRef<'a> {
+        CategoryRef {
+            header: HeaderRef::from(&category.header),
+            bookmarks: category.bookmarks.iter().map(BookmarkRef::from).collect(),
+            children: category.children.iter().map(CategoryRef::from).collect(),
+        }
+    }
+}
+
+impl PartialEq<Category> for CategoryRef<'_> {
+    fn eq(&self, other: &Category) -> bool {
+        self.header == other.header
+            && self.bookmarks == other.bookmarks
+            && self.children == other.children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_borrows_from_input() {
+        let data = "#Web|🌐\nMDN|Web documentation|https://developer.mozilla.org/\nBash|pipes \\| more|https://www.gnu.org/\n";
+        let categories = parser::parse_categories_ref(data).unwrap();
+        let bookmarks = &categories[0].bookmarks;
+        assert!(matches!(
+            categories[0].header.icon,
+            Some(Cow::Borrowed("🌐"))
+        ));
+        assert!(matches!(bookmarks[0].description, Cow::Borrowed(_)));
+        assert!(matches!(bookmarks[1].description, Cow::Owned(_)));
+        assert_eq!(bookmarks[1].description, "pipes | more");
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let category = Category {
+            header: Header::new("Web", Some("🌐")),
+            bookmarks: vec![Bookmark::new("Bash", "a | b", "https://www.gnu.org/")
+                .with_metadata("added", "2024-01-31")],
+            children: vec![Category::new(
+                Header::new("Shells", None).with_metadata("owner", "ops"),
+            )],
+        };
+        let borrowed = CategoryRef::from(&category);
+        assert_eq!(borrowed, category);
+        assert_eq!(borrowed.header.to_string(), category.header.to_string());
+        assert_eq!(
+            borrowed.bookmarks[0].to_string(),
+            category.bookmarks[0].to_string()
+        );
+        assert_eq!(borrowed.into_owned(), category);
+    }
+}
@@ -0,0 +1,212 @@
+//! Conversion to and from JSON, YAML and TOML
+//!
+//! Available with the `serde` feature, which also implements `Serialize`
+//! and `Deserialize` for the data model. The shape is stable: an [`Sbm`] is
+//! a list of categories, each with a `header`, its `bookmarks` and its
+//! subcategories under `children`.
+//!
+//! ```json
+//! [
+//!   {
+//!     "header": { "name": "Languages", "icon": "👨‍💻" },
+//!     "bookmarks": [
+//!       {
+//!         "name": "Rust",
+//!         "description": "Rust",
+//!         "url": "https://www.rust-lang.org/",
+//!         "tags": ["lang"],
+//!         "metadata": { "added": "2024-01-31" }
+//!       }
+//!     ],
+//!     "children": [
+//!       {
+//!         "header": { "name": "Crates", "icon": null },
+//!         "bookmarks": [
+//!           { "name": "serde", "description": "", "url": "https://serde.rs/" }
+//!         ],
+//!         "children": []
+//!       }
+//!     ]
+//!   }
+//! ]
+//! ```
+//!
+//! `icon` is `null` when the category has none, and `tags` and `metadata`
+//! are left out when they are empty. When reading, `icon`, `description`,
+//! `tags`, `metadata`, `bookmarks` and `children` may be left out. TOML
+//! documents must be tables, so there the list is the `categories` key, and
+//! categories without an icon simply have no `icon` key.
+
+use crate::{Category, Sbm};
+
+/// Error returned when a document can't be converted to an [`Sbm`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Json(serde_json::Error),
+    Yaml(serde_norway::Error),
+    Toml(toml::de::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Json(e) => write!(f, "invalid JSON: {}", e),
+            Error::Yaml(e) => write!(f, "invalid YAML: {}", e),
+            Error::Toml(e) => write!(f, "invalid TOML: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json(e) => Some(e),
+            Error::Yaml(e) => Some(e),
+            Error::Toml(e) => Some(e),
+        }
+    }
+}
+
+/// The TOML document: a table holding the list of categories
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TomlDocument<C> {
+    categories: C,
+}
+
+/// Write an `Sbm` as pretty-printed JSON
+///
+/// # Examples
+///
+/// ```
+/// use sbm::{convert, Sbm};
+/// let sbm: Sbm = "#Web\nMDN|Docs|https://developer.mozilla.org/".parse().unwrap();
+/// let json = convert::to_json(&sbm);
+/// assert!(json.contains(r#""url": "https://developer.mozilla.org/""#));
+/// assert_eq!(convert::from_json(&json).unwrap(), sbm);
+/// ```
+pub fn to_json(sbm: &Sbm) -> String {
+    serde_json::to_string_pretty(sbm).expect("an Sbm is always valid JSON")
+}
+
+/// Read an `Sbm` from JSON
+pub fn from_json(json: &str) -> Result<Sbm, Error> {
+    serde_json::from_str(json).map_err(Error::Json)
+}
+
+/// Write an `Sbm` as YAML
+pub fn to_yaml(sbm: &Sbm) -> String {
+    serde_norway::to_string(sbm).expect("an Sbm is always valid YAML")
+}
+
+/// Read an `Sbm` from YAML
+pub fn from_yaml(yaml: &str) -> Result<Sbm, Error> {
+    serde_norway::from_str(yaml).map_err(Error::Yaml)
+}
+
+/// Write an `Sbm` as TOML, with the categories under `categories`
+///
+/// # Examples
+///
+/// ```
+/// use sbm::{convert, Sbm};
+/// let sbm: Sbm = "#Web|🌐\nMDN|Docs|https://developer.mozilla.org/".parse().unwrap();
+/// let toml = convert::to_toml(&sbm);
+/// assert!(toml.contains("[[categories]]"));
+/// assert_eq!(convert::from_toml(&toml).unwrap(), sbm);
+/// ```
+pub fn to_toml(sbm: &Sbm) -> String {
+    let document = TomlDocument { categories: &sbm.0 };
+    toml::to_string(&document).expect("an Sbm is always valid TOML")
+}
+
+/// Read an `Sbm` from TOML
+pub fn from_toml(toml: &str) -> Result<Sbm, Error> {
+    let document: TomlDocument<Vec<Category>> = toml::from_str(toml).map_err(Error::Toml)?;
+    Ok(Sbm(document.categories))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bookmark, Header};
+
+    fn sample() -> Sbm {
+        Sbm(vec![
+            Category {
+                header: Header::new("Languages", Some("👨‍💻")),
+                bookmarks: vec![Bookmark::new(
+                    "Rust",
+                    "Pipes | \"quotes\"",
+                    "https://www.rust-lang.org/",
+                )
+                .with_tags(["lang"])
+                .with_metadata("added", "2024-01-31")],
+                children: vec![Category {
+                    bookmarks: vec![Bookmark::new("serde", "", "https://serde.rs/")],
+                    ..Category::new(Header::new("Crates", None))
+                }],
+            },
+            Category::new(Header::new("Empty", None)),
+        ])
+    }
+
+    #[test]
+    fn test_json_shape() {
+        let value: serde_json::Value = serde_json::from_str(&to_json(&sample())).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([
+                {
+                    "header": { "name": "Languages", "icon": "👨‍💻" },
+                    "bookmarks": [{
+                        "name": "Rust",
+                        "description": "Pipes | \"quotes\"",
+                        "url": "https://www.rust-lang.org/",
+                        "tags": ["lang"],
+                        "metadata": { "added": "2024-01-31" }
+                    }],
+                    "children": [{
+                        "header": { "name": "Crates", "icon": null },
+                        "bookmarks": [
+                            { "name": "serde", "description": "", "url": "https://serde.rs/" }
+                        ],
+                        "children": []
+                    }]
+                },
+                {
+                    "header": { "name": "Empty", "icon": null },
+                    "bookmarks": [],
+                    "children": []
+                }
+            ])
+        );
+        assert_eq!(from_json(&to_json(&sample())).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_defaults() {
+        let sbm = from_json(
+            r#"[{"header": {"name": "Web"}}, {"header": {"name": "Go"}, "bookmarks": [{"name": "Go", "url": "https://go.dev/"}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(sbm.0[0], Category::new(Header::new("Web", None)));
+        assert_eq!(
+            sbm.0[1].bookmarks,
+            vec![Bookmark::new("Go", "", "https://go.dev/")]
+        );
+    }
+
+    #[test]
+    fn test_yaml_and_toml() {
+        assert_eq!(from_yaml(&to_yaml(&sample())).unwrap(), sample());
+        assert_eq!(from_toml(&to_toml(&sample())).unwrap(), sample());
+        assert_eq!(from_toml("categories = []").unwrap(), Sbm(vec![]));
+        assert!(from_toml("[[categories]]\nname = 1").is_err());
+        assert!(matches!(from_yaml("- header: 3"), Err(Error::Yaml(_))));
+        assert!(from_json("{}")
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid JSON"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Duplicate bookmarks
+//!
+//! Files merged from several sources often hold the same link more than
+//! once, spelled slightly differently. Two bookmarks are duplicates when
+//! their URLs have the same [`url::key`], wherever they are filed.
+//! [`Sbm::duplicates`] reports them and [`Sbm::dedupe`] removes all but one
+//! copy of each, following a [`Policy`].
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::dedupe::Policy;
+//! use sbm::Sbm;
+//! let mut sbm: Sbm = "#Languages\nRust|Home page|https://www.rust-lang.org/\n#Work\nRust|The Rust Programming Language|http://rust-lang.org"
+//!     .parse()
+//!     .unwrap();
+//! let duplicates = sbm.duplicates();
+//! assert_eq!(duplicates[0].copies[1].path, vec!["Work"]);
+//!
+//! assert_eq!(sbm.dedupe(Policy::LongestDescription), 1);
+//! assert!(sbm.categories()[0].bookmarks.is_empty());
+//! assert_eq!(sbm.categories()[1].bookmarks[0].url, "http://rust-lang.org");
+//! ```
+
+use crate::{url, Bookmark, Category, Sbm};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// Where one copy of a duplicated bookmark is filed
+#[derive(Debug, PartialEq, Clone)]
+pub struct Location<'a> {
+    /// Names of the categories the copy is filed under, from the top level
+    /// down to its own category
+    pub path: Vec<&'a str>,
+    /// Position of the copy among its category's bookmarks
+    pub index: usize,
+    pub bookmark: &'a Bookmark,
+}
+
+/// Bookmarks sharing a normalized URL
+#[derive(Debug, PartialEq, Clone)]
+pub struct Duplicates<'a> {
+    /// The [`url::key`] they share
+    pub url: String,
+    /// Every copy, in file order
+    pub copies: Vec<Location<'a>>,
+}
+
+/// Which copy of a duplicated bookmark [`Sbm::dedupe`] keeps
+///
+/// Whatever the policy, the kept copy also gets the tags and metadata keys
+/// of the copies that are removed, so those are never lost.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Policy {
+    /// Keep the first copy in file order
+    #[default]
+    KeepFirst,
+    /// Keep the copy with the longest description, the first of those if
+    /// several are as long
+    LongestDescription,
+    /// Keep the first copy, with the different descriptions of all copies
+    /// joined by `; `
+    MergeDescriptions,
+}
+
+/// Every group of duplicates in `sbm`, in the order their first copies appear
+pub fn find(sbm: &Sbm) -> Vec<Duplicates<'_>> {
+    let mut groups: Vec<Duplicates> = Vec::new();
+    let mut by_url = HashMap::new();
+    let mut path = Vec::new();
+    for (depth, category) in sbm.walk() {
+        path.truncate(depth - 1);
+        path.push(category.header.name.as_str());
+        for (index, bookmark) in category.bookmarks.iter().enumerate() {
+            let url = url::key(&bookmark.url);
+            let group = *by_url.entry(url.clone()).or_insert_with(|| {
+                groups.push(Duplicates {
+                    url,
+                    copies: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            groups[group].copies.push(Location {
+                path: path.clone(),
+                index,
+                bookmark,
+            });
+        }
+    }
+    groups.retain(|group| group.copies.len() > 1);
+    groups
+}
+
+/// Decide what becomes of each bookmark when deduplicating
+///
+/// Takes bookmarks in file order and returns, for each of them, the bookmark
+/// to put in its place, or `None` if it is a copy to remove. This lets
+/// callers dedupe structures other than [`Sbm`], such as a
+/// [`Document`](crate::document::Document).
+pub fn resolve<'a, I>(bookmarks: I, policy: Policy) -> Vec<Option<Bookmark>>
+where
+    I: IntoIterator<Item = &'a Bookmark>,
+{
+    let bookmarks: Vec<&Bookmark> = bookmarks.into_iter().collect();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut by_url = HashMap::new();
+    for (i, bookmark) in bookmarks.iter().enumerate() {
+        let group = *by_url.entry(url::key(&bookmark.url)).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+
+    let mut plan = vec![None; bookmarks.len()];
+    for group in groups {
+        let copies: Vec<&Bookmark> = group.iter().map(|&i| bookmarks[i]).collect();
+        let (keep, bookmark) = merge(&copies, policy);
+        plan[group[keep]] = Some(bookmark);
+    }
+    plan
+}
+
+/// The copy to keep and what it becomes
+fn merge(copies: &[&Bookmark], policy: Policy) -> (usize, Bookmark) {
+    let keep = match policy {
+        Policy::LongestDescription => copies
+            .iter()
+            .enumerate()
+            .max_by_key(|(i, b)| (b.description.chars().count(), Reverse(*i)))
+            .map_or(0, |(i, _)| i),
+        Policy::KeepFirst | Policy::MergeDescriptions => 0,
+    };
+    let mut kept = copies[keep].clone();
+    if policy == Policy::MergeDescriptions {
+        let mut descriptions: Vec<&str> = Vec::new();
+        for copy in copies {
+            if !copy.description.is_empty() && !descriptions.contains(&copy.description.as_str()) {
+                descriptions.push(&copy.description);
+            }
+        }
+        kept.description = descriptions.join("; ");
+    }
+    for copy in copies {
+        for tag in &copy.tags {
+            if !kept.tags.contains(tag) {
+                kept.tags.push(tag.clone());
+            }
+        }
+        for (key, value) in &copy.metadata {
+            kept.metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+    (keep, kept)
+}
+
+/// Replace the bookmarks of `categories` and their children, in file order,
+/// with the results of [`resolve`]
+pub(crate) fn apply<I>(categories: &mut [Category], plan: &mut I)
+where
+    I: Iterator<Item = Option<Bookmark>>,
+{
+    for category in categories {
+        let count = category.bookmarks.len();
+        category.bookmarks = plan.by_ref().take(count).flatten().collect();
+        apply(&mut category.children, plan);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "#Languages\nRust|Home|https://www.rust-lang.org/|lang\n##Docs\nBook|The book|https://doc.rust-lang.org/book/\n#Work\nRust|The Rust Programming Language|http://rust-lang.org?utm_source=feed|work\nBook|The book|https://doc.rust-lang.org/book\nZig|Zig|https://ziglang.org/\n#Home\nRust||https://rust-lang.org/#\n";
+
+    #[test]
+    fn test_find() {
+        let sbm: Sbm = DATA.parse().unwrap();
+        let groups = find(&sbm);
+        assert_eq!(groups.len(), 2);
+        let where_: Vec<(Vec<&str>, usize)> = groups[0]
+            .copies
+            .iter()
+            .map(|c| (c.path.clone(), c.index))
+            .collect();
+        assert_eq!(
+            where_,
+            vec![(vec!["Languages"], 0), (vec!["Work"], 0), (vec!["Home"], 0)]
+        );
+        assert_eq!(groups[1].url, "//doc.rust-lang.org/book");
+        assert_eq!(groups[1].copies[0].path, vec!["Languages", "Docs"]);
+        assert_eq!(groups[1].copies[1].index, 1);
+    }
+
+    #[test]
+    fn test_policies() {
+        let original: Sbm = DATA.parse().unwrap();
+        let rust = |sbm: &Sbm| sbm.bookmarks_named("Rust").next().unwrap().1.clone();
+
+        let mut sbm = original.clone();
+        assert_eq!(sbm.dedupe(Policy::KeepFirst), 3);
+        assert!(find(&sbm).is_empty());
+        assert_eq!(sbm.dedupe(Policy::KeepFirst), 0);
+        let kept = rust(&sbm);
+        assert_eq!(kept.description, "Home");
+        assert_eq!(kept.tags, vec!["lang", "work"]);
+        assert_eq!(sbm.find(&["Work"]).unwrap().bookmarks.len(), 1);
+
+        let mut sbm = original.clone();
+        sbm.dedupe(Policy::LongestDescription);
+        assert!(sbm.find(&["Languages"]).unwrap().bookmarks.is_empty());
+        let kept = rust(&sbm);
+        assert_eq!(kept.description, "The Rust Programming Language");
+        assert_eq!(kept.url, "http://rust-lang.org?utm_source=feed");
+
+        let mut sbm = original;
+        sbm.dedupe(Policy::MergeDescriptions);
+        assert_eq!(
+            rust(&sbm).description,
+            "Home; The Rust Programming Language"
+        );
+        // identical descriptions are not repeated
+        assert_eq!(
+            sbm.find(&["Languages", "Docs"]).unwrap().bookmarks[0].description,
+            "The book"
+        );
+    }
+}
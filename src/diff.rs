@@ -0,0 +1,497 @@
+//! Differences between two files
+//!
+//! A line-by-line diff of two files is noisy once bookmarks are reordered.
+//! [`Sbm::diff`] compares the structure instead. It pairs categories with
+//! the category of the same name under the same parent. Failing that, it
+//! treats a category as renamed when at least half of its bookmarks, counting
+//! subcategories, are in a category of the other file under the same parent.
+//! Bookmarks are paired by [`url::key`], preferring a copy in the paired
+//! category, and a paired bookmark in a category that isn't paired with its
+//! old one has moved.
+//!
+//! A [`Change`] displays as a line for people; [`Change::tsv`] is a line for
+//! other programs.
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::Sbm;
+//! let old: Sbm = "#Languages\nRust|Home page|https://www.rust-lang.org/\nZig|Zig|https://ziglang.org/".parse().unwrap();
+//! let new: Sbm = "#Work\nRust|The Rust Programming Language|https://rust-lang.org\n#Languages".parse().unwrap();
+//! let changes: Vec<String> = old.diff(&new).iter().map(|c| c.to_string()).collect();
+//! assert_eq!(
+//!     changes,
+//!     vec![
+//!         "+ category Work",
+//!         "- Languages: Zig <https://ziglang.org/>",
+//!         "> Rust <https://rust-lang.org>: Languages -> Work",
+//!         r#"~ Work: https://rust-lang.org: description "Home page" -> "The Rust Programming Language""#,
+//!     ]
+//! );
+//! ```
+
+use crate::{url, Bookmark, Category, Sbm};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// One difference between two files
+///
+/// Paths are the names of categories from the top level down. A bookmark
+/// that was both moved and renamed is reported as two changes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change<'a> {
+    /// A category only in the new file
+    AddedCategory { path: Vec<&'a str> },
+    /// A category only in the old file
+    RemovedCategory { path: Vec<&'a str> },
+    /// A category with a new name
+    RenamedCategory {
+        from: Vec<&'a str>,
+        to: Vec<&'a str>,
+    },
+    /// A bookmark only in the new file
+    Added {
+        path: Vec<&'a str>,
+        bookmark: &'a Bookmark,
+    },
+    /// A bookmark only in the old file
+    Removed {
+        path: Vec<&'a str>,
+        bookmark: &'a Bookmark,
+    },
+    /// A bookmark filed under another category, as it is in the new file
+    Moved {
+        from: Vec<&'a str>,
+        to: Vec<&'a str>,
+        bookmark: &'a Bookmark,
+    },
+    /// A bookmark with a new name, filed under `path` in the new file
+    Renamed {
+        path: Vec<&'a str>,
+        old: &'a Bookmark,
+        new: &'a Bookmark,
+    },
+    /// A bookmark with a new description, filed under `path` in the new file
+    Redescribed {
+        path: Vec<&'a str>,
+        old: &'a Bookmark,
+        new: &'a Bookmark,
+    },
+}
+
+impl Change<'_> {
+    /// A name for the kind of change, such as `added` or `renamed-category`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Change::AddedCategory { .. } => "added-category",
+            Change::RemovedCategory { .. } => "removed-category",
+            Change::RenamedCategory { .. } => "renamed-category",
+            Change::Added { .. } => "added",
+            Change::Removed { .. } => "removed",
+            Change::Moved { .. } => "moved",
+            Change::Renamed { .. } => "renamed",
+            Change::Redescribed { .. } => "redescribed",
+        }
+    }
+
+    /// The change as six tab-separated columns: the [`kind`](Change::kind),
+    /// the old and the new path with categories separated by ` / `, the URL,
+    /// and the old and the new value
+    ///
+    /// The values are bookmark names, except for [`Change::Redescribed`]
+    /// where they are descriptions. Columns that don't apply are empty.
+    /// Backslashes, tabs, line feeds and carriage returns in a column are
+    /// written as `\\`, `\t`, `\n` and `\r`, so every change is a single
+    /// line of exactly six columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let old: Sbm = "#Work\n##Infra\nGrafana|Dashboards|https://grafana.example.com/".parse().unwrap();
+    /// let new: Sbm = "#Work\n##Infrastructure\nGrafana|Dashboards|https://grafana.example.com/".parse().unwrap();
+    /// let changes = old.diff(&new);
+    /// assert_eq!(changes[0].tsv(), "renamed-category\tWork / Infra\tWork / Infrastructure\t\t\t");
+    /// ```
+    pub fn tsv(&self) -> String {
+        let none = Vec::new();
+        let (from, to, url, old, new) = match self {
+            Change::AddedCategory { path } => (&none, path, "", "", ""),
+            Change::RemovedCategory { path } => (path, &none, "", "", ""),
+            Change::RenamedCategory { from, to } => (from, to, "", "", ""),
+            Change::Added { path, bookmark } => (
+                &none,
+                path,
+                bookmark.url.as_str(),
+                "",
+                bookmark.name.as_str(),
+            ),
+            Change::Removed { path, bookmark } => (
+                path,
+                &none,
+                bookmark.url.as_str(),
+                bookmark.name.as_str(),
+                "",
+            ),
+            Change::Moved { from, to, bookmark } => (
+                from,
+                to,
+                bookmark.url.as_str(),
+                bookmark.name.as_str(),
+                bookmark.name.as_str(),
+            ),
+            Change::Renamed { path, old, new } => (
+                path,
+                path,
+                new.url.as_str(),
+                old.name.as_str(),
+                new.name.as_str(),
+            ),
+            Change::Redescribed { path, old, new } => (
+                path,
+                path,
+                new.url.as_str(),
+                old.description.as_str(),
+                new.description.as_str(),
+            ),
+        };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.kind(),
+            escape(&from.join(" / ")),
+            escape(&to.join(" / ")),
+            escape(url),
+            escape(old),
+            escape(new)
+        )
+    }
+}
+
+/// Escape a column of [`Change::tsv`]
+fn escape(column: &str) -> Cow<'_, str> {
+    if !column.contains(['\\', '\t', '\n', '\r']) {
+        return Cow::Borrowed(column);
+    }
+    let mut out = String::with_capacity(column.len() + 2);
+    for c in column.chars() {
+        match c {
+            '\\' => out.push_str(r"\\"),
+            '\t' => out.push_str(r"\t"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+impl std::fmt::Display for Change<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::AddedCategory { path } => write!(f, "+ category {}", path.join(" / ")),
+            Change::RemovedCategory { path } => write!(f, "- category {}", path.join(" / ")),
+            Change::RenamedCategory { from, to } => {
+                write!(f, "~ category {} -> {}", from.join(" / "), to.join(" / "))
+            }
+            Change::Added { path, bookmark } => write!(
+                f,
+                "+ {}: {} <{}>",
+                path.join(" / "),
+                bookmark.name,
+                bookmark.url
+            ),
+            Change::Removed { path, bookmark } => write!(
+                f,
+                "- {}: {} <{}>",
+                path.join(" / "),
+                bookmark.name,
+                bookmark.url
+            ),
+            Change::Moved { from, to, bookmark } => write!(
+                f,
+                "> {} <{}>: {} -> {}",
+                bookmark.name,
+                bookmark.url,
+                from.join(" / "),
+                to.join(" / ")
+            ),
+            Change::Renamed { path, old, new } => write!(
+                f,
+                "~ {}: {}: name {:?} -> {:?}",
+                path.join(" / "),
+                new.url,
+                old.name,
+                new.name
+            ),
+            Change::Redescribed { path, old, new } => write!(
+                f,
+                "~ {}: {}: description {:?} -> {:?}",
+                path.join(" / "),
+                new.url,
+                old.description,
+                new.description
+            ),
+        }
+    }
+}
+
+/// A category of a flattened file
+struct Node<'a> {
+    path: Vec<&'a str>,
+    /// Index of the parent category, if any
+    parent: Option<usize>,
+    category: &'a Category,
+    /// The keys of the URLs in the category and its subcategories
+    keys: HashSet<String>,
+}
+
+/// Every category of `sbm`, parents before their children
+fn flatten(sbm: &Sbm) -> Vec<Node<'_>> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut ancestors: Vec<usize> = Vec::new();
+    for (depth, category) in sbm.walk() {
+        ancestors.truncate(depth - 1);
+        let parent = ancestors.last().copied();
+        let mut path = parent.map_or_else(Vec::new, |p| nodes[p].path.clone());
+        path.push(category.header.name.as_str());
+        ancestors.push(nodes.len());
+        nodes.push(Node {
+            path,
+            parent,
+            category,
+            keys: category.all_bookmarks().map(|b| url::key(&b.url)).collect(),
+        });
+    }
+    nodes
+}
+
+/// For each old category, the index of the new category paired with it
+fn pair_categories(old: &[Node], new: &[Node]) -> Vec<Option<usize>> {
+    let mut paired = vec![None; old.len()];
+    let mut taken = vec![false; new.len()];
+    let mut parents = vec![(None, None)];
+    while let Some((old_parent, new_parent)) = parents.pop() {
+        let olds: Vec<usize> = (0..old.len())
+            .filter(|&o| old[o].parent == old_parent)
+            .collect();
+        let news: Vec<usize> = (0..new.len())
+            .filter(|&n| new[n].parent == new_parent)
+            .collect();
+        for &o in &olds {
+            let name = &old[o].category.header.name;
+            if let Some(&n) = news
+                .iter()
+                .find(|&&n| !taken[n] && new[n].category.header.name == *name)
+            {
+                paired[o] = Some(n);
+                taken[n] = true;
+            }
+        }
+        for &o in &olds {
+            if paired[o].is_some() {
+                continue;
+            }
+            let best = news
+                .iter()
+                .filter(|&&n| !taken[n])
+                .map(|&n| (old[o].keys.intersection(&new[n].keys).count(), n))
+                .max_by_key(|&(shared, n)| (shared, Reverse(n)));
+            if let Some((shared, n)) = best {
+                if shared > 0 && shared * 2 >= old[o].keys.len() {
+                    paired[o] = Some(n);
+                    taken[n] = true;
+                }
+            }
+        }
+        for o in olds {
+            if let Some(n) = paired[o] {
+                parents.push((Some(o), Some(n)));
+            }
+        }
+    }
+    paired
+}
+
+/// Every change from `old` to `new`, see [`Sbm::diff`]
+///
+/// Changes to categories come first: added and renamed ones in the order of
+/// the new file, then removed ones in the order of the old file. Removed
+/// bookmarks follow in the order of the old file, then the other changes to
+/// bookmarks in the order of the new file.
+pub fn diff<'a>(old: &'a Sbm, new: &'a Sbm) -> Vec<Change<'a>> {
+    let old_nodes = flatten(old);
+    let new_nodes = flatten(new);
+    let paired = pair_categories(&old_nodes, &new_nodes);
+    let mut pairs_of_new = vec![None; new_nodes.len()];
+    for (o, n) in paired.iter().enumerate() {
+        if let Some(n) = *n {
+            pairs_of_new[n] = Some(o);
+        }
+    }
+
+    let mut changes = Vec::new();
+    for (n, node) in new_nodes.iter().enumerate() {
+        match pairs_of_new[n] {
+            None => changes.push(Change::AddedCategory {
+                path: node.path.clone(),
+            }),
+            Some(o) if old_nodes[o].category.header.name != node.category.header.name => changes
+                .push(Change::RenamedCategory {
+                    from: old_nodes[o].path.clone(),
+                    to: node.path.clone(),
+                }),
+            Some(_) => {}
+        }
+    }
+    for (o, node) in old_nodes.iter().enumerate() {
+        if paired[o].is_none() {
+            changes.push(Change::RemovedCategory {
+                path: node.path.clone(),
+            });
+        }
+    }
+
+    let entries: Vec<(usize, &Bookmark)> = old_nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(o, node)| node.category.bookmarks.iter().map(move |b| (o, b)))
+        .collect();
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (e, (_, bookmark)) in entries.iter().enumerate() {
+        by_key.entry(url::key(&bookmark.url)).or_default().push(e);
+    }
+    let mut used = vec![false; entries.len()];
+    let mut updates = Vec::new();
+    for (n, node) in new_nodes.iter().enumerate() {
+        for new in &node.category.bookmarks {
+            let copies = by_key
+                .get(&url::key(&new.url))
+                .map_or(&[][..], Vec::as_slice);
+            let Some(&e) = copies
+                .iter()
+                .find(|&&e| !used[e] && paired[entries[e].0] == Some(n))
+                .or_else(|| copies.iter().find(|&&e| !used[e]))
+            else {
+                updates.push(Change::Added {
+                    path: node.path.clone(),
+                    bookmark: new,
+                });
+                continue;
+            };
+            used[e] = true;
+            let (o, old) = entries[e];
+            if paired[o] != Some(n) {
+                updates.push(Change::Moved {
+                    from: old_nodes[o].path.clone(),
+                    to: node.path.clone(),
+                    bookmark: new,
+                });
+            }
+            if old.name != new.name {
+                updates.push(Change::Renamed {
+                    path: node.path.clone(),
+                    old,
+                    new,
+                });
+            }
+            if old.description != new.description {
+                updates.push(Change::Redescribed {
+                    path: node.path.clone(),
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+    for (e, &(o, bookmark)) in entries.iter().enumerate() {
+        if !used[e] {
+            changes.push(Change::Removed {
+                path: old_nodes[o].path.clone(),
+                bookmark,
+            });
+        }
+    }
+    changes.extend(updates);
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Header;
+
+    const OLD: &str = "#Work\nJira|Issues|https://jira.example.com/\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\nKibana|Logs|https://kibana.example.com/\n#Languages\nRust|Home|https://www.rust-lang.org/\nGo|Go|https://go.dev/\n#Archive\nOld|Old|https://old.example.com/\n";
+    const NEW: &str = "#Languages\nGo|The Go Programming Language|https://go.dev\nZig|Zig|https://ziglang.org/\n#Work\n##Infrastructure\nKibana|Logs|https://kibana.example.com/\nGrafana|Dashboards|https://grafana.example.com/\nRust at work|Home|http://rust-lang.org/\n##Docs\nJira|Issues|https://jira.example.com/\n";
+
+    #[test]
+    fn test_diff() {
+        let old: Sbm = OLD.parse().unwrap();
+        let new: Sbm = NEW.parse().unwrap();
+        let changes: Vec<String> = diff(&old, &new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                "~ category Work / Infra -> Work / Infrastructure",
+                "+ category Work / Docs",
+                "- category Archive",
+                "- Archive: Old <https://old.example.com/>",
+                r#"~ Languages: https://go.dev: description "Go" -> "The Go Programming Language""#,
+                "+ Languages: Zig <https://ziglang.org/>",
+                "> Rust at work <http://rust-lang.org/>: Languages -> Work / Infrastructure",
+                r#"~ Work / Infrastructure: http://rust-lang.org/: name "Rust" -> "Rust at work""#,
+                "> Jira <https://jira.example.com/>: Work -> Work / Docs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tsv() {
+        let old: Sbm = OLD.parse().unwrap();
+        let new: Sbm = NEW.parse().unwrap();
+        let rows: Vec<String> = diff(&old, &new).iter().map(Change::tsv).collect();
+        assert_eq!(rows[2], "removed-category\tArchive\t\t\t\t");
+        assert_eq!(
+            rows[3],
+            "removed\tArchive\t\thttps://old.example.com/\tOld\t"
+        );
+        assert_eq!(
+            rows[6],
+            "moved\tLanguages\tWork / Infrastructure\thttp://rust-lang.org/\tRust at work\tRust at work"
+        );
+        assert_eq!(
+            rows[7],
+            "renamed\tWork / Infrastructure\tWork / Infrastructure\thttp://rust-lang.org/\tRust\tRust at work"
+        );
+    }
+
+    #[test]
+    fn test_tsv_escaping() {
+        let file = |name: &str, description: &str| {
+            let mut category = Category::new(Header::new("C:\\Work", None));
+            category
+                .bookmarks
+                .push(Bookmark::new(name, description, "https://example.com/"));
+            Sbm::new(vec![category])
+        };
+        let old = file("Tab\there", "one line");
+        let new = file("Tab\there too", "two\r\nlines");
+        let rows: Vec<String> = diff(&old, &new).iter().map(Change::tsv).collect();
+        assert_eq!(
+            rows,
+            vec![
+                r"renamed	C:\\Work	C:\\Work	https://example.com/	Tab\there	Tab\there too",
+                r"redescribed	C:\\Work	C:\\Work	https://example.com/	one line	two\r\nlines",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let old: Sbm = OLD.parse().unwrap();
+        let mut new = old.clone();
+        new.0.reverse();
+        new.0[1].bookmarks.reverse();
+        assert!(diff(&old, &new).is_empty());
+    }
+}
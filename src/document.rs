@@ -0,0 +1,743 @@
+//! Lossless representation of an SBM file
+//!
+//! [`Document`] keeps every line of the source, including comments, blank
+//! lines, spacing and line endings, so that printing a freshly parsed document
+//! gives back exactly the bytes it was parsed from. Headers and bookmarks are
+//! wrapped in a [`Node`] which remembers the text it was parsed from; a node is
+//! only re-encoded once its value has been changed, so edits made through this
+//! API only touch the lines they affect. `//@` annotation lines belong to the
+//! node below them, and are part of its source text.
+
+use crate::borrowed::{HeaderAt, HeaderRef};
+use crate::merge::{Conflict, Resolution};
+use crate::parser::{self, Depths, LineKind, Nesting, Orphans, ParseError, ParseOptions};
+use crate::{Bookmark, Category, Header, Sbm};
+use std::collections::BTreeMap;
+
+/// A parsed value along with the source text it came from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Node<T> {
+    /// The current value
+    pub value: T,
+    // boxed, as most nodes in a document are never edited
+    source: Option<Box<(T, String)>>,
+    ending: Option<String>,
+}
+
+impl<T> Node<T> {
+    /// Create a node that has no source text
+    ///
+    /// It will be written out with the value's `Display` impl.
+    pub fn new(value: T) -> Node<T> {
+        Node {
+            value,
+            source: None,
+            ending: None,
+        }
+    }
+}
+
+impl<T: Clone> Node<T> {
+    fn parsed(value: T, raw: &str, ending: &str) -> Node<T> {
+        Node {
+            source: Some(Box::new((value.clone(), raw.to_string()))),
+            value,
+            ending: Some(ending.to_string()),
+        }
+    }
+}
+
+impl<T: PartialEq + std::fmt::Display> Node<T> {
+    /// The text this node will be written as, without its line ending
+    ///
+    /// This includes any annotation lines above the entry.
+    pub fn text(&self) -> String {
+        match self.source.as_deref() {
+            Some((original, raw)) if *original == self.value => raw.clone(),
+            _ => self.value.to_string(),
+        }
+    }
+
+    /// Whether the value has changed since it was parsed
+    pub fn is_modified(&self) -> bool {
+        !matches!(self.source.as_deref(), Some((original, _)) if *original == self.value)
+    }
+}
+
+/// A line that carries no data: a comment or a blank line
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    /// The text of the line, without its line ending
+    pub text: String,
+    ending: Option<String>,
+}
+
+impl Trivia {
+    /// Create a comment line; `//` is prepended to `text`
+    pub fn comment(text: &str) -> Trivia {
+        Trivia {
+            text: format!("//{}", text),
+            ending: None,
+        }
+    }
+
+    /// Create a blank line
+    pub fn blank() -> Trivia {
+        Trivia {
+            text: String::new(),
+            ending: None,
+        }
+    }
+
+    /// Whether this line is a `//` comment
+    pub fn is_comment(&self) -> bool {
+        self.text.starts_with("//")
+    }
+}
+
+/// A line inside a category
+#[derive(Debug, PartialEq, Clone)]
+pub enum Item {
+    Bookmark(Node<Bookmark>),
+    Trivia(Trivia),
+}
+
+impl Item {
+    /// Add the lines of this item to `lines`, see [`push_lines`]
+    fn push_lines<'a>(&'a self, lines: &mut Vec<(String, Option<&'a str>)>) {
+        match self {
+            Item::Bookmark(node) => {
+                push_lines(lines, node.text(), node.is_modified(), &node.ending)
+            }
+            Item::Trivia(trivia) => lines.push((trivia.text.clone(), trivia.ending.as_deref())),
+        }
+    }
+}
+
+/// Add the text of a node to `lines`, along with its line ending if known
+///
+/// Source text keeps the line endings of its annotation lines; re-encoded
+/// text is split into lines so that each gets the document's line ending.
+fn push_lines<'a>(
+    lines: &mut Vec<(String, Option<&'a str>)>,
+    text: String,
+    modified: bool,
+    ending: &'a Option<String>,
+) {
+    if modified && text.contains('\n') {
+        let mut split: Vec<&str> = text.split('\n').collect();
+        let last = split.pop().unwrap_or_default().to_string();
+        lines.extend(split.into_iter().map(|line| (line.to_string(), None)));
+        lines.push((last, ending.as_deref()));
+    } else {
+        lines.push((text, ending.as_deref()));
+    }
+}
+
+/// A category along with the lines that follow its header
+///
+/// Subcategories are not stored inside their parent but follow it in
+/// [`Document::categories`], as they do in the file.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DocumentCategory {
+    pub header: Node<Header>,
+    /// Number of `#`s the header starts with, 1 for a top-level category
+    pub depth: usize,
+    pub items: Vec<Item>,
+}
+
+impl DocumentCategory {
+    /// Create an empty top-level category with a new header
+    pub fn new(header: Header) -> DocumentCategory {
+        DocumentCategory {
+            header: Node::new(header),
+            depth: 1,
+            items: Vec::new(),
+        }
+    }
+
+    /// The text the header will be written as, without its line ending
+    pub fn header_text(&self) -> String {
+        if self.header.is_modified() {
+            let header = HeaderRef::from(&self.header.value);
+            HeaderAt(&header, self.depth.max(1)).to_string()
+        } else {
+            self.header.text()
+        }
+    }
+
+    /// Iterate over the bookmarks in this category
+    pub fn bookmarks(&self) -> impl Iterator<Item = &Bookmark> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Bookmark(node) => Some(&node.value),
+            Item::Trivia(_) => None,
+        })
+    }
+
+    /// Iterate mutably over the bookmarks in this category
+    pub fn bookmarks_mut(&mut self) -> impl Iterator<Item = &mut Bookmark> {
+        self.items.iter_mut().filter_map(|item| match item {
+            Item::Bookmark(node) => Some(&mut node.value),
+            Item::Trivia(_) => None,
+        })
+    }
+
+    /// Add a bookmark directly after the last bookmark of the category
+    ///
+    /// Comments and blank lines trailing the category stay where they are.
+    pub fn push_bookmark(&mut self, bookmark: Bookmark) {
+        let index = self
+            .items
+            .iter()
+            .rposition(|item| matches!(item, Item::Bookmark(_)))
+            .map_or(0, |i| i + 1);
+        self.items
+            .insert(index, Item::Bookmark(Node::new(bookmark)));
+    }
+
+    /// Remove every bookmark matching `f`, returning how many were removed
+    pub fn remove_bookmarks<F: FnMut(&Bookmark) -> bool>(&mut self, mut f: F) -> usize {
+        let before = self.items.len();
+        self.items
+            .retain(|item| !matches!(item, Item::Bookmark(node) if f(&node.value)));
+        before - self.items.len()
+    }
+
+    /// Convert into a plain [`Category`], dropping comments and layout
+    ///
+    /// The result has no children; use [`Document::to_sbm`] to get the tree.
+    pub fn to_category(&self) -> Category {
+        Category {
+            bookmarks: self.bookmarks().cloned().collect(),
+            ..Category::new(self.header.value.clone())
+        }
+    }
+}
+
+/// A lossless SBM document
+///
+/// # Examples
+///
+/// ```
+/// use sbm::document::Document;
+/// let data = "// my links\n# Rust | 🦀\nDocs | The book | https://doc.rust-lang.org/book/\n";
+/// let mut doc = Document::parse(data).unwrap();
+/// assert_eq!(doc.to_string(), data);
+///
+/// doc.categories[0].header.value.icon = None;
+/// assert_eq!(
+///     doc.to_string(),
+///     "// my links\n#Rust\nDocs | The book | https://doc.rust-lang.org/book/\n"
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Document {
+    /// Lines before the first category header
+    pub preamble: Vec<Item>,
+    pub categories: Vec<DocumentCategory>,
+    newline: Option<String>,
+    trailing_newline: bool,
+}
+
+impl Document {
+    /// Parse a document, keeping all comments, blank lines and spacing
+    ///
+    /// Like [`parser::parse_categories`], bookmarks before the first header
+    /// are rejected.
+    pub fn parse(data: &str) -> Result<Document, ParseError> {
+        Document::parse_with(data, &ParseOptions::default())
+    }
+
+    /// Parse a document with the given options
+    ///
+    /// Bookmarks allowed before the first header are kept in the preamble.
+    pub fn parse_with(data: &str, options: &ParseOptions) -> Result<Document, ParseError> {
+        let mut doc = Document {
+            trailing_newline: data.is_empty() || data.ends_with('\n'),
+            ..Document::default()
+        };
+
+        let mut depths = Depths::new();
+        // annotation lines waiting for the entry they belong to
+        let mut annotations = Annotations::default();
+        for (index, (offset, line, ending)) in parser::lines(data).enumerate() {
+            if doc.newline.is_none() && !ending.is_empty() {
+                doc.newline = Some(ending.to_string());
+            }
+            let located = |e: ParseError| e.at(index + 1, offset, line);
+            match parser::classify(line) {
+                LineKind::Blank | LineKind::Comment => {
+                    doc.flush(&mut annotations);
+                    let trivia = Trivia {
+                        text: line.to_string(),
+                        ending: Some(ending.to_string()),
+                    };
+                    doc.items_mut().push(Item::Trivia(trivia));
+                }
+                LineKind::Annotation { key, value } => {
+                    annotations.push(&key, &value, line, ending);
+                }
+                LineKind::Header { depth, text } => {
+                    let (depth, text, _) = depths.place(line, depth, text);
+                    let header = Header {
+                        metadata: std::mem::take(&mut annotations.metadata),
+                        ..parser::parse_header(text).map_err(located)?
+                    };
+                    let raw = annotations.take_raw(line);
+                    doc.categories.push(DocumentCategory {
+                        header: Node::parsed(header, &raw, ending),
+                        depth,
+                        items: Vec::new(),
+                    });
+                }
+                LineKind::Bookmark(text) => {
+                    let bookmark = Bookmark {
+                        metadata: std::mem::take(&mut annotations.metadata),
+                        ..parser::parse_bookmark(text).map_err(located)?
+                    };
+                    options.check_url(&bookmark.url, index + 1, offset, line)?;
+                    if doc.categories.is_empty() && options.orphans == Orphans::Reject {
+                        return Err(parser::orphan(index + 1, offset, line));
+                    }
+                    let raw = annotations.take_raw(line);
+                    doc.items_mut()
+                        .push(Item::Bookmark(Node::parsed(bookmark, &raw, ending)));
+                }
+            }
+        }
+        doc.flush(&mut annotations);
+
+        Ok(doc)
+    }
+
+    /// Keep annotations that no entry follows as plain comments
+    fn flush(&mut self, annotations: &mut Annotations) {
+        let lines = std::mem::take(&mut annotations.lines);
+        annotations.metadata.clear();
+        self.items_mut()
+            .extend(lines.into_iter().map(|(text, ending)| {
+                Item::Trivia(Trivia {
+                    text,
+                    ending: Some(ending),
+                })
+            }));
+    }
+
+    /// Add a category at the end of the document
+    pub fn push_category(&mut self, header: Header) -> &mut DocumentCategory {
+        self.categories.push(DocumentCategory::new(header));
+        self.categories.last_mut().unwrap()
+    }
+
+    /// Add a category as the last child of the category at index `parent`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::document::Document;
+    /// use sbm::Header;
+    /// let mut doc = Document::parse("#Work\n##Infra\n#Home\n").unwrap();
+    /// let work = doc.find(&["Work"]).unwrap();
+    /// doc.push_subcategory(work, Header::new("Docs", None));
+    /// assert_eq!(doc.to_string(), "#Work\n##Infra\n##Docs\n#Home\n");
+    /// assert_eq!(doc.find(&["Work", "Docs"]), Some(2));
+    /// ```
+    pub fn push_subcategory(&mut self, parent: usize, header: Header) -> &mut DocumentCategory {
+        let index = self.subtree_end(parent);
+        let category = DocumentCategory {
+            depth: self.categories[parent].depth + 1,
+            ..DocumentCategory::new(header)
+        };
+        self.categories.insert(index, category);
+        &mut self.categories[index]
+    }
+
+    /// Find the first category with the given name, at any depth
+    pub fn category_mut(&mut self, name: &str) -> Option<&mut DocumentCategory> {
+        self.categories
+            .iter_mut()
+            .find(|c| c.header.value.name == name)
+    }
+
+    /// The index of a category, found by the names along its path from the
+    /// top level
+    pub fn find(&self, path: &[&str]) -> Option<usize> {
+        let parents = self.parents();
+        let mut found = None;
+        for name in path {
+            found =
+                Some((0..self.categories.len()).find(|&i| {
+                    parents[i] == found && self.categories[i].header.value.name == *name
+                })?);
+        }
+        found
+    }
+
+    /// The index of each category's parent, `None` for top-level ones
+    fn parents(&self) -> Vec<Option<usize>> {
+        let mut open: Vec<usize> = Vec::new();
+        self.categories
+            .iter()
+            .enumerate()
+            .map(|(i, category)| {
+                while open
+                    .last()
+                    .is_some_and(|&j| self.categories[j].depth >= category.depth)
+                {
+                    open.pop();
+                }
+                let parent = open.last().copied();
+                open.push(i);
+                parent
+            })
+            .collect()
+    }
+
+    /// The index just past the last descendant of the category at `index`
+    fn subtree_end(&self, index: usize) -> usize {
+        let depth = self.categories[index].depth;
+        self.categories[index + 1..]
+            .iter()
+            .position(|c| c.depth <= depth)
+            .map_or(self.categories.len(), |i| index + 1 + i)
+    }
+
+    /// Convert into a plain [`Sbm`], dropping comments and layout
+    ///
+    /// Bookmarks in the preamble end up in an implicit
+    /// [`parser::UNCATEGORIZED`] category at the front, or at the front of
+    /// the document's own top-level category of that name.
+    pub fn to_sbm(&self) -> Sbm {
+        let orphans: Vec<Bookmark> = self
+            .preamble
+            .iter()
+            .filter_map(|item| match item {
+                Item::Bookmark(node) => Some(node.value.clone()),
+                Item::Trivia(_) => None,
+            })
+            .collect();
+        let mut categories = self.tree();
+        if !orphans.is_empty() {
+            match categories
+                .iter_mut()
+                .find(|c| c.header.name == parser::UNCATEGORIZED)
+            {
+                Some(existing) => {
+                    existing.bookmarks.splice(0..0, orphans);
+                }
+                None => categories.insert(
+                    0,
+                    Category {
+                        bookmarks: orphans,
+                        ..Category::new(Header::new(parser::UNCATEGORIZED, None))
+                    },
+                ),
+            }
+        }
+        Sbm(categories)
+    }
+
+    /// The categories as a tree, without the preamble
+    fn tree(&self) -> Vec<Category> {
+        let mut nesting = Nesting::new(|parent: &mut Category, child| parent.children.push(child));
+        for category in &self.categories {
+            nesting.open(category.depth, category.to_category());
+        }
+        nesting.finish()
+    }
+
+    /// Merge `other` into the document the way [`Sbm::merge`] does, and
+    /// return the conflicts
+    ///
+    /// Conflicts are resolved as `resolution` says. Only the headers and
+    /// bookmarks that change are re-encoded; new categories and bookmarks are
+    /// added after the existing ones. Bookmarks in the preamble are left out
+    /// of the merge.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::document::Document;
+    /// use sbm::merge::Resolution;
+    /// use sbm::Sbm;
+    /// let mut doc = Document::parse("# Web\n// docs\nMDN |  | https://developer.mozilla.org/\n").unwrap();
+    /// let other: Sbm = "#Web|🌐\nMDN|Docs|https://developer.mozilla.org\n#News".parse().unwrap();
+    /// assert!(doc.merge(&other, Resolution::Ours).is_empty());
+    /// assert_eq!(doc.to_string(), "#Web|🌐\n// docs\nMDN|Docs|https://developer.mozilla.org/\n#News\n");
+    /// ```
+    pub fn merge(&mut self, other: &Sbm, resolution: Resolution) -> Vec<Conflict> {
+        let ours = Sbm(self.tree());
+        let depths: Vec<usize> = ours.walk().map(|(depth, _)| depth).collect();
+        let merge = ours.merge(other);
+        let conflicts = merge.conflicts.clone();
+        let merged = merge.resolve(resolution);
+
+        // the merged tree holds the categories of the document in order, with
+        // new ones after their siblings, and the bookmarks of each in order,
+        // with new ones at the end
+        let mut existing = depths.into_iter().peekable();
+        let mut ancestors: Vec<usize> = Vec::new();
+        for (index, (depth, category)) in merged.walk().enumerate() {
+            ancestors.truncate(depth - 1);
+            if existing.next_if_eq(&depth).is_none() {
+                let header = category.header.clone();
+                match ancestors.last() {
+                    Some(&parent) => self.push_subcategory(parent, header),
+                    None => self.push_category(header),
+                };
+            }
+            let target = &mut self.categories[index];
+            if target.header.value != category.header {
+                target.header.value = category.header.clone();
+            }
+            let mut bookmarks = category.bookmarks.iter();
+            for (old, new) in target.bookmarks_mut().zip(bookmarks.by_ref()) {
+                if old != new {
+                    *old = new.clone();
+                }
+            }
+            for bookmark in bookmarks {
+                target.push_bookmark(bookmark.clone());
+            }
+            ancestors.push(index);
+        }
+        conflicts
+    }
+
+    /// The items of the last category, or the preamble if there is none yet
+    fn items_mut(&mut self) -> &mut Vec<Item> {
+        match self.categories.last_mut() {
+            Some(c) => &mut c.items,
+            None => &mut self.preamble,
+        }
+    }
+
+    fn newline(&self) -> &str {
+        self.newline.as_deref().unwrap_or("\n")
+    }
+}
+
+/// Annotation lines read but not yet attached to an entry
+#[derive(Default)]
+struct Annotations {
+    metadata: BTreeMap<String, String>,
+    lines: Vec<(String, String)>,
+}
+
+impl Annotations {
+    fn push(&mut self, key: &str, value: &str, line: &str, ending: &str) {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self.lines.push((line.to_string(), ending.to_string()));
+    }
+
+    /// The source text of the entry on `line`, starting with its annotations
+    fn take_raw(&mut self, line: &str) -> String {
+        let mut raw: String = self
+            .lines
+            .drain(..)
+            .map(|(text, ending)| text + &ending)
+            .collect();
+        raw.push_str(line);
+        raw
+    }
+}
+
+impl From<&Sbm> for Document {
+    fn from(sbm: &Sbm) -> Document {
+        Document {
+            preamble: Vec::new(),
+            categories: sbm
+                .walk()
+                .map(|(depth, c)| DocumentCategory {
+                    header: Node::new(c.header.clone()),
+                    depth,
+                    items: c
+                        .bookmarks
+                        .iter()
+                        .map(|b| Item::Bookmark(Node::new(b.clone())))
+                        .collect(),
+                })
+                .collect(),
+            newline: None,
+            trailing_newline: true,
+        }
+    }
+}
+
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut lines: Vec<(String, Option<&str>)> = Vec::new();
+        for item in &self.preamble {
+            item.push_lines(&mut lines);
+        }
+        for category in &self.categories {
+            push_lines(
+                &mut lines,
+                category.header_text(),
+                category.header.is_modified(),
+                &category.header.ending,
+            );
+            for item in &category.items {
+                item.push_lines(&mut lines);
+            }
+        }
+
+        let last = lines.len().saturating_sub(1);
+        for (i, (text, ending)) in lines.iter().enumerate() {
+            let ending = match ending {
+                Some(ending) if !ending.is_empty() || i == last => ending,
+                _ if i == last && !self.trailing_newline => "",
+                _ => self.newline(),
+            };
+            write!(f, "{}{}", text, ending)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "// Team bookmarks\r\n\r\n# Programming Languages | 👨‍💻\r\nRust   | The Rust Programming Language | https://www.rust-lang.org/\r\n// TODO: add Zig\r\nPython | Python Programming Language   | https://www.python.org/\r\n\r\n#Web Development\r\nMDN|Web documentation|https://developer.mozilla.org/";
+
+    #[test]
+    fn test_roundtrip() {
+        let doc = Document::parse(DATA).unwrap();
+        assert_eq!(doc.to_string(), DATA);
+        assert_eq!(doc.to_sbm().0, parser::parse_categories(DATA).unwrap());
+
+        for data in ["", "\n", "  \n//x", "#A\n\n\n"] {
+            assert_eq!(Document::parse(data).unwrap().to_string(), data);
+        }
+    }
+
+    #[test]
+    fn test_orphans() {
+        let data = "a|b|c\n\n#A\nd|e|f\n";
+        assert!(Document::parse(data).is_err());
+
+        let doc = Document::parse_with(data, &ParseOptions::lenient()).unwrap();
+        assert_eq!(doc.to_string(), data);
+        assert_eq!(
+            doc.to_sbm().0,
+            parser::parse_categories_with(data, &ParseOptions::lenient()).unwrap()
+        );
+
+        let data = "a|b|c\n#A\n#Uncategorized|📥\nd|e|f\n";
+        let doc = Document::parse_with(data, &ParseOptions::lenient()).unwrap();
+        assert_eq!(
+            doc.to_sbm().to_string(),
+            "#A\n\n#Uncategorized|📥\na|b|c\nd|e|f"
+        );
+        assert_eq!(
+            doc.to_sbm().0,
+            parser::parse_categories_with(data, &ParseOptions::lenient()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_edit_touches_only_changed_lines() {
+        let mut doc = Document::parse(DATA).unwrap();
+        let category = doc.category_mut("Programming Languages").unwrap();
+        category.bookmarks_mut().nth(1).unwrap().description = "Python".to_string();
+        category.push_bookmark(Bookmark::new("Go", "Go", "https://go.dev/"));
+        doc.push_category(Header::new("News", None))
+            .push_bookmark(Bookmark::new(
+                "HN",
+                "Hacker News",
+                "https://news.ycombinator.com/",
+            ));
+
+        assert_eq!(
+            doc.to_string(),
+            "// Team bookmarks\r\n\r\n# Programming Languages | 👨‍💻\r\nRust   | The Rust Programming Language | https://www.rust-lang.org/\r\n// TODO: add Zig\r\nPython|Python|https://www.python.org/\r\nGo|Go|https://go.dev/\r\n\r\n#Web Development\r\nMDN|Web documentation|https://developer.mozilla.org/\r\n#News\r\nHN|Hacker News|https://news.ycombinator.com/"
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let data = "# Work\nJira | Issues | https://jira.example.com/\nJira | Copy | https://jira.example.com/\n### Infra\n// dashboards\nGrafana | | https://grafana.example.com/\n# Work\n# Home\n";
+        let other: Sbm = "#Work\nJira|My issues|https://jira.example.com|mine\n##Infra\nGrafana|Dashboards|http://grafana.example.com\n###Alerts\nPD|On call|https://pagerduty.example.com/\n##Docs\n#News\n"
+            .parse()
+            .unwrap();
+        let mut doc = Document::parse(data).unwrap();
+        let mut sbm = doc.to_sbm();
+        let conflicts = doc.merge(&other, Resolution::Ours);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            doc.to_string(),
+            "# Work\nJira|Issues|https://jira.example.com/|mine\nJira | Copy | https://jira.example.com/\n### Infra\n// dashboards\nGrafana|Dashboards|https://grafana.example.com/\n####Alerts\nPD|On call|https://pagerduty.example.com/\n##Docs\n# Work\n# Home\n#News\n"
+        );
+        sbm = sbm.merge(&other).resolve(Resolution::Ours);
+        assert_eq!(doc.to_sbm(), sbm);
+
+        let mut doc = Document::parse(data).unwrap();
+        doc.merge(&other, Resolution::Theirs);
+        assert_eq!(
+            doc.categories[0].bookmarks().next().unwrap().description,
+            "My issues"
+        );
+    }
+
+    #[test]
+    fn test_from_sbm() {
+        let sbm = Sbm::new(vec![Category {
+            header: Header::new("Rust", None),
+            bookmarks: vec![Bookmark::new(
+                "Docs",
+                "The book",
+                "https://doc.rust-lang.org/book/",
+            )],
+            children: vec![Category::new(Header::new("Crates", None))],
+        }]);
+        let doc = Document::from(&sbm);
+        assert_eq!(
+            doc.to_string(),
+            "#Rust\nDocs|The book|https://doc.rust-lang.org/book/\n##Crates\n"
+        );
+        assert_eq!(doc.to_sbm(), sbm);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let data = "//@ owner: ops\r\n# Work\r\n//@ added: 2024-01-31\r\nJira | Issues | https://jira.example.com/\r\n//@ dangling: kept as a comment\r\n\r\n#Home";
+        let mut doc = Document::parse(data).unwrap();
+        assert_eq!(doc.to_string(), data);
+        assert_eq!(doc.to_sbm().0, parser::parse_categories(data).unwrap());
+        assert_eq!(doc.categories[0].header.value.metadata["owner"], "ops");
+        assert!(matches!(&doc.categories[0].items[1], Item::Trivia(t) if t.is_comment()));
+
+        let jira = doc.categories[0].bookmarks_mut().next().unwrap();
+        jira.metadata
+            .insert("owner".to_string(), "platform".to_string());
+        doc.categories[1]
+            .header
+            .value
+            .metadata
+            .insert("archived".to_string(), "yes".to_string());
+        assert_eq!(
+            doc.to_string(),
+            "//@ owner: ops\r\n# Work\r\n//@ added: 2024-01-31\r\n//@ owner: platform\r\nJira|Issues|https://jira.example.com/\r\n//@ dangling: kept as a comment\r\n\r\n//@ archived: yes\r\n#Home"
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let data =
+            "# Work\n## Infra | 🔧\nGrafana | Dashboards | https://grafana.example.com/\n#Home\n";
+        let mut doc = Document::parse(data).unwrap();
+        assert_eq!(doc.to_string(), data);
+        assert_eq!(doc.to_sbm().0, parser::parse_categories(data).unwrap());
+
+        let infra = doc.find(&["Work", "Infra"]).unwrap();
+        doc.categories[infra].header.value.icon = None;
+        let work = doc.find(&["Work"]).unwrap();
+        doc.push_subcategory(work, Header::new("Docs", None));
+        assert_eq!(
+            doc.to_string(),
+            "# Work\n##Infra\nGrafana | Dashboards | https://grafana.example.com/\n##Docs\n#Home\n"
+        );
+        assert_eq!(doc.find(&["Infra"]), None);
+    }
+}
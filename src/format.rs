@@ -0,0 +1,330 @@
+//! Canonical formatter
+//!
+//! Unlike the `Display` impls, which always write the compact `#Name|icon`
+//! form, the formatter lays out a whole [`Document`] according to a
+//! [`FormatOptions`] house style, keeping comments if asked to. [`check`]
+//! tells whether a file is already formatted.
+//!
+//! Within a category, runs of blank lines are collapsed into one and blank
+//! lines at the start or end of the category are dropped. Annotations are
+//! data rather than comments: they are always kept, sorted by key, directly
+//! above their entry.
+
+use crate::document::{Document, Item};
+use crate::parser::{self, ParseError};
+use crate::{Bookmark, Header};
+use std::collections::BTreeMap;
+
+/// Options controlling the formatter's output
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FormatOptions {
+    /// Put spaces around pipes and after the `#` of a header
+    pub spaced: bool,
+    /// Pad fields so that the pipes of a category line up
+    pub align: bool,
+    /// Separate categories with a blank line
+    pub blank_between: bool,
+    /// End the file with a newline
+    pub trailing_newline: bool,
+    /// Keep `//` comments; otherwise they are dropped
+    pub keep_comments: bool,
+}
+
+impl Default for FormatOptions {
+    /// The style used in the README: `# Name | icon` and `Name | Description | URL`
+    fn default() -> FormatOptions {
+        FormatOptions {
+            spaced: true,
+            align: false,
+            blank_between: true,
+            trailing_newline: true,
+            keep_comments: true,
+        }
+    }
+}
+
+impl FormatOptions {
+    /// The same layout as the `Display` impls: no spacing, no blank lines
+    pub fn compact() -> FormatOptions {
+        FormatOptions {
+            spaced: false,
+            align: false,
+            blank_between: false,
+            trailing_newline: false,
+            keep_comments: false,
+        }
+    }
+}
+
+/// Format a parsed document
+///
+/// # Examples
+///
+/// ```
+/// use sbm::document::Document;
+/// use sbm::format::{self, FormatOptions};
+/// let doc = Document::parse("#Languages\nRust|Rust|https://www.rust-lang.org/\nGo|Go|https://go.dev/\n#Web|🌐\n").unwrap();
+/// let options = FormatOptions { align: true, ..FormatOptions::default() };
+/// assert_eq!(
+///     format::format(&doc, &options),
+///     "# Languages\nRust | Rust | https://www.rust-lang.org/\nGo   | Go   | https://go.dev/\n\n# Web | 🌐\n"
+/// );
+/// ```
+pub fn format(doc: &Document, options: &FormatOptions) -> String {
+    let mut blocks = Vec::new();
+    let preamble = format_items(&doc.preamble, options);
+    if !preamble.is_empty() {
+        blocks.push(preamble);
+    }
+    for category in &doc.categories {
+        let mut block = annotations(&category.header.value.metadata);
+        block.push(format_header(
+            &category.header.value,
+            category.depth,
+            options,
+        ));
+        block.extend(format_items(&category.items, options));
+        blocks.push(block);
+    }
+
+    let separator = if options.blank_between { "\n\n" } else { "\n" };
+    let mut out = blocks
+        .iter()
+        .map(|block| block.join("\n"))
+        .collect::<Vec<_>>()
+        .join(separator);
+    if options.trailing_newline && !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/// Parse and format a string
+pub fn format_str(data: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    Document::parse(data).map(|doc| format(&doc, options))
+}
+
+/// Whether a string is already formatted according to `options`
+///
+/// # Examples
+///
+/// ```
+/// use sbm::format::{self, FormatOptions};
+/// let options = FormatOptions::default();
+/// assert!(format::check("# Web | 🌐\nMDN | Docs | https://developer.mozilla.org/\n", &options).unwrap());
+/// assert!(!format::check("#Web|🌐\nMDN|Docs|https://developer.mozilla.org/", &options).unwrap());
+/// ```
+pub fn check(data: &str, options: &FormatOptions) -> Result<bool, ParseError> {
+    format_str(data, options).map(|formatted| formatted == data)
+}
+
+fn annotations(metadata: &BTreeMap<String, String>) -> Vec<String> {
+    metadata
+        .iter()
+        .map(|(key, value)| parser::annotation(key, value))
+        .collect()
+}
+
+fn format_header(header: &Header, depth: usize, options: &FormatOptions) -> String {
+    let mut fields = vec![parser::escape_first(&header.name).into_owned()];
+    fields.extend(header.icon.iter().map(|i| parser::escape(i).into_owned()));
+    let space = if options.spaced && !header.name.is_empty() {
+        " "
+    } else {
+        ""
+    };
+    format!(
+        "{}{}{}",
+        "#".repeat(depth.max(1)),
+        space,
+        join_fields(&fields, &[], options)
+    )
+}
+
+/// Join fields with pipes, padding each to the matching entry of `widths`
+///
+/// Spacing is only added next to non-empty fields, and the line never ends
+/// with whitespace.
+fn join_fields(fields: &[String], widths: &[usize], options: &FormatOptions) -> String {
+    let mut line = String::new();
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            if options.spaced && !line.is_empty() {
+                line.push(' ');
+            }
+            line.push('|');
+            if options.spaced && !field.is_empty() {
+                line.push(' ');
+            }
+        }
+        line.push_str(field);
+        let width = widths.get(i).copied().unwrap_or(0);
+        let len = field.chars().count();
+        if len < width {
+            line.extend(std::iter::repeat_n(' ', width - len));
+        }
+    }
+    line.truncate(line.trim_end().len());
+    line
+}
+
+fn bookmark_fields(bookmark: &Bookmark, options: &FormatOptions) -> Vec<String> {
+    let mut fields = vec![
+        parser::escape_first(&bookmark.name).into_owned(),
+        parser::escape(&bookmark.description).into_owned(),
+        parser::escape(&bookmark.url).into_owned(),
+    ];
+    if !bookmark.tags.is_empty() {
+        let separator = if options.spaced { ", " } else { "," };
+        let tags: Vec<_> = bookmark
+            .tags
+            .iter()
+            .map(|t| parser::escape_tag(t))
+            .collect();
+        fields.push(tags.join(separator));
+    }
+    fields
+}
+
+/// Format the lines of a category, or the preamble
+fn format_items(items: &[Item], options: &FormatOptions) -> Vec<String> {
+    let fields: Vec<Option<Vec<String>>> = items
+        .iter()
+        .map(|item| match item {
+            Item::Bookmark(node) => Some(bookmark_fields(&node.value, options)),
+            Item::Trivia(_) => None,
+        })
+        .collect();
+
+    // the URL is only padded when tags follow it
+    let mut widths = [0; 3];
+    if options.align {
+        for f in fields.iter().flatten() {
+            for (width, field) in widths.iter_mut().zip(f) {
+                *width = (*width).max(field.chars().count());
+            }
+        }
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    for (item, fields) in items.iter().zip(fields) {
+        match (item, fields) {
+            (Item::Bookmark(node), Some(fields)) => {
+                lines.extend(annotations(&node.value.metadata));
+                lines.push(join_fields(&fields, &widths, options));
+            }
+            (Item::Trivia(trivia), _) if trivia.is_comment() => {
+                if options.keep_comments {
+                    lines.push(trivia.text.trim_end().to_string());
+                }
+            }
+            _ => {
+                // a blank line, kept only between two other lines
+                if lines.last().is_some_and(|l| !l.is_empty()) {
+                    lines.push(String::new());
+                }
+            }
+        }
+    }
+    if lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "// Team bookmarks\r\n#Programming Languages|👨‍💻\r\n\r\nRust|The Rust Programming Language|https://www.rust-lang.org/\r\n\r\n\r\n// scripting\r\nPython   |Python|https://www.python.org/   \r\n\r\n#Web Development\r\nMDN|pipes \\| docs|https://developer.mozilla.org/";
+
+    #[test]
+    fn test_default_style() {
+        let formatted = format_str(DATA, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            formatted,
+            "// Team bookmarks\n\n# Programming Languages | 👨‍💻\nRust | The Rust Programming Language | https://www.rust-lang.org/\n\n// scripting\nPython | Python | https://www.python.org/\n\n# Web Development\nMDN | pipes \\| docs | https://developer.mozilla.org/\n"
+        );
+        assert!(check(&formatted, &FormatOptions::default()).unwrap());
+        assert!(!check(DATA, &FormatOptions::default()).unwrap());
+    }
+
+    #[test]
+    fn test_aligned_without_comments() {
+        let options = FormatOptions {
+            align: true,
+            keep_comments: false,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_str(DATA, &options).unwrap(),
+            "# Programming Languages | 👨‍💻\nRust   | The Rust Programming Language | https://www.rust-lang.org/\n\nPython | Python                        | https://www.python.org/\n\n# Web Development\nMDN | pipes \\| docs | https://developer.mozilla.org/\n"
+        );
+    }
+
+    #[test]
+    fn test_compact_matches_display() {
+        let doc = Document::parse(DATA).unwrap();
+        let sbm = doc.to_sbm();
+        let compact = format(&Document::from(&sbm), &FormatOptions::compact());
+        assert_eq!(compact, sbm.to_string());
+        assert_eq!(compact.parse::<crate::Sbm>().unwrap(), sbm);
+    }
+
+    #[test]
+    fn test_nested() {
+        let data = "#Work\n##Infra|🔧\nGrafana|Dashboards|https://grafana.example.com/\n###Alerts\n#Home\n";
+        let formatted = format_str(data, &FormatOptions::default()).unwrap();
+        assert_eq!(
+            formatted,
+            "# Work\n\n## Infra | 🔧\nGrafana | Dashboards | https://grafana.example.com/\n\n### Alerts\n\n# Home\n"
+        );
+        assert_eq!(
+            Document::parse(&formatted).unwrap().to_sbm(),
+            Document::parse(data).unwrap().to_sbm()
+        );
+    }
+
+    #[test]
+    fn test_tags() {
+        let data = "#Languages\nRust|Rust|https://www.rust-lang.org/|systems,a\\,b\nGo|Go|https://go.dev/\n";
+        let options = FormatOptions {
+            align: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_str(data, &options).unwrap(),
+            "# Languages\nRust | Rust | https://www.rust-lang.org/ | systems, a\\,b\nGo   | Go   | https://go.dev/\n"
+        );
+        assert_eq!(
+            format_str(data, &FormatOptions::compact()).unwrap(),
+            data.trim_end()
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let data = "//@ owner : ops\n#Work\n//@ expires:2025-01-01\n//@ added: 2024-01-31\nJira|Issues|https://jira.example.com/\nWiki|Wiki|https://wiki.example.com/\n";
+        let options = FormatOptions {
+            align: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_str(data, &options).unwrap(),
+            "//@ owner: ops\n# Work\n//@ added: 2024-01-31\n//@ expires: 2025-01-01\nJira | Issues | https://jira.example.com/\nWiki | Wiki   | https://wiki.example.com/\n"
+        );
+        let compact = format_str(data, &FormatOptions::compact()).unwrap();
+        assert_eq!(compact, Document::parse(data).unwrap().to_sbm().to_string());
+    }
+
+    #[test]
+    fn test_empty_fields() {
+        let data = "#|\n||\n";
+        let formatted = format_str(data, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, "#|\n| |\n");
+        assert_eq!(
+            Document::parse(&formatted).unwrap().to_sbm(),
+            Document::parse(data).unwrap().to_sbm()
+        );
+    }
+}
@@ -0,0 +1,384 @@
+//! Chromium `Bookmarks` file (Chrome, Edge, Brave, Vivaldi, ...)
+//!
+//! The file is JSON with three folder trees under `roots`: `bookmark_bar`,
+//! `other` and `synced`. Each tree that holds anything becomes a top-level
+//! category named after it, e.g. `Bookmarks bar`, with its folders as
+//! subcategories.
+//!
+//! Exporting reverses the mapping: a top-level category named after a tree
+//! goes into that tree, and any other category becomes a folder in the
+//! bookmarks bar. Descriptions, tags and category icons have no place in
+//! Chromium's model, so they are kept in each node's `meta_info`, which the
+//! browser preserves. Tags are kept there as a comma-separated list, escaped
+//! as in an SBM tags field.
+
+use super::json::{self, Value};
+use super::md5::Md5;
+use super::{join_tags, Folder, ImportError};
+use crate::{parser, Bookmark, Sbm};
+
+const FORMAT: &str = "Chromium bookmarks";
+
+/// The permanent folders: key under `roots`, default name and GUID
+const ROOTS: [(&str, &str, &str); 3] = [
+    (
+        "bookmark_bar",
+        "Bookmarks bar",
+        "0bc5d13f-2cba-5d74-951f-3f233fe6c908",
+    ),
+    (
+        "other",
+        "Other bookmarks",
+        "82b081ec-3dd3-529c-8475-ab6c344590dd",
+    ),
+    (
+        "synced",
+        "Mobile bookmarks",
+        "4cf2e351-0e85-532b-bb37-df045d8f8d0f",
+    ),
+];
+
+/// Read a Chromium `Bookmarks` file
+///
+/// The checksum is not verified; browsers recompute it anyway.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::chromium;
+/// let json = r#"{"roots": {"bookmark_bar": {"name": "Bookmarks bar", "type": "folder", "children": [
+///     {"name": "Rust", "type": "url", "url": "https://www.rust-lang.org/"}
+/// ]}}, "version": 1}"#;
+/// let sbm = chromium::import(json).unwrap();
+/// assert_eq!(sbm.0[0].header.name, "Bookmarks bar");
+/// assert_eq!(sbm.0[0].bookmarks[0].url, "https://www.rust-lang.org/");
+/// ```
+pub fn import(json: &str) -> Result<Sbm, ImportError> {
+    let value = json::parse(json).map_err(|e| ImportError::new(FORMAT, e))?;
+    let roots = value
+        .get("roots")
+        .ok_or_else(|| ImportError::new(FORMAT, "missing \"roots\""))?;
+
+    let mut root = Folder::default();
+    for (key, default_name, _) in ROOTS {
+        let Some(node) = roots.get(key) else {
+            continue;
+        };
+        let name = node.get("name").and_then(Value::as_str);
+        let mut folder = Folder::new(name.unwrap_or(default_name));
+        read_children(node, &mut folder);
+        if !folder.bookmarks.is_empty() || !folder.children.is_empty() {
+            root.children.push(folder);
+        }
+    }
+    Ok(root.into_sbm())
+}
+
+fn read_children(node: &Value, folder: &mut Folder) {
+    let children = node.get("children").and_then(Value::as_array);
+    for child in children.unwrap_or_default() {
+        let name = child.get("name").and_then(Value::as_str).unwrap_or("");
+        let meta = |key| {
+            child
+                .get("meta_info")
+                .and_then(|m| m.get(key))
+                .and_then(Value::as_str)
+        };
+        match child.get("type").and_then(Value::as_str) {
+            Some("url") => {
+                let url = child.get("url").and_then(Value::as_str).unwrap_or("");
+                let description = meta("description").unwrap_or("");
+                let tags = parser::split_tags(meta("tags").unwrap_or(""));
+                folder
+                    .bookmarks
+                    .push(Bookmark::new(name, description, url).with_tags(tags));
+            }
+            Some("folder") => {
+                let mut sub = Folder::new(name);
+                sub.icon = meta("icon").map(str::to_string);
+                read_children(child, &mut sub);
+                folder.children.push(sub);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Write a Chromium `Bookmarks` file with a valid checksum
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::chromium;
+/// use sbm::Sbm;
+/// let sbm: Sbm = "#Bookmarks bar\n##Languages\nRust|Systems programming language|https://www.rust-lang.org/".parse().unwrap();
+/// let json = chromium::export(&sbm);
+/// assert!(json.contains(r#""url": "https://www.rust-lang.org/""#));
+/// assert_eq!(chromium::import(&json).unwrap(), sbm);
+/// ```
+pub fn export(sbm: &Sbm) -> String {
+    let mut roots: Vec<Folder> = ROOTS.iter().map(|(_, name, _)| Folder::new(name)).collect();
+    for category in &sbm.0 {
+        let Some(root) = roots.iter_mut().find(|r| r.name == category.header.name) else {
+            roots[0].children.push(Folder::from(category));
+            continue;
+        };
+        let folder = Folder::from(category);
+        root.bookmarks.extend(folder.bookmarks);
+        root.children.extend(folder.children);
+        if folder.icon.is_some() {
+            root.icon = folder.icon;
+        }
+    }
+
+    let mut writer = Writer {
+        next_id: ROOTS.len() + 1,
+        date_added: now().to_string(),
+    };
+    let roots = Value::Object(
+        ROOTS
+            .iter()
+            .zip(&roots)
+            .enumerate()
+            .map(|(i, ((key, _, guid), folder))| {
+                let id = (i + 1).to_string();
+                (key.to_string(), writer.folder(folder, id, guid.to_string()))
+            })
+            .collect(),
+    );
+    let file = Value::object([
+        ("checksum", Value::String(checksum(&roots))),
+        ("roots", roots),
+        ("version", Value::Number("1".to_string())),
+    ]);
+    file.to_pretty("   ") + "\n"
+}
+
+struct Writer {
+    next_id: usize,
+    date_added: String,
+}
+
+impl Writer {
+    fn id(&mut self) -> String {
+        self.next_id += 1;
+        (self.next_id - 1).to_string()
+    }
+
+    fn folder(&mut self, folder: &Folder, id: String, guid: String) -> Value {
+        let mut children = Vec::new();
+        for bookmark in &folder.bookmarks {
+            let id = self.id();
+            let mut node = vec![
+                ("date_added", Value::string(&self.date_added)),
+                ("guid", Value::String(guid_for(&id, &bookmark.url))),
+                ("id", Value::String(id)),
+            ];
+            let mut meta = Vec::new();
+            if !bookmark.description.is_empty() {
+                meta.push(("description", Value::string(&bookmark.description)));
+            }
+            if !bookmark.tags.is_empty() {
+                meta.push(("tags", Value::String(join_tags(&bookmark.tags))));
+            }
+            if !meta.is_empty() {
+                node.push(("meta_info", Value::object(meta)));
+            }
+            node.push(("name", Value::string(&bookmark.name)));
+            node.push(("type", Value::string("url")));
+            node.push(("url", Value::string(&bookmark.url)));
+            children.push(Value::object(node));
+        }
+        for child in &folder.children {
+            let id = self.id();
+            let guid = guid_for(&id, &child.name);
+            children.push(self.folder(child, id, guid));
+        }
+
+        let mut node = vec![
+            ("children", Value::Array(children)),
+            ("date_added", Value::string(&self.date_added)),
+            ("date_modified", Value::string(&self.date_added)),
+            ("guid", Value::String(guid)),
+            ("id", Value::String(id)),
+        ];
+        if let Some(icon) = &folder.icon {
+            node.push(("meta_info", Value::object([("icon", Value::string(icon))])));
+        }
+        node.push(("name", Value::string(&folder.name)));
+        node.push(("type", Value::string("folder")));
+        Value::object(node)
+    }
+}
+
+/// A stable, well-formed version 4 GUID derived from a node's id and content
+fn guid_for(id: &str, content: &str) -> String {
+    let mut md5 = Md5::new();
+    md5.update(id.as_bytes());
+    md5.update(&[0]);
+    md5.update(content.as_bytes());
+    let hex = md5.hex_digest();
+    format!(
+        "{}-{}-4{}-{:x}{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[13..16],
+        (u8::from_str_radix(&hex[16..17], 16).unwrap() & 0x3) | 0x8,
+        &hex[17..20],
+        &hex[20..32]
+    )
+}
+
+/// The current time in Chromium's format: microseconds since 1601-01-01
+fn now() -> u64 {
+    const UNIX_EPOCH_OFFSET: u64 = 11_644_473_600;
+    let unix = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    (unix.as_secs() + UNIX_EPOCH_OFFSET) * 1_000_000 + u64::from(unix.subsec_micros())
+}
+
+/// The checksum Chromium stores alongside `roots`
+///
+/// It is the MD5 of every node in pre-order: its id, its title as UTF-16LE,
+/// then `url` and the URL for bookmarks or `folder` for folders.
+fn checksum(roots: &Value) -> String {
+    fn update(md5: &mut Md5, node: &Value) {
+        let field = |key| node.get(key).and_then(Value::as_str).unwrap_or("");
+        md5.update(field("id").as_bytes());
+        let title: Vec<u8> = field("name")
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        md5.update(&title);
+        if field("type") == "url" {
+            md5.update(b"url");
+            md5.update(field("url").as_bytes());
+        } else {
+            md5.update(b"folder");
+            let children = node.get("children").and_then(Value::as_array);
+            for child in children.unwrap_or_default() {
+                update(md5, child);
+            }
+        }
+    }
+
+    let mut md5 = Md5::new();
+    for (key, _, _) in ROOTS {
+        if let Some(node) = roots.get(key) {
+            update(&mut md5, node);
+        }
+    }
+    md5.hex_digest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Category, Header};
+
+    const BOOKMARKS: &str = include_str!("../../tests/fixtures/chromium/Bookmarks");
+
+    #[test]
+    fn test_import() {
+        let sbm = import(BOOKMARKS).unwrap();
+        let names: Vec<(usize, &str)> = sbm
+            .walk()
+            .map(|(depth, c)| (depth, c.header.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (1, "Bookmarks bar"),
+                (2, "Work"),
+                (3, "Empty"),
+                (1, "Other bookmarks")
+            ]
+        );
+        assert_eq!(
+            sbm.0[0].children[0].bookmarks,
+            vec![
+                Bookmark::new("GitHub", "", "https://github.com/"),
+                Bookmark::new("Crates 📦", "", "https://crates.io/"),
+            ]
+        );
+        assert_eq!(sbm.0[1].bookmarks[0].name, "Hacker News");
+    }
+
+    #[test]
+    fn test_checksum() {
+        let value = json::parse(BOOKMARKS).unwrap();
+        let expected = value.get("checksum").and_then(Value::as_str).unwrap();
+        assert_eq!(checksum(value.get("roots").unwrap()), expected);
+
+        let exported = json::parse(&export(&import(BOOKMARKS).unwrap())).unwrap();
+        let stored = exported.get("checksum").and_then(Value::as_str).unwrap();
+        assert_eq!(checksum(exported.get("roots").unwrap()), stored);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let sbm = import(BOOKMARKS).unwrap();
+        assert_eq!(import(&export(&sbm)).unwrap(), sbm);
+
+        let sbm = Sbm(vec![
+            Category {
+                children: vec![Category {
+                    header: Header::new("Languages", Some("👨‍💻")),
+                    bookmarks: vec![Bookmark::new(
+                        "Rust",
+                        "A \"systems\" language",
+                        "https://www.rust-lang.org/",
+                    )
+                    .with_tags(["lang", "systems", "a,b"])],
+                    children: Vec::new(),
+                }],
+                ..Category::new(Header::new("Bookmarks bar", None))
+            },
+            Category {
+                header: Header::new("Other bookmarks", None),
+                bookmarks: vec![Bookmark::new("Go", "", "https://go.dev/")],
+                children: Vec::new(),
+            },
+        ]);
+        assert_eq!(import(&export(&sbm)).unwrap(), sbm);
+    }
+
+    #[test]
+    fn test_export_layout() {
+        let sbm: Sbm = "#Work\n##Infra\nGrafana||https://grafana.example.com/"
+            .parse()
+            .unwrap();
+        let exported = import(&export(&sbm)).unwrap();
+        assert!(exported.find(&["Bookmarks bar", "Work", "Infra"]).is_some());
+
+        let value = json::parse(&export(&sbm)).unwrap();
+        let roots = value.get("roots").unwrap();
+        let ids: Vec<&str> = ROOTS
+            .iter()
+            .map(|(key, _, _)| roots.get(key).unwrap().get("id").unwrap().as_str().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        let guid = roots
+            .get("bookmark_bar")
+            .unwrap()
+            .get("children")
+            .unwrap()
+            .as_array()
+            .unwrap()[0]
+            .get("guid")
+            .and_then(Value::as_str)
+            .unwrap();
+        assert_eq!(guid.len(), 36);
+        assert_eq!(&guid[14..15], "4");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(import("not json").is_err());
+        assert_eq!(
+            import("{\"version\": 1}").unwrap_err().to_string(),
+            "invalid Chromium bookmarks file: missing \"roots\""
+        );
+    }
+}
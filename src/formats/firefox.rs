@@ -0,0 +1,282 @@
+//! Firefox `places.sqlite`
+//!
+//! Bookmarks live in the `moz_bookmarks` table, pointing into `moz_places`
+//! for their URLs. Firefox's root folders (the menu, the toolbar, other and
+//! mobile bookmarks) become top-level categories as described in the
+//! [module docs](super), with their folders as subcategories. A bookmark without
+//! a title is named after its page, and the page's description becomes the
+//! bookmark's description.
+//!
+//! Firefox stores tags as folders under the `tags` root, each holding an
+//! entry for every page with that tag. They become the tags of every bookmark
+//! of that page. A page's keyword, which Firefox keeps in `moz_keywords`,
+//! becomes `keyword` metadata on its bookmarks.
+//!
+//! Copy the database while Firefox is closed: a running browser keeps recent
+//! changes in a separate write-ahead log, which is not read.
+
+use super::sqlite::{Database, Table};
+use super::{Folder, ImportError};
+use crate::{Bookmark, Sbm};
+use std::collections::{HashMap, HashSet};
+
+const FORMAT: &str = "Firefox places";
+
+const TYPE_BOOKMARK: i64 = 1;
+const TYPE_FOLDER: i64 = 2;
+
+/// Display names of the built-in folders, by GUID
+const ROOTS: [(&str, &str); 4] = [
+    ("menu________", "Bookmarks Menu"),
+    ("toolbar_____", "Bookmarks Toolbar"),
+    ("unfiled_____", "Other Bookmarks"),
+    ("mobile______", "Mobile Bookmarks"),
+];
+
+const TAGS_ROOT: &str = "tags________";
+
+/// A row of `moz_bookmarks`
+struct Item {
+    id: i64,
+    kind: i64,
+    place: Option<i64>,
+    title: Option<String>,
+    guid: String,
+}
+
+/// A row of `moz_places`
+struct Place {
+    url: String,
+    title: Option<String>,
+    description: Option<String>,
+}
+
+/// Read the bookmarks of a `places.sqlite` database
+///
+/// # Examples
+///
+/// ```no_run
+/// use sbm::formats::firefox;
+/// let data = std::fs::read("places.sqlite").unwrap();
+/// let sbm = firefox::import(&data).unwrap();
+/// println!("{}", sbm);
+/// ```
+pub fn import(data: &[u8]) -> Result<Sbm, ImportError> {
+    let error = |message: String| ImportError::new(FORMAT, message);
+    let db = Database::open(data).map_err(error)?;
+    let table = |name: &str| match db.table(name) {
+        Ok(Some(table)) => Ok(table),
+        Ok(None) => Err(error(format!("missing table {}", name))),
+        Err(e) => Err(error(e)),
+    };
+    let places = read_places(&table("moz_places")?);
+    let bookmarks = table("moz_bookmarks")?;
+    // older profiles have no keywords table
+    let keywords = match db.table("moz_keywords") {
+        Ok(Some(table)) => read_keywords(&table),
+        Ok(None) => HashMap::new(),
+        Err(e) => return Err(error(e)),
+    };
+
+    // children of every folder, in position order
+    let mut children: HashMap<i64, Vec<(i64, Item)>> = HashMap::new();
+    for row in &bookmarks.rows {
+        let get = |column| bookmarks.get(row, column);
+        let item = Item {
+            id: get("id").as_i64().unwrap_or(0),
+            kind: get("type").as_i64().unwrap_or(0),
+            place: get("fk").as_i64(),
+            title: get("title").as_str().map(str::to_string),
+            guid: get("guid").as_str().unwrap_or("").to_string(),
+        };
+        let parent = get("parent").as_i64().unwrap_or(0);
+        let position = get("position").as_i64().unwrap_or(0);
+        children.entry(parent).or_default().push((position, item));
+    }
+    for items in children.values_mut() {
+        items.sort_by_key(|(position, _)| *position);
+    }
+
+    let root = bookmarks
+        .rows
+        .iter()
+        .find(|row| bookmarks.get(row, "guid").as_str() == Some("root________"))
+        .and_then(|row| bookmarks.get(row, "id").as_i64())
+        .ok_or_else(|| error("missing root folder".to_string()))?;
+    let top_level = children.get(&root).into_iter().flatten();
+    let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+    for (_, tag) in top_level
+        .clone()
+        .filter(|(_, item)| item.guid == TAGS_ROOT)
+        .flat_map(|(_, item)| children.get(&item.id).into_iter().flatten())
+    {
+        let name = tag.title.as_deref().unwrap_or("").trim();
+        if tag.kind != TYPE_FOLDER || name.is_empty() {
+            continue;
+        }
+        for (_, entry) in children.get(&tag.id).into_iter().flatten() {
+            if let Some(place) = entry.place {
+                let place_tags = tags.entry(place).or_default();
+                if !place_tags.iter().any(|t| t == name) {
+                    place_tags.push(name.to_string());
+                }
+            }
+        }
+    }
+
+    let mut tree = Folder::default();
+    let mut reader = Reader {
+        children: &children,
+        places: &places,
+        tags: &tags,
+        keywords: &keywords,
+        seen: HashSet::from([root]),
+    };
+    for (_, item) in top_level {
+        if item.kind != TYPE_FOLDER || item.guid == TAGS_ROOT {
+            continue;
+        }
+        let name = ROOTS
+            .iter()
+            .find(|(guid, _)| *guid == item.guid)
+            .map(|(_, name)| name.to_string())
+            .or_else(|| item.title.clone())
+            .unwrap_or_default();
+        let mut folder = Folder::new(&name);
+        reader.read(item.id, &mut folder);
+        if !folder.bookmarks.is_empty() || !folder.children.is_empty() {
+            tree.children.push(folder);
+        }
+    }
+    Ok(tree.into_sbm())
+}
+
+fn read_places(table: &Table) -> HashMap<i64, Place> {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let text = |column| table.get(row, column).as_str().map(str::to_string);
+            let place = Place {
+                url: text("url")?,
+                title: text("title"),
+                description: text("description"),
+            };
+            Some((table.get(row, "id").as_i64()?, place))
+        })
+        .collect()
+}
+
+/// The keyword of each place
+fn read_keywords(table: &Table) -> HashMap<i64, String> {
+    table
+        .rows
+        .iter()
+        .filter_map(|row| {
+            let keyword = table.get(row, "keyword").as_str()?.trim();
+            let place = table.get(row, "place_id").as_i64()?;
+            (!keyword.is_empty()).then(|| (place, keyword.to_string()))
+        })
+        .collect()
+}
+
+struct Reader<'a> {
+    children: &'a HashMap<i64, Vec<(i64, Item)>>,
+    places: &'a HashMap<i64, Place>,
+    /// Tags of each place
+    tags: &'a HashMap<i64, Vec<String>>,
+    keywords: &'a HashMap<i64, String>,
+    /// Folders already read, so that a corrupt database can't loop forever
+    seen: HashSet<i64>,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, id: i64, folder: &mut Folder) {
+        if !self.seen.insert(id) {
+            return;
+        }
+        for (_, item) in self.children.get(&id).into_iter().flatten() {
+            match item.kind {
+                TYPE_BOOKMARK => {
+                    let Some((id, place)) =
+                        item.place.and_then(|id| Some((id, self.places.get(&id)?)))
+                    else {
+                        continue;
+                    };
+                    let name = item
+                        .title
+                        .as_deref()
+                        .filter(|t| !t.is_empty())
+                        .or(place.title.as_deref())
+                        .unwrap_or(&place.url);
+                    let description = place.description.as_deref().unwrap_or("");
+                    let tags = self.tags.get(&id).into_iter().flatten();
+                    let mut bookmark =
+                        Bookmark::new(name, description, &place.url).with_tags(tags.cloned());
+                    if let Some(keyword) = self.keywords.get(&id) {
+                        bookmark = bookmark.with_metadata("keyword", keyword);
+                    }
+                    folder.bookmarks.push(bookmark);
+                }
+                TYPE_FOLDER => {
+                    let mut child = Folder::new(item.title.as_deref().unwrap_or(""));
+                    self.read(item.id, &mut child);
+                    folder.children.push(child);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::assert_outline;
+
+    const PLACES: &[u8] = include_bytes!("../../tests/fixtures/firefox/places.sqlite");
+
+    #[test]
+    fn test_import() {
+        let sbm = import(PLACES).unwrap();
+        assert_outline(
+            &sbm,
+            vec![
+                ("Bookmarks Menu", vec!["Getting Started"]),
+                ("Bookmarks Menu / Mozilla Firefox", vec!["Get Help"]),
+                ("Bookmarks Toolbar", vec!["MDN Web Docs", "Rust 🦀"]),
+                ("Bookmarks Toolbar / Work", vec!["GitHub", "Long search"]),
+                ("Other Bookmarks", vec!["Hacker News"]),
+            ],
+        );
+
+        let toolbar = &sbm.0[1].bookmarks;
+        assert_eq!(
+            toolbar[0],
+            Bookmark::new(
+                "MDN Web Docs",
+                "Resources for Developers, by Developers",
+                "https://developer.mozilla.org/"
+            )
+            .with_tags(["docs"])
+            .with_metadata("keyword", "mdn")
+        );
+        assert_eq!(toolbar[1].tags, vec!["rust"]);
+        assert!(toolbar[1].metadata.is_empty());
+        assert_eq!(sbm.bookmarks_with_tag("docs").count(), 2);
+        let long = &sbm.0[1].children[0].bookmarks[1].url;
+        assert_eq!(long.len(), 3029);
+        assert!(long.ends_with("xxxx"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(
+            import(b"<html>").unwrap_err().to_string(),
+            "invalid Firefox places file: not an SQLite database"
+        );
+        let mut truncated = PLACES.to_vec();
+        truncated.truncate(4096);
+        assert!(import(&truncated).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+//! Just enough JSON to read and write browser bookmark files
+
+use std::fmt::Write;
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    /// A number, kept as written so that large ids and timestamps survive
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    /// An object, with its keys in document order
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Build an object from key-value pairs
+    pub fn object<'k>(entries: impl IntoIterator<Item = (&'k str, Value)>) -> Value {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    /// The value of a key of an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Serialize with each nested value on its own line, indented by `indent`
+    pub fn to_pretty(&self, indent: &str) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, indent, 0);
+        out
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: &str, depth: usize) {
+        let newline = |out: &mut String, depth: usize| {
+            out.push('\n');
+            out.push_str(&indent.repeat(depth));
+        };
+        match self {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => out.push_str(n),
+            Value::String(s) => write_string(out, s),
+            Value::Array(items) if items.is_empty() => out.push_str("[  ]"),
+            Value::Array(items) => {
+                out.push_str("[ ");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(", ");
+                    }
+                    item.write_pretty(out, indent, depth);
+                }
+                out.push_str(" ]");
+            }
+            Value::Object(entries) if entries.is_empty() => {
+                out.push('{');
+                newline(out, depth);
+                out.push('}');
+            }
+            Value::Object(entries) => {
+                out.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write_pretty(out, indent, depth + 1);
+                }
+                newline(out, depth);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// How deep arrays and objects may nest, so that hostile input can't
+/// overflow the stack while parsing or dropping a [`Value`]
+const MAX_DEPTH: usize = 512;
+
+/// Parse a JSON document
+pub(crate) fn parse(input: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.value()?;
+    parser.whitespace();
+    if parser.pos < input.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// Arrays and objects currently open
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        let before = &self.input[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        format!("{} at line {}, column {}", message, line, column)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.pos += token.len();
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.whitespace();
+        match self.rest().chars().next() {
+            Some('{' | '[') if self.depth == MAX_DEPTH => Err(self.error("nesting too deep")),
+            Some('{') => {
+                self.depth += 1;
+                let object = self.object();
+                self.depth -= 1;
+                object
+            }
+            Some('[') => {
+                self.depth += 1;
+                let array = self.array();
+                self.depth -= 1;
+                array
+            }
+            Some('"') => self.string().map(Value::String),
+            Some('-' | '0'..='9') => self.number(),
+            _ if self.eat("null") => Ok(Value::Null),
+            _ if self.eat("true") => Ok(Value::Bool(true)),
+            _ if self.eat("false") => Ok(Value::Bool(false)),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        self.whitespace();
+        if self.eat("}") {
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.whitespace();
+            if !self.rest().starts_with('"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.whitespace();
+            if !self.eat(":") {
+                return Err(self.error("expected ':'"));
+            }
+            entries.push((key, self.value()?));
+            self.whitespace();
+            if self.eat("}") {
+                return Ok(Value::Object(entries));
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.whitespace();
+        if self.eat("]") {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.whitespace();
+            if self.eat("]") {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(",") {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    /// Read a number, `-? (0 | [1-9][0-9]*) (. [0-9]+)? ([eE] [+-]? [0-9]+)?`
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        self.eat("-");
+        if !self.eat("0") && self.digits() == 0 {
+            return Err(self.error("invalid number"));
+        }
+        if self.eat(".") && self.digits() == 0 {
+            return Err(self.error("invalid number"));
+        }
+        if self.eat("e") || self.eat("E") {
+            let _ = self.eat("+") || self.eat("-");
+            if self.digits() == 0 {
+                return Err(self.error("invalid number"));
+            }
+        }
+        Ok(Value::Number(self.input[start..self.pos].to_string()))
+    }
+
+    /// Skip ASCII digits, returning how many there were
+    fn digits(&mut self) -> usize {
+        let rest = self.rest();
+        let len = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        self.pos += len;
+        len
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = self.rest();
+            let Some(i) = rest.find(['"', '\\']) else {
+                return Err(self.error("unterminated string"));
+            };
+            out.push_str(&rest[..i]);
+            self.pos += i + 1;
+            if rest.as_bytes()[i] == b'"' {
+                return Ok(out);
+            }
+            let c = match self.rest().chars().next() {
+                Some('u') => {
+                    self.pos += 1;
+                    self.unicode_escape()?
+                }
+                Some(c) => {
+                    self.pos += c.len_utf8();
+                    match c {
+                        '"' | '\\' | '/' => c,
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                None => return Err(self.error("unterminated string")),
+            };
+            out.push(c);
+        }
+    }
+
+    /// Read the digits of a `\u` escape, combining surrogate pairs
+    ///
+    /// An unpaired surrogate becomes U+FFFD. The escape after a high
+    /// surrogate is only consumed if it is the low half of the pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return Ok(char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER));
+        }
+        let before = self.pos;
+        if self.eat("\\u") {
+            match self.hex4() {
+                Ok(low @ 0xdc00..=0xdfff) => {
+                    let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                    return Ok(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                }
+                _ => self.pos = before,
+            }
+        }
+        Ok(char::REPLACEMENT_CHARACTER)
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .rest()
+            .get(..4)
+            .ok_or_else(|| self.error("invalid escape"))?;
+        let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let value =
+            parse(r#" {"a": [1, -2.5e3, true, null], "b": {}, "s": "q\"\\\/\né🦀"} "#).unwrap();
+        assert_eq!(value.get("s").and_then(Value::as_str), Some("q\"\\/\né🦀"));
+        assert_eq!(
+            value.get("a").and_then(Value::as_array).map(<[_]>::len),
+            Some(4)
+        );
+        let pretty = value.to_pretty("   ");
+        assert_eq!(
+            pretty,
+            "{\n   \"a\": [ 1, -2.5e3, true, null ],\n   \"b\": {\n   },\n   \"s\": \"q\\\"\\\\/\\né🦀\"\n}"
+        );
+        assert_eq!(parse(&pretty).unwrap(), value);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse("{\n  \"a\" 1}").unwrap_err(),
+            "expected ':' at line 2, column 7"
+        );
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("\"abc").is_err());
+        assert!(parse("{} x").is_err());
+        for bad in [
+            "--1e+", "-", "1.", ".5", "1e", "1e+", "01", "+1", "1.2.3", "-a",
+        ] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+        for good in ["0", "-0", "10", "1.25", "-2.5e3", "1E-7", "6e+1"] {
+            assert_eq!(parse(good).unwrap(), Value::Number(good.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            parse(&nested(MAX_DEPTH + 1)).unwrap_err(),
+            "nesting too deep at line 1, column 513"
+        );
+        // far deeper than the stack could take
+        assert!(parse(&"[{\"a\":".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn test_surrogates() {
+        let string = |json: &str| parse(json).unwrap().as_str().unwrap().to_string();
+        assert_eq!(string(r#""\ud83e\udd80""#), "🦀");
+        // an unpaired high surrogate leaves the next escape alone
+        assert_eq!(string(r#""\ud83e\u0041""#), "\u{fffd}A");
+        assert_eq!(string(r#""\ud83e\ud83e\udd80""#), "\u{fffd}🦀");
+        assert_eq!(string(r#""\ud83e\n""#), "\u{fffd}\n");
+        assert_eq!(string(r#""\udd80x""#), "\u{fffd}x");
+        assert!(parse(r#""\ud83e\u00zz""#).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! A small, forgiving tokenizer for the HTML and XML bookmark formats
+//!
+//! It only knows about tags, attributes, text, comments, CDATA sections and
+//! the predefined and numeric character references, which is all that
+//! bookmark files use. Malformed markup never fails; it is read as text.
+
+use std::borrow::Cow;
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Token<'a> {
+    Start {
+        name: &'a str,
+        attributes: Vec<(&'a str, Cow<'a, str>)>,
+        self_closing: bool,
+    },
+    End {
+        name: &'a str,
+    },
+    /// Text with character references resolved
+    Text(Cow<'a, str>),
+}
+
+impl<'a> Token<'a> {
+    /// Whether this is a start tag with the given name, ignoring ASCII case
+    pub fn is_start(&self, tag: &str) -> bool {
+        matches!(self, Token::Start { name, .. } if name.eq_ignore_ascii_case(tag))
+    }
+
+    /// Whether this is an end tag with the given name, ignoring ASCII case
+    pub fn is_end(&self, tag: &str) -> bool {
+        matches!(self, Token::End { name } if name.eq_ignore_ascii_case(tag))
+    }
+
+    /// The value of an attribute of a start tag, ignoring ASCII case in its name
+    pub fn attribute(&self, attribute: &str) -> Option<&str> {
+        match self {
+            Token::Start { attributes, .. } => attributes
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(attribute))
+                .map(|(_, value)| value.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Split markup into tokens
+pub(crate) fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            tokens.push(Token::Text(decode(rest)));
+            break;
+        };
+        if lt > 0 {
+            tokens.push(Token::Text(decode(&rest[..lt])));
+        }
+        rest = &rest[lt..];
+
+        let skip_until = |rest: &str, start: &str, end: &str| {
+            rest.starts_with(start)
+                .then(|| rest.find(end).map_or(rest.len(), |i| i + end.len()))
+        };
+        if let Some(len) = skip_until(rest, "<!--", "-->") {
+            rest = &rest[len..];
+        } else if let Some(stripped) = rest.strip_prefix("<![CDATA[") {
+            let end = stripped.find("]]>").unwrap_or(stripped.len());
+            tokens.push(Token::Text(Cow::Borrowed(&stripped[..end])));
+            rest = stripped.get(end + 3..).unwrap_or("");
+        } else if let Some(len) =
+            skip_until(rest, "<!", ">").or_else(|| skip_until(rest, "<?", ">"))
+        {
+            rest = &rest[len..];
+        } else if let Some((token, len)) = tag(rest) {
+            tokens.push(token);
+            rest = &rest[len..];
+        } else {
+            tokens.push(Token::Text(Cow::Borrowed("<")));
+            rest = &rest[1..];
+        }
+    }
+    tokens
+}
+
+/// Read a start or end tag at the start of `input`, returning it and its length
+fn tag(input: &str) -> Option<(Token<'_>, usize)> {
+    if !input[1..].starts_with(|c: char| c.is_alphabetic() || c == '/') {
+        return None;
+    }
+    let mut quote = None;
+    let end = input.char_indices().find_map(|(i, c)| {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+        None
+    })?;
+    let inner = &input[1..end];
+    if let Some(name) = inner.strip_prefix('/') {
+        return Some((Token::End { name: name.trim() }, end + 1));
+    }
+    let (inner, self_closing) = match inner.strip_suffix('/') {
+        Some(inner) => (inner, true),
+        None => (inner, false),
+    };
+    let name_end = inner
+        .find(|c: char| c.is_whitespace())
+        .unwrap_or(inner.len());
+    let name = &inner[..name_end];
+    let token = Token::Start {
+        name,
+        attributes: attributes(&inner[name_end..]),
+        self_closing,
+    };
+    Some((token, end + 1))
+}
+
+fn attributes(mut input: &str) -> Vec<(&str, Cow<'_, str>)> {
+    let mut attributes = Vec::new();
+    loop {
+        input = input.trim_start();
+        let name_end = input
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(input.len());
+        if name_end == 0 {
+            return attributes;
+        }
+        let name = &input[..name_end];
+        input = input[name_end..].trim_start();
+        let Some(value) = input.strip_prefix('=') else {
+            attributes.push((name, Cow::Borrowed("")));
+            continue;
+        };
+        let value = value.trim_start();
+        let (raw, rest) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value[1..];
+                let end = value.find(quote).unwrap_or(value.len());
+                (&value[..end], value.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attributes.push((name, decode(raw)));
+        input = rest;
+    }
+}
+
+/// Resolve character references; unknown ones are kept as-is
+pub(crate) fn decode(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let resolved = rest.find(';').and_then(|semi| {
+            let c = match &rest[1..semi] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                entity => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, semi + 1))
+        });
+        match resolved {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Escape text for use in element content or a double-quoted attribute
+pub(crate) fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len() + 8);
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize(
+            "<!DOCTYPE x><!-- <A> --><DT><A HREF=\"a?b=1&amp;c=2\" TITLE='>' ICON=x PRIVATE>Fish &amp; Chips</A><br/>",
+        );
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Start {
+                    name: "DT",
+                    attributes: vec![],
+                    self_closing: false
+                },
+                Token::Start {
+                    name: "A",
+                    attributes: vec![
+                        ("HREF", Cow::Borrowed("a?b=1&c=2")),
+                        ("TITLE", Cow::Borrowed(">")),
+                        ("ICON", Cow::Borrowed("x")),
+                        ("PRIVATE", Cow::Borrowed(""))
+                    ],
+                    self_closing: false
+                },
+                Token::Text(Cow::Borrowed("Fish & Chips")),
+                Token::End { name: "A" },
+                Token::Start {
+                    name: "br",
+                    attributes: vec![],
+                    self_closing: true
+                },
+            ]
+        );
+        assert_eq!(tokens[1].attribute("href"), Some("a?b=1&c=2"));
+        assert!(tokens[3].is_end("a"));
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(
+            tokenize("a < b <![CDATA[<x>]]> &bogus; &#x1F980;"),
+            vec![
+                Token::Text(Cow::Borrowed("a ")),
+                Token::Text(Cow::Borrowed("<")),
+                Token::Text(Cow::Borrowed(" b ")),
+                Token::Text(Cow::Borrowed("<x>")),
+                Token::Text(Cow::Borrowed(" &bogus; 🦀")),
+            ]
+        );
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! Conversion to and from other bookmark formats
+//!
+//! Browsers keep bookmarks in nested folders, which map onto categories and
+//! their subcategories:
+//!
+//! - every top-level folder becomes a top-level category, and every folder
+//!   inside it a subcategory;
+//! - bookmarks outside of any folder go into a category named after the
+//!   file's root, or [`UNCATEGORIZED`] if it has no name.
+//!
+//! Line breaks in imported names and descriptions are replaced by spaces.
+//!
+//! [`UNCATEGORIZED`]: crate::parser::UNCATEGORIZED
+
+pub mod chromium;
+pub mod firefox;
+mod json;
+mod markup;
+mod md5;
+pub mod netscape;
+mod sqlite;
+pub mod xbel;
+
+use crate::parser::{self, UNCATEGORIZED};
+use crate::{Bookmark, Category, Header, Sbm};
+use std::collections::BTreeMap;
+
+/// How deeply folders may nest in an imported file, so that hostile input
+/// can't overflow the stack
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// Error returned when a file can't be imported
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImportError {
+    /// Name of the format being imported
+    pub format: &'static str,
+    pub message: String,
+}
+
+impl ImportError {
+    pub(crate) fn new(format: &'static str, message: impl Into<String>) -> ImportError {
+        ImportError {
+            format,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "invalid {} file: {}", self.format, self.message)
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// A folder tree, as read by the importers
+#[derive(Debug, Default, PartialEq, Clone)]
+pub(crate) struct Folder {
+    pub name: String,
+    pub icon: Option<String>,
+    pub bookmarks: Vec<Bookmark>,
+    pub children: Vec<Folder>,
+}
+
+impl Folder {
+    pub fn new(name: &str) -> Folder {
+        Folder {
+            name: name.to_string(),
+            ..Folder::default()
+        }
+    }
+
+    /// Convert the tree below this root folder as described in the module docs
+    pub fn into_sbm(self) -> Sbm {
+        let mut categories = Vec::new();
+        if !self.bookmarks.is_empty() {
+            let root = Folder {
+                name: match self.name.trim() {
+                    "" => UNCATEGORIZED.to_string(),
+                    name => name.to_string(),
+                },
+                icon: self.icon,
+                bookmarks: self.bookmarks,
+                children: Vec::new(),
+            };
+            categories.push(root.into_category());
+        }
+        categories.extend(self.children.into_iter().map(Folder::into_category));
+        Sbm(categories)
+    }
+
+    fn into_category(self) -> Category {
+        Category {
+            header: Header {
+                name: single_line(&self.name),
+                icon: self.icon.map(|i| single_line(&i)),
+                metadata: BTreeMap::new(),
+            },
+            bookmarks: self
+                .bookmarks
+                .into_iter()
+                .map(|b| Bookmark {
+                    name: single_line(&b.name),
+                    description: single_line(&b.description),
+                    url: single_line(&b.url),
+                    tags: b
+                        .tags
+                        .iter()
+                        .map(|t| single_line(t))
+                        .filter(|t| !t.is_empty())
+                        .collect(),
+                    metadata: b
+                        .metadata
+                        .iter()
+                        .map(|(k, v)| (single_line(k), single_line(v)))
+                        .filter(|(k, _)| !k.is_empty() && !k.contains(':'))
+                        .collect(),
+                })
+                .collect(),
+            children: self
+                .children
+                .into_iter()
+                .map(Folder::into_category)
+                .collect(),
+        }
+    }
+}
+
+impl From<&Category> for Folder {
+    fn from(category: &Category) -> Folder {
+        Folder {
+            name: category.header.name.clone(),
+            icon: category.header.icon.clone(),
+            bookmarks: category.bookmarks.clone(),
+            children: category.children.iter().map(Folder::from).collect(),
+        }
+    }
+}
+
+/// Collapse whitespace containing line breaks into single spaces and trim,
+/// so that the text fits on one SBM line
+pub(crate) fn single_line(text: &str) -> String {
+    if !text.contains(['\n', '\r']) {
+        return text.trim().to_string();
+    }
+    text.split(['\n', '\r'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Join tags into a comma-separated list, escaped so that
+/// [`parser::split_tags`] reads them back
+pub(crate) fn join_tags(tags: &[String]) -> String {
+    let tags: Vec<_> = tags.iter().map(|t| parser::escape_tag(t)).collect();
+    tags.join(",")
+}
+
+/// Check each category's path, joined with ` / `, and the names of its bookmarks
+#[cfg(test)]
+pub(crate) fn assert_outline(sbm: &Sbm, expected: Vec<(&str, Vec<&str>)>) {
+    let mut path: Vec<&str> = Vec::new();
+    let actual: Vec<(String, Vec<&str>)> = sbm
+        .walk()
+        .map(|(depth, category)| {
+            path.truncate(depth - 1);
+            path.push(&category.header.name);
+            let names = category.bookmarks.iter().map(|b| b.name.as_str()).collect();
+            (path.join(" / "), names)
+        })
+        .collect();
+    let expected: Vec<(String, Vec<&str>)> = expected
+        .into_iter()
+        .map(|(path, names)| (path.to_string(), names))
+        .collect();
+    assert_eq!(actual, expected);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_sbm() {
+        let mut root = Folder::new("Bookmarks");
+        root.bookmarks
+            .push(Bookmark::new("Loose", "", "https://example.com/"));
+        let mut bar = Folder::new("Bookmarks bar");
+        let mut work = Folder::new("Work");
+        work.children.push(Folder::new("Empty"));
+        let mut infra = Folder::new("Infra");
+        infra.bookmarks.push(Bookmark::new(
+            "Grafana",
+            "dash\n    boards",
+            "https://grafana.example.com/",
+        ));
+        work.children.push(infra);
+        bar.children.push(work);
+        root.children.push(bar);
+
+        let sbm = root.into_sbm();
+        let names: Vec<(usize, &str)> = sbm
+            .walk()
+            .map(|(depth, c)| (depth, c.header.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (1, "Bookmarks"),
+                (1, "Bookmarks bar"),
+                (2, "Work"),
+                (3, "Empty"),
+                (3, "Infra")
+            ]
+        );
+        assert!(sbm.0[0].children.is_empty());
+        let infra = sbm.find(&["Bookmarks bar", "Work", "Infra"]).unwrap();
+        assert_eq!(infra.bookmarks[0].description, "dash boards");
+
+        let mut root = Folder::default();
+        root.bookmarks.push(Bookmark::new("Loose", "", "x"));
+        assert_eq!(root.into_sbm().0[0].header.name, UNCATEGORIZED);
+    }
+}
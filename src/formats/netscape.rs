@@ -0,0 +1,340 @@
+//! Netscape bookmark file (`bookmarks.html`)
+//!
+//! This is the format every browser imports and exports. Folders (`<H3>`)
+//! become categories as described in the [module docs](super), links
+//! (`<A HREF>`) become bookmarks and the `<DD>` text following a link becomes
+//! its description. Links outside of any folder go into a category named
+//! after the file's `<H1>` title.
+//!
+//! Firefox keeps a link's tags in its `TAGS` attribute, as a comma-separated
+//! list; they become the bookmark's tags. Commas and backslashes inside a
+//! tag are escaped with a backslash, as in an SBM tags field. Its keyword, in
+//! the `SHORTCUTURL` attribute, becomes `keyword` metadata. Favicons (the
+//! `ICON` attribute of links) are dropped, as SBM has no place for them. A
+//! category's icon is written as an `ICON` attribute on its folder, and read
+//! back from there.
+
+use super::markup::{self, Token};
+use super::{join_tags, Folder, ImportError, MAX_DEPTH};
+use crate::{parser, Bookmark, Category, Sbm};
+use std::iter::Peekable;
+
+const FORMAT: &str = "Netscape bookmark";
+
+/// Read a Netscape bookmark file
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::netscape;
+/// let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+/// <TITLE>Bookmarks</TITLE>
+/// <H1>Bookmarks</H1>
+/// <DL><p>
+///     <DT><H3>Languages</H3>
+///     <DL><p>
+///         <DT><A HREF="https://www.rust-lang.org/">Rust</A>
+///         <DD>Systems programming language
+///     </DL><p>
+/// </DL><p>"#;
+/// let sbm = netscape::import(html).unwrap();
+/// assert_eq!(sbm.0[0].header.name, "Languages");
+/// assert_eq!(sbm.0[0].bookmarks[0].description, "Systems programming language");
+/// ```
+pub fn import(html: &str) -> Result<Sbm, ImportError> {
+    let tokens = markup::tokenize(html);
+    if !tokens
+        .iter()
+        .any(|t| t.is_start("dl") || t.is_start("h3") || t.is_start("a"))
+    {
+        return Err(ImportError::new(FORMAT, "no bookmarks or folders found"));
+    }
+
+    let mut root = Folder::default();
+    read_list(&mut tokens.into_iter().peekable(), &mut root, 0)?;
+    Ok(root.into_sbm())
+}
+
+/// Read the items of a `<DL>` into `folder`, up to and including its `</DL>`
+///
+/// `depth` counts the lists around this one, whether or not they belong to
+/// a folder.
+fn read_list<'a, I>(
+    tokens: &mut Peekable<I>,
+    folder: &mut Folder,
+    depth: usize,
+) -> Result<(), ImportError>
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    // the folder declared by the last <H3>, waiting for its <DL>
+    let mut pending: Option<usize> = None;
+    // whether the last item was a link, which a <DD> describes
+    let mut after_link = false;
+
+    while let Some(token) = tokens.next() {
+        if token.is_start("h1") && depth == 0 {
+            folder.name = text(tokens, "h1");
+        } else if token.is_start("h3") {
+            let mut child = Folder::new(&text(tokens, "h3"));
+            child.icon = token
+                .attribute("icon")
+                .filter(|i| !i.is_empty())
+                .map(str::to_string);
+            folder.children.push(child);
+            pending = Some(folder.children.len() - 1);
+            after_link = false;
+        } else if token.is_start("a") {
+            let name = text(tokens, "a");
+            if let Some(url) = token.attribute("href") {
+                let tags = parser::split_tags(token.attribute("tags").unwrap_or(""));
+                let mut bookmark = Bookmark::new(&name, "", url).with_tags(tags);
+                if let Some(keyword) = token.attribute("shortcuturl").filter(|k| !k.is_empty()) {
+                    bookmark = bookmark.with_metadata("keyword", keyword);
+                }
+                folder.bookmarks.push(bookmark);
+                after_link = true;
+            }
+            pending = None;
+        } else if token.is_start("dd") {
+            let description = text(tokens, "dd");
+            if let (true, Some(bookmark)) = (after_link, folder.bookmarks.last_mut()) {
+                bookmark.description = description;
+            }
+            after_link = false;
+        } else if token.is_start("dl") {
+            if depth == MAX_DEPTH {
+                return Err(ImportError::new(FORMAT, "folders nested too deep"));
+            }
+            match pending.take() {
+                Some(i) => read_list(tokens, &mut folder.children[i], depth + 1)?,
+                None => read_list(tokens, folder, depth + 1)?,
+            }
+        } else if token.is_end("dl") {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Collect the text up to the end tag `tag`, or the next structural tag if
+/// the end tag is missing
+fn text<'a, I>(tokens: &mut Peekable<I>, tag: &str) -> String
+where
+    I: Iterator<Item = Token<'a>>,
+{
+    let mut text = String::new();
+    while let Some(token) = tokens.peek() {
+        match token {
+            Token::Text(t) => text.push_str(t),
+            t if t.is_end(tag) => {
+                tokens.next();
+                break;
+            }
+            t if ["dt", "dd", "dl", "h3", "a", "hr"]
+                .iter()
+                .any(|name| t.is_start(name) || t.is_end(name)) =>
+            {
+                break
+            }
+            _ => {}
+        }
+        tokens.next();
+    }
+    text.trim().to_string()
+}
+
+/// Write a Netscape bookmark file
+///
+/// Every category becomes a folder, with its subcategories nested inside.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::netscape;
+/// use sbm::Sbm;
+/// let sbm: Sbm = "#Languages\nRust|Systems programming language|https://www.rust-lang.org/".parse().unwrap();
+/// let html = netscape::export(&sbm);
+/// assert!(html.contains(r#"<DT><A HREF="https://www.rust-lang.org/">Rust</A>"#));
+/// assert_eq!(netscape::import(&html).unwrap(), sbm);
+/// ```
+pub fn export(sbm: &Sbm) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+",
+    );
+    for category in &sbm.0 {
+        write_folder(&mut out, category, 1);
+    }
+    out.push_str("</DL><p>\n");
+    out
+}
+
+/// Write a category as an `<H3>` folder, with its subcategories nested inside
+fn write_folder(out: &mut String, category: &Category, depth: usize) {
+    let indent = "    ".repeat(depth);
+    let icon = match &category.header.icon {
+        Some(icon) => format!(" ICON=\"{}\"", markup::escape(icon)),
+        None => String::new(),
+    };
+    out.push_str(&format!(
+        "{indent}<DT><H3{}>{}</H3>\n{indent}<DL><p>\n",
+        icon,
+        markup::escape(&category.header.name)
+    ));
+    for bookmark in &category.bookmarks {
+        let tags = match bookmark.tags.is_empty() {
+            true => String::new(),
+            false => format!(" TAGS=\"{}\"", markup::escape(&join_tags(&bookmark.tags))),
+        };
+        let keyword = match bookmark.metadata.get("keyword") {
+            Some(keyword) => format!(" SHORTCUTURL=\"{}\"", markup::escape(keyword)),
+            None => String::new(),
+        };
+        out.push_str(&format!(
+            "{indent}    <DT><A HREF=\"{}\"{}{}>{}</A>\n",
+            markup::escape(&bookmark.url),
+            tags,
+            keyword,
+            markup::escape(&bookmark.name)
+        ));
+        if !bookmark.description.is_empty() {
+            out.push_str(&format!(
+                "{indent}    <DD>{}\n",
+                markup::escape(&bookmark.description)
+            ));
+        }
+    }
+    for child in &category.children {
+        write_folder(out, child, depth + 1);
+    }
+    out.push_str(&format!("{indent}</DL><p>\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::assert_outline;
+    use crate::Header;
+
+    const CHROME: &str = include_str!("../../tests/fixtures/netscape/chrome.html");
+    const FIREFOX: &str = include_str!("../../tests/fixtures/netscape/firefox.html");
+    const SAFARI: &str = include_str!("../../tests/fixtures/netscape/safari.html");
+
+    fn assert_roundtrip(sbm: &Sbm) {
+        assert_eq!(&import(&export(sbm)).unwrap(), sbm);
+    }
+
+    #[test]
+    fn test_chrome() {
+        let sbm = import(CHROME).unwrap();
+        assert_outline(
+            &sbm,
+            vec![
+                ("Bookmarks", vec!["Hacker News"]),
+                ("Bookmarks bar", vec!["Rust Programming Language"]),
+                ("Bookmarks bar / Work", vec!["GitHub", "Search \"A\" & B"]),
+                ("Bookmarks bar / Work / Empty", vec![]),
+            ],
+        );
+        assert_eq!(
+            sbm.0[1].children[0].bookmarks[1].url,
+            "https://example.com/search?q=a&b=c"
+        );
+        assert_roundtrip(&sbm);
+    }
+
+    #[test]
+    fn test_firefox() {
+        let sbm = import(FIREFOX).unwrap();
+        assert_outline(
+            &sbm,
+            vec![
+                ("Bookmarks Menu", vec!["Getting Started"]),
+                ("Bookmarks Toolbar", vec!["MDN Web Docs", "Rust 🦀"]),
+                ("Other Bookmarks", vec!["Unicode ☃ & friends"]),
+            ],
+        );
+        let toolbar = &sbm.0[1].bookmarks;
+        assert_eq!(
+            toolbar[0].description,
+            "Resources for Developers, by Developers"
+        );
+        assert_eq!(toolbar[0].tags, vec!["docs", "web"]);
+        assert_eq!(toolbar[1].description, "");
+        assert_roundtrip(&sbm);
+    }
+
+    #[test]
+    fn test_safari() {
+        let sbm = import(SAFARI).unwrap();
+        assert_outline(
+            &sbm,
+            vec![
+                ("Favourites", vec!["Apple"]),
+                ("Bookmarks Menu", vec![]),
+                ("Reading List", vec!["An article"]),
+            ],
+        );
+        assert_eq!(sbm.0[2].bookmarks[0].description, "Saved for later");
+        assert_roundtrip(&sbm);
+    }
+
+    #[test]
+    fn test_export() {
+        let sbm = Sbm(vec![Category {
+            header: Header::new("Tools <& more>", Some("🔧")),
+            bookmarks: vec![Bookmark::new(
+                "Search",
+                "Uses \"quotes\"",
+                "https://example.com/?a=1&b=2",
+            )
+            .with_tags(["a&b", "c"])
+            .with_metadata("keyword", "s")],
+            children: vec![Category::new(Header::new("Nested", None))],
+        }]);
+        let html = export(&sbm);
+        assert!(html.contains(
+            "    <DT><H3 ICON=\"🔧\">Tools &lt;&amp; more&gt;</H3>\n    <DL><p>\n        <DT><A HREF=\"https://example.com/?a=1&amp;b=2\" TAGS=\"a&amp;b,c\" SHORTCUTURL=\"s\">Search</A>\n        <DD>Uses &quot;quotes&quot;\n        <DT><H3>Nested</H3>\n        <DL><p>\n        </DL><p>\n    </DL><p>\n"
+        ));
+        assert_roundtrip(&sbm);
+        assert_roundtrip(&Sbm(Vec::new()));
+    }
+
+    #[test]
+    fn test_tag_with_comma() {
+        let bookmark = Bookmark::new("A", "", "https://example.com/").with_tags(["a,b", r"c\d"]);
+        let sbm = Sbm(vec![Category {
+            bookmarks: vec![bookmark],
+            ..Category::new(Header::new("Tags", None))
+        }]);
+        assert!(export(&sbm).contains(r#"TAGS="a\,b,c\\d""#));
+        assert_roundtrip(&sbm);
+    }
+
+    #[test]
+    fn test_not_netscape() {
+        assert!(import("just some text").is_err());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested =
+            |depth: usize| "<DT><H3>f</H3><DL><p>".repeat(depth) + &"</DL><p>".repeat(depth);
+        assert!(import(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            import(&nested(MAX_DEPTH + 1)).unwrap_err().to_string(),
+            "invalid Netscape bookmark file: folders nested too deep"
+        );
+        // lists without folders count too
+        assert!(import(&"<DL>".repeat(MAX_DEPTH + 1)).is_err());
+        assert!(import(&nested(100_000)).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//! Read-only access to the tables of an SQLite database file
+//!
+//! Supports what browser profiles need: rowid tables of any size, overflow
+//! pages, columns added with `ALTER TABLE` and UTF-8 text. Indexes, `WITHOUT
+//! ROWID` tables and write-ahead logs are ignored, so the file must have
+//! been checkpointed, which happens when the browser closes.
+
+use std::collections::HashSet;
+
+const MAGIC: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Blob(Vec<u8>),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// A table's rows, in rowid order
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl Table {
+    /// The value of a named column in a row, `Null` if there is no such column
+    pub fn get<'t>(&self, row: &'t [Value], column: &str) -> &'t Value {
+        self.columns
+            .iter()
+            .position(|c| c.eq_ignore_ascii_case(column))
+            .and_then(|i| row.get(i))
+            .unwrap_or(&Value::Null)
+    }
+}
+
+pub(crate) struct Database<'a> {
+    data: &'a [u8],
+    page_size: usize,
+    usable_size: usize,
+}
+
+impl<'a> Database<'a> {
+    pub fn open(data: &'a [u8]) -> Result<Database<'a>, String> {
+        if data.len() < 100 || !data.starts_with(MAGIC) {
+            return Err("not an SQLite database".to_string());
+        }
+        let page_size = match u16::from_be_bytes([data[16], data[17]]) {
+            1 => 65536,
+            size => size as usize,
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(format!("invalid page size {}", page_size));
+        }
+        if u32::from_be_bytes(data[56..60].try_into().unwrap()) > 1 {
+            return Err("only UTF-8 databases are supported".to_string());
+        }
+        Ok(Database {
+            data,
+            page_size,
+            usable_size: page_size - data[20] as usize,
+        })
+    }
+
+    /// Read a whole table, or `None` if the database has no such table
+    pub fn table(&self, name: &str) -> Result<Option<Table>, String> {
+        let schema = Table {
+            columns: ["type", "name", "tbl_name", "rootpage", "sql"]
+                .map(String::from)
+                .to_vec(),
+            rows: self.rows(1, None)?,
+        };
+        let Some(row) = schema.rows.iter().find(|row| {
+            schema.get(row, "type").as_str() == Some("table")
+                && schema
+                    .get(row, "name")
+                    .as_str()
+                    .is_some_and(|n| n.eq_ignore_ascii_case(name))
+        }) else {
+            return Ok(None);
+        };
+        let root = schema.get(row, "rootpage").as_i64().unwrap_or(0);
+        let sql = schema.get(row, "sql").as_str().unwrap_or("");
+        let (columns, rowid_alias) = columns(sql);
+        let root = usize::try_from(root).map_err(|_| format!("invalid root page {}", root))?;
+        let rows = self.rows(root, rowid_alias)?;
+        Ok(Some(Table { columns, rows }))
+    }
+
+    fn page(&self, number: usize) -> Result<&'a [u8], String> {
+        let start = number
+            .checked_sub(1)
+            .ok_or("invalid page number 0")?
+            .checked_mul(self.page_size);
+        start
+            .and_then(|start| Some(start..start.checked_add(self.page_size)?))
+            .and_then(|range| self.data.get(range))
+            .ok_or_else(|| format!("page {} is past the end of the file", number))
+    }
+
+    /// Read every row of the table b-tree rooted at `root`, putting the rowid
+    /// into column `rowid_alias` if it is an `INTEGER PRIMARY KEY`
+    fn rows(&self, root: usize, rowid_alias: Option<usize>) -> Result<Vec<Vec<Value>>, String> {
+        let mut rows = Vec::new();
+        let mut stack = vec![root];
+        let mut seen = HashSet::new();
+        while let Some(number) = stack.pop() {
+            if !seen.insert(number) {
+                return Err(format!("page {} is referenced twice", number));
+            }
+            let page = self.page(number)?;
+            let header = if number == 1 { 100 } else { 0 };
+            let count = u16::from_be_bytes([page[header + 3], page[header + 4]]) as usize;
+            let (interior, pointers) = match page[header] {
+                0x05 => (true, header + 12),
+                0x0d => (false, header + 8),
+                kind => return Err(format!("page {} is not a table page ({:#x})", number, kind)),
+            };
+            let cell = |i: usize| -> Result<&[u8], String> {
+                let at = pointers + 2 * i;
+                let offset = page
+                    .get(at..at + 2)
+                    .map(|p| u16::from_be_bytes([p[0], p[1]]) as usize)
+                    .ok_or("cell pointer out of bounds")?;
+                page.get(offset..)
+                    .ok_or_else(|| "cell out of bounds".to_string())
+            };
+
+            if interior {
+                // push in reverse so that pages are visited in rowid order
+                let right = u32::from_be_bytes(page[header + 8..header + 12].try_into().unwrap());
+                stack.push(right as usize);
+                for i in (0..count).rev() {
+                    let child = cell(i)?.get(..4).ok_or("truncated cell")?;
+                    stack.push(u32::from_be_bytes(child.try_into().unwrap()) as usize);
+                }
+                continue;
+            }
+            for i in 0..count {
+                let cell = cell(i)?;
+                let (size, n) = varint(cell)?;
+                let (rowid, m) = varint(&cell[n..])?;
+                let payload = self.payload(&cell[n + m..], size as usize)?;
+                let mut row = record(&payload)?;
+                if let Some(alias) = rowid_alias {
+                    if row.len() <= alias {
+                        row.resize(alias + 1, Value::Null);
+                    }
+                    if row[alias] == Value::Null {
+                        row[alias] = Value::Integer(rowid as i64);
+                    }
+                }
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Assemble a cell's payload, following overflow pages
+    fn payload(&self, cell: &[u8], size: usize) -> Result<Vec<u8>, String> {
+        let usable = self.usable_size;
+        let max_local = usable - 35;
+        let local = if size <= max_local {
+            size
+        } else {
+            let min_local = (usable - 12) * 32 / 255 - 23;
+            let k = min_local + (size - min_local) % (usable - 4);
+            if k <= max_local {
+                k
+            } else {
+                min_local
+            }
+        };
+        let mut payload = cell.get(..local).ok_or("truncated cell")?.to_vec();
+        if local == size {
+            return Ok(payload);
+        }
+
+        let next = cell.get(local..local + 4).ok_or("truncated cell")?;
+        let mut next = u32::from_be_bytes(next.try_into().unwrap()) as usize;
+        let mut seen = HashSet::new();
+        while payload.len() < size {
+            if next == 0 || !seen.insert(next) {
+                return Err("broken overflow chain".to_string());
+            }
+            let page = self.page(next)?;
+            let take = (size - payload.len()).min(usable - 4);
+            payload.extend_from_slice(&page[4..4 + take]);
+            next = u32::from_be_bytes(page[..4].try_into().unwrap()) as usize;
+        }
+        Ok(payload)
+    }
+}
+
+/// Read a big-endian variable-length integer, returning it and its length
+fn varint(data: &[u8]) -> Result<(u64, usize), String> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().take(9).enumerate() {
+        if i == 8 {
+            return Ok(((value << 8) | byte as u64, 9));
+        }
+        value = (value << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("truncated varint".to_string())
+}
+
+/// Decode a record into its column values
+fn record(payload: &[u8]) -> Result<Vec<Value>, String> {
+    let (header_size, mut at) = varint(payload)?;
+    let mut types = Vec::new();
+    while at < header_size as usize {
+        let (serial, n) = varint(payload.get(at..).ok_or("truncated record")?)?;
+        types.push(serial);
+        at += n;
+    }
+
+    let mut body = payload
+        .get(header_size as usize..)
+        .ok_or("truncated record")?;
+    let mut values = Vec::with_capacity(types.len());
+    for serial in types {
+        let len = match serial {
+            0 | 8 | 9 => 0,
+            1..=4 => serial as usize,
+            5 => 6,
+            6 | 7 => 8,
+            10 | 11 => return Err("reserved serial type".to_string()),
+            n => (n as usize - 12) / 2,
+        };
+        let bytes = body.get(..len).ok_or("truncated record")?;
+        body = &body[len..];
+        let integer = || {
+            // sign-extend from the top byte
+            let fill = if bytes[0] & 0x80 != 0 { 0xff } else { 0 };
+            let mut buf = [fill; 8];
+            buf[8 - len..].copy_from_slice(bytes);
+            i64::from_be_bytes(buf)
+        };
+        values.push(match serial {
+            0 => Value::Null,
+            1..=6 => Value::Integer(integer()),
+            7 => Value::Real(f64::from_be_bytes(bytes.try_into().unwrap())),
+            8 => Value::Integer(0),
+            9 => Value::Integer(1),
+            n if n % 2 == 0 => Value::Blob(bytes.to_vec()),
+            _ => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+        });
+    }
+    Ok(values)
+}
+
+/// Column names from a `CREATE TABLE` statement, and the index of the
+/// `INTEGER PRIMARY KEY` column, which holds the rowid
+fn columns(sql: &str) -> (Vec<String>, Option<usize>) {
+    let (Some(open), Some(close)) = (sql.find('('), sql.rfind(')')) else {
+        return (Vec::new(), None);
+    };
+    let body = &sql[open + 1..close];
+    let mut definitions = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in body.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                definitions.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    definitions.push(&body[start..]);
+
+    let mut columns = Vec::new();
+    let mut rowid_alias = None;
+    for definition in definitions {
+        let words: Vec<String> = definition
+            .split_whitespace()
+            .map(|w| w.to_ascii_uppercase())
+            .collect();
+        let Some(first) = words.first() else {
+            continue;
+        };
+        if ["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"].contains(&first.as_str()) {
+            continue;
+        }
+        let primary = words.windows(2).any(|w| w[0] == "PRIMARY" && w[1] == "KEY");
+        if primary && words.get(1).map(String::as_str) == Some("INTEGER") {
+            rowid_alias = Some(columns.len());
+        }
+        let name = definition.split_whitespace().next().unwrap_or("");
+        columns.push(name.trim_matches(['"', '`', '[', ']']).to_string());
+    }
+    (columns, rowid_alias)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        assert_eq!(varint(&[0x05]).unwrap(), (5, 1));
+        assert_eq!(varint(&[0x81, 0x00]).unwrap(), (128, 2));
+        assert_eq!(varint(&[0xff; 9]).unwrap(), (u64::MAX, 9));
+        assert!(varint(&[0x81]).is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        // header: size 5, NULL, 8-bit int, text of length 2, constant 1
+        let payload = [5, 0, 1, 17, 9, 0xfe, b'h', b'i'];
+        assert_eq!(
+            record(&payload).unwrap(),
+            vec![
+                Value::Null,
+                Value::Integer(-2),
+                Value::Text("hi".to_string()),
+                Value::Integer(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_columns() {
+        let (columns, alias) = columns(
+            "CREATE TABLE moz_x ( id INTEGER PRIMARY KEY, \"url\" LONGVARCHAR, n NUMERIC(10, 2) DEFAULT 0, CONSTRAINT c UNIQUE (url))",
+        );
+        assert_eq!(columns, vec!["id", "url", "n"]);
+        assert_eq!(alias, Some(0));
+    }
+
+    /// A database whose schema lists table `t` with this root page, given
+    /// as a serial type and its bytes
+    fn with_root(serial: u8, root: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 512];
+        data[..16].copy_from_slice(MAGIC);
+        data[16..18].copy_from_slice(&512u16.to_be_bytes());
+        data[56..60].copy_from_slice(&1u32.to_be_bytes());
+        // a leaf table page holding one cell at offset 200
+        data[100] = 0x0d;
+        data[104] = 1;
+        data[108..110].copy_from_slice(&200u16.to_be_bytes());
+        let mut payload = vec![6, 23, 15, 15, serial, 47];
+        payload.extend_from_slice(b"tablett");
+        payload.extend_from_slice(root);
+        payload.extend_from_slice(b"CREATE TABLE t(a)");
+        let cell = [&[payload.len() as u8, 1], &payload[..]].concat();
+        data[200..200 + cell.len()].copy_from_slice(&cell);
+        data
+    }
+
+    #[test]
+    fn test_bad_root_page() {
+        let data = with_root(1, &[2]);
+        let err = Database::open(&data).unwrap().table("t").unwrap_err();
+        assert_eq!(err, "page 2 is past the end of the file");
+
+        let data = with_root(1, &[0xff]);
+        let err = Database::open(&data).unwrap().table("t").unwrap_err();
+        assert_eq!(err, "invalid root page -1");
+
+        let data = with_root(6, &i64::MAX.to_be_bytes());
+        let db = Database::open(&data).unwrap();
+        assert!(db.table("t").is_err());
+        assert!(db.page(usize::MAX).is_err());
+    }
+
+    #[test]
+    fn test_not_sqlite() {
+        assert!(Database::open(b"SQLite format 2").is_err());
+        assert!(Database::open(&[0; 200]).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! XBEL, the XML Bookmark Exchange Language
+//!
+//! Used by Konqueror, Midori, Floccus and other sync tools. `<folder>`s
+//! become categories as described in the [module docs](super), and
+//! `<bookmark>`s become bookmarks with their `<title>` and `<desc>`.
+//! Bookmarks outside of any folder go into a category named after the
+//! document's `<title>`. Separators and aliases are dropped, and so are tags
+//! on export, as XBEL has no notion of them.
+//!
+//! A category's icon is kept in the folder's `<info>` as a freedesktop.org
+//! `<bookmark:icon name="...">`, the element KDE uses for folder icons.
+
+use super::markup::{self, Token};
+use super::{Folder, ImportError, MAX_DEPTH};
+use crate::{Bookmark, Category, Sbm};
+use std::vec::IntoIter;
+
+const FORMAT: &str = "XBEL";
+
+/// Read an XBEL document
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::xbel;
+/// let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+/// <xbel version="1.0">
+///   <folder>
+///     <title>Languages</title>
+///     <bookmark href="https://www.rust-lang.org/">
+///       <title>Rust</title>
+///       <desc>Fast &amp; reliable</desc>
+///     </bookmark>
+///   </folder>
+/// </xbel>"#;
+/// let sbm = xbel::import(xml).unwrap();
+/// assert_eq!(sbm.0[0].header.name, "Languages");
+/// assert_eq!(sbm.0[0].bookmarks[0].description, "Fast & reliable");
+/// ```
+pub fn import(xml: &str) -> Result<Sbm, ImportError> {
+    let mut tokens = markup::tokenize(xml).into_iter();
+    if !tokens.any(|t| t.is_start("xbel")) {
+        return Err(ImportError::new(FORMAT, "missing <xbel> element"));
+    }
+    let mut root = Folder::default();
+    read_folder(&mut tokens, &mut root, "xbel", 0)?;
+    Ok(root.into_sbm())
+}
+
+/// Read the contents of a folder (or the document, at `depth` 0), up to its
+/// end tag
+fn read_folder(
+    tokens: &mut IntoIter<Token>,
+    folder: &mut Folder,
+    tag: &str,
+    depth: usize,
+) -> Result<(), ImportError> {
+    while let Some(token) = tokens.next() {
+        let Token::Start {
+            name, self_closing, ..
+        } = &token
+        else {
+            if token.is_end(tag) {
+                return Ok(());
+            }
+            continue;
+        };
+        if *self_closing {
+            continue;
+        }
+        match name.to_ascii_lowercase().as_str() {
+            "title" => folder.name = text(tokens, "title"),
+            "info" => {
+                if let Some(icon) = icon(tokens) {
+                    folder.icon = Some(icon);
+                }
+            }
+            "folder" => {
+                if depth == MAX_DEPTH {
+                    return Err(ImportError::new(FORMAT, "folders nested too deep"));
+                }
+                let mut child = Folder::default();
+                read_folder(tokens, &mut child, "folder", depth + 1)?;
+                folder.children.push(child);
+            }
+            "bookmark" => {
+                let url = token.attribute("href").unwrap_or("");
+                let mut bookmark = Bookmark::new("", "", url);
+                read_bookmark(tokens, &mut bookmark);
+                folder.bookmarks.push(bookmark);
+            }
+            other => skip(tokens, other),
+        }
+    }
+    Ok(())
+}
+
+fn read_bookmark(tokens: &mut IntoIter<Token>, bookmark: &mut Bookmark) {
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Start {
+                name,
+                self_closing: false,
+                ..
+            } => match name.to_ascii_lowercase().as_str() {
+                "title" => bookmark.name = text(tokens, "title"),
+                "desc" => bookmark.description = text(tokens, "desc"),
+                other => skip(tokens, other),
+            },
+            t if t.is_end("bookmark") => return,
+            _ => {}
+        }
+    }
+}
+
+/// Read an `<info>` element, returning the icon it declares
+fn icon(tokens: &mut IntoIter<Token>) -> Option<String> {
+    let mut icon = None;
+    for token in tokens.by_ref() {
+        if token.is_end("info") {
+            break;
+        }
+        if token.is_start("bookmark:icon") {
+            icon = token
+                .attribute("name")
+                .filter(|name| !name.is_empty())
+                .map(str::to_string);
+        }
+    }
+    icon
+}
+
+/// The text inside an element, up to its end tag
+fn text(tokens: &mut IntoIter<Token>, tag: &str) -> String {
+    let mut text = String::new();
+    for token in tokens.by_ref() {
+        match token {
+            Token::Text(t) => text.push_str(&t),
+            t if t.is_end(tag) => break,
+            _ => {}
+        }
+    }
+    text
+}
+
+/// Skip an element, up to its end tag
+fn skip(tokens: &mut IntoIter<Token>, tag: &str) {
+    let mut depth = 0;
+    for token in tokens.by_ref() {
+        match &token {
+            Token::Start {
+                self_closing: false,
+                ..
+            } if token.is_start(tag) => depth += 1,
+            Token::End { .. } if token.is_end(tag) => {
+                if depth == 0 {
+                    return;
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Write an `Sbm` as an XBEL document
+///
+/// Every category becomes a folder, with its subcategories nested inside.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::formats::xbel;
+/// use sbm::Sbm;
+/// let sbm: Sbm = "#Languages\nRust|Fast & reliable|https://www.rust-lang.org/".parse().unwrap();
+/// let xml = xbel::export(&sbm);
+/// assert!(xml.contains("<desc>Fast &amp; reliable</desc>"));
+/// assert_eq!(xbel::import(&xml).unwrap(), sbm);
+/// ```
+pub fn export(sbm: &Sbm) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<!DOCTYPE xbel PUBLIC \"+//IDN python.org//DTD XML Bookmark Exchange Language 1.0//EN//XML\" \"http://pyxml.sourceforge.net/topics/dtds/xbel.dtd\">
+<xbel version=\"1.0\" xmlns:bookmark=\"http://www.freedesktop.org/standards/desktop-bookmarks\">
+",
+    );
+    for category in &sbm.0 {
+        write_folder(&mut out, category, 1);
+    }
+    out.push_str("</xbel>\n");
+    out
+}
+
+fn write_folder(out: &mut String, category: &Category, depth: usize) {
+    let indent = "  ".repeat(depth);
+    out.push_str(&format!(
+        "{indent}<folder>\n{indent}  <title>{}</title>\n",
+        markup::escape(&category.header.name)
+    ));
+    if let Some(icon) = &category.header.icon {
+        out.push_str(&format!(
+            "{indent}  <info>\n{indent}    <metadata owner=\"http://freedesktop.org\">\n{indent}      <bookmark:icon name=\"{}\"/>\n{indent}    </metadata>\n{indent}  </info>\n",
+            markup::escape(icon)
+        ));
+    }
+    for bookmark in &category.bookmarks {
+        out.push_str(&format!(
+            "{indent}  <bookmark href=\"{}\">\n{indent}    <title>{}</title>\n",
+            markup::escape(&bookmark.url),
+            markup::escape(&bookmark.name)
+        ));
+        if !bookmark.description.is_empty() {
+            out.push_str(&format!(
+                "{indent}    <desc>{}</desc>\n",
+                markup::escape(&bookmark.description)
+            ));
+        }
+        out.push_str(&format!("{indent}  </bookmark>\n"));
+    }
+    for child in &category.children {
+        write_folder(out, child, depth + 1);
+    }
+    out.push_str(&format!("{indent}</folder>\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::assert_outline;
+    use crate::Header;
+
+    const KONQUEROR: &str = include_str!("../../tests/fixtures/xbel/konqueror.xbel");
+
+    fn assert_roundtrip(sbm: &Sbm) {
+        assert_eq!(&import(&export(sbm)).unwrap(), sbm);
+    }
+
+    #[test]
+    fn test_import() {
+        let sbm = import(KONQUEROR).unwrap();
+        assert_outline(
+            &sbm,
+            vec![
+                ("Uncategorized", vec!["KDE"]),
+                ("Toolbar", vec!["Rust 🦀"]),
+                ("Toolbar / Docs & References", vec!["std <docs>"]),
+                ("Ünïcödé — 日本語", vec!["日本語 - Wikipedia"]),
+            ],
+        );
+        assert_eq!(sbm.0[0].header.icon, None);
+        assert_eq!(sbm.0[1].header.icon.as_deref(), Some("bookmark-toolbar"));
+        assert_eq!(
+            sbm.0[1].bookmarks[0].description,
+            "A language empowering everyone to build reliable & efficient software"
+        );
+        assert_eq!(
+            sbm.0[1].children[0].bookmarks[0].url,
+            "https://doc.rust-lang.org/std/?search=a&b"
+        );
+        assert_eq!(sbm.0[2].bookmarks[0].description, "\"Japanese\" <language>");
+        assert_roundtrip(&sbm);
+    }
+
+    #[test]
+    fn test_roundtrip_escaping() {
+        let sbm = Sbm(vec![
+            Category {
+                header: Header::new("<Tools> & \"Things\"", Some("🔧")),
+                bookmarks: vec![
+                    Bookmark::new(
+                        "Ünïcödé ☃ 日本語 👨‍💻",
+                        "a < b && c > d; it's \"quoted\"",
+                        "https://example.com/?a=1&b=<2>",
+                    ),
+                    Bookmark::new("&amp; literally", "", "https://example.com/&amp;"),
+                ],
+                children: vec![Category::new(Header::new("Nested", None))],
+            },
+            Category::new(Header::new("Empty", None)),
+        ]);
+        let xml = export(&sbm);
+        assert!(xml.contains("<title>&lt;Tools&gt; &amp; &quot;Things&quot;</title>"));
+        assert!(xml.contains("<title>&amp;amp; literally</title>"));
+        assert!(xml.contains("<bookmark:icon name=\"🔧\"/>"));
+        assert_roundtrip(&sbm);
+        assert_roundtrip(&Sbm(Vec::new()));
+    }
+
+    #[test]
+    fn test_not_xbel() {
+        assert_eq!(
+            import("<html></html>").unwrap_err().to_string(),
+            "invalid XBEL file: missing <xbel> element"
+        );
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        let nested = |depth: usize| {
+            let folders = "<folder><title>f</title>".repeat(depth);
+            format!("<xbel>{}{}</xbel>", folders, "</folder>".repeat(depth))
+        };
+        let mut sbm = import(&nested(MAX_DEPTH)).unwrap();
+        let mut depth = 0;
+        while let Some(category) = sbm.0.pop() {
+            depth += 1;
+            sbm.0 = category.children;
+        }
+        assert_eq!(depth, MAX_DEPTH);
+        assert_eq!(
+            import(&nested(MAX_DEPTH + 1)).unwrap_err().to_string(),
+            "invalid XBEL file: folders nested too deep"
+        );
+        // far deeper than the stack could take
+        assert!(import(&nested(100_000)).is_err());
+    }
+}
@@ -0,0 +1,605 @@
+//! Fuzzy matching for launchers
+//!
+//! [`Index`] is built once from an [`Sbm`] and then answers fuzzy queries the
+//! way rofi, dmenu or fzf do: every character of a query word has to appear in
+//! a bookmark's name, description, URL or category name, in order but not
+//! necessarily next to each other. Matches that start words, run together or
+//! sit in the bookmark's name rank higher. Every [`Match`] carries the
+//! positions of the matched characters, so callers can highlight them.
+//!
+//! The words of a query are matched separately and must all match, each in
+//! whichever field suits it best. Matching ignores case.
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::fuzzy::Index;
+//! use sbm::Sbm;
+//! let sbm: Sbm = "#Languages\nRust|The Rust Programming Language|https://www.rust-lang.org/\nPython|Python Programming Language|https://www.python.org/"
+//!     .parse()
+//!     .unwrap();
+//! let index = Index::new(&sbm);
+//! let matches = index.search("rst");
+//! assert_eq!(matches[0].bookmark.name, "Rust");
+//! assert_eq!(matches[0].positions.name, vec![0, 2, 3]);
+//! ```
+
+use crate::{Bookmark, Category, Sbm};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const SCORE_MATCH: i32 = 16;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+/// A match right after whitespace, or at the start of the text
+const BONUS_BOUNDARY_WHITE: u8 = 10;
+/// A match right after a delimiter such as `/`, `.` or `-`
+const BONUS_BOUNDARY_DELIMITER: u8 = 9;
+/// A match right after any other punctuation
+const BONUS_BOUNDARY: u8 = 8;
+/// A match at a camelCase hump or where digits start
+const BONUS_CAMEL: u8 = 7;
+/// The least a match gets for following another match
+const BONUS_CONSECUTIVE: u8 = 4;
+/// The bonus of the first character of a word counts this many times
+const BONUS_FIRST_MULTIPLIER: i32 = 2;
+
+/// The fields a query word is matched against, and the bonus each adds to
+/// a word matched in it
+const FIELDS: [(Field, i32); 4] = [
+    (Field::Name, 12),
+    (Field::Category, 4),
+    (Field::Description, 0),
+    (Field::Url, 0),
+];
+
+/// A field of a bookmark that fuzzy queries look at
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    Name,
+    Description,
+    Url,
+    /// The name of the bookmark's category
+    Category,
+}
+
+/// Character positions matched in each field, in increasing order
+///
+/// Positions count characters, not bytes, of the original text.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Positions {
+    pub name: Vec<usize>,
+    pub description: Vec<usize>,
+    pub url: Vec<usize>,
+    pub category: Vec<usize>,
+}
+
+impl Positions {
+    /// The positions matched in `field`
+    pub fn get(&self, field: Field) -> &[usize] {
+        match field {
+            Field::Name => &self.name,
+            Field::Description => &self.description,
+            Field::Url => &self.url,
+            Field::Category => &self.category,
+        }
+    }
+
+    fn get_mut(&mut self, field: Field) -> &mut Vec<usize> {
+        match field {
+            Field::Name => &mut self.name,
+            Field::Description => &mut self.description,
+            Field::Url => &mut self.url,
+            Field::Category => &mut self.category,
+        }
+    }
+}
+
+/// A bookmark matching a fuzzy query
+#[derive(Debug, PartialEq, Clone)]
+pub struct Match<'a> {
+    pub category: &'a Category,
+    /// The categories from the top level down to [`Match::category`], as
+    /// [`Query::matches`](crate::query::Query::matches) takes them
+    pub path: Vec<&'a Category>,
+    pub bookmark: &'a Bookmark,
+    /// Higher is better; only comparable between results of the same query
+    pub score: i32,
+    pub positions: Positions,
+}
+
+/// Where a field's text lives in the buffers of an [`Index`]
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+/// The characters of a field, as [`mask`]s
+#[derive(Debug, Clone, Copy)]
+struct Masks {
+    /// Every character
+    all: u64,
+    /// Characters with [`BONUS_BOUNDARY_WHITE`], see [`Word::bound`]
+    white: u64,
+    /// Characters with any other bonus
+    boundary: u64,
+}
+
+/// A field prepared for matching
+#[derive(Debug, Clone, Copy)]
+struct Text<'t> {
+    /// Lowercased characters; a character that lowercases to several keeps
+    /// only the first, so that positions line up with the original
+    chars: &'t [char],
+    /// Bonus for matching each character, from the character before it
+    bonus: &'t [u8],
+}
+
+impl Text<'_> {
+    /// Score `pattern` against this text, recording the matched positions if
+    /// asked to
+    ///
+    /// The leftmost occurrence of the pattern is found first, then narrowed
+    /// from the right to the shortest window ending there.
+    fn score(&self, pattern: &[char], mut positions: Option<&mut Vec<usize>>) -> Option<i32> {
+        if pattern.is_empty() {
+            return None;
+        }
+        let find =
+            |from: usize, c: char| Some(from + self.chars[from..].iter().position(|&x| x == c)?);
+        let mut start = find(0, pattern[0])?;
+        let mut end = start + 1;
+        for &c in &pattern[1..] {
+            end = find(end, c)? + 1;
+        }
+        let mut p = pattern.len();
+        for i in (start..end).rev() {
+            if self.chars[i] == pattern[p - 1] {
+                p -= 1;
+                if p == 0 {
+                    start = i;
+                    break;
+                }
+            }
+        }
+
+        let mut score = 0;
+        let mut p = 0;
+        let mut in_gap = false;
+        let mut consecutive = 0;
+        let mut first_bonus = 0;
+        for i in start..end {
+            if self.chars[i] != pattern[p] {
+                score -= if in_gap {
+                    PENALTY_GAP_EXTENSION
+                } else {
+                    PENALTY_GAP_START
+                };
+                in_gap = true;
+                consecutive = 0;
+                first_bonus = 0;
+                continue;
+            }
+            if let Some(positions) = positions.as_deref_mut() {
+                positions.push(i);
+            }
+            let mut bonus = self.bonus[i];
+            if consecutive == 0 {
+                first_bonus = bonus;
+            } else {
+                // a run keeps the bonus of the word it started in
+                if bonus >= BONUS_BOUNDARY && bonus > first_bonus {
+                    first_bonus = bonus;
+                }
+                bonus = bonus.max(first_bonus).max(BONUS_CONSECUTIVE);
+            }
+            score += SCORE_MATCH
+                + match p {
+                    0 => i32::from(bonus) * BONUS_FIRST_MULTIPLIER,
+                    _ => i32::from(bonus),
+                };
+            in_gap = false;
+            consecutive += 1;
+            p += 1;
+            if p == pattern.len() {
+                break;
+            }
+        }
+        Some(score)
+    }
+}
+
+/// How good a place `c` is to match, given the character before it
+fn bonus_for(prev: Option<char>, c: char) -> u8 {
+    if !c.is_alphanumeric() {
+        return 0;
+    }
+    match prev {
+        None => BONUS_BOUNDARY_WHITE,
+        Some(p) if p.is_whitespace() => BONUS_BOUNDARY_WHITE,
+        Some('/' | ',' | ':' | ';' | '|' | '.' | '-' | '_' | '?' | '&' | '=' | '#') => {
+            BONUS_BOUNDARY_DELIMITER
+        }
+        Some(p) if !p.is_alphanumeric() => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_numeric() && c.is_numeric() => BONUS_CAMEL,
+        Some(_) => 0,
+    }
+}
+
+/// Bit set of the ASCII letters and digits in some text, used to skip
+/// bookmarks that can't possibly match
+fn mask(chars: impl IntoIterator<Item = char>) -> u64 {
+    chars.into_iter().fold(0, |mask, c| mask | bit(c))
+}
+
+/// The bit of `c` in a [`mask`], or 0 for any other character
+fn bit(c: char) -> u64 {
+    match c {
+        'a'..='z' => 1 << (c as u32 - 'a' as u32),
+        '0'..='9' => 1 << (26 + c as u32 - '0' as u32),
+        _ => 0,
+    }
+}
+
+#[derive(Debug)]
+struct Entry<'a> {
+    /// The categories from the top level down to the bookmark's own
+    path: Vec<&'a Category>,
+    bookmark: &'a Bookmark,
+    /// Name, category, description and URL, in the order of [`FIELDS`]
+    fields: [Span; 4],
+    /// Length of the bookmark's name in characters, for breaking ties
+    name_len: usize,
+}
+
+/// A query word, lowercased
+struct Word {
+    chars: Vec<char>,
+    mask: u64,
+    /// Whether the word has letters or digits outside the [`mask`], which
+    /// [`Word::bound`] knows nothing about
+    exotic: bool,
+}
+
+impl Word {
+    fn new(word: &str) -> Word {
+        let chars: Vec<char> = word
+            .chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect();
+        Word {
+            mask: mask(chars.iter().copied()),
+            exotic: chars.iter().any(|&c| bit(c) == 0 && c.is_alphanumeric()),
+            chars,
+        }
+    }
+
+    /// Whether every ASCII letter and digit of the word is in the field
+    fn fits(&self, masks: &Masks) -> bool {
+        self.mask & !masks.all == 0
+    }
+
+    /// The most [`Text::score`] can give this word in a field
+    ///
+    /// Every character is assumed to match with the best bonus that any of
+    /// the word's characters has somewhere in the field, which only takes
+    /// a few masks to work out.
+    fn bound(&self, masks: &Masks) -> i32 {
+        let bonus = |bits: u64| {
+            i32::from(match () {
+                _ if self.exotic || bits & masks.white != 0 => BONUS_BOUNDARY_WHITE,
+                _ if bits & masks.boundary != 0 => BONUS_BOUNDARY_DELIMITER,
+                _ => 0,
+            })
+        };
+        let len = self.chars.len() as i32;
+        let rest = bonus(self.mask).max(i32::from(BONUS_CONSECUTIVE));
+        len * SCORE_MATCH + bonus(bit(self.chars[0])) * BONUS_FIRST_MULTIPLIER + (len - 1) * rest
+    }
+}
+
+/// Fuzzy search index over the bookmarks of an [`Sbm`]
+#[derive(Debug)]
+pub struct Index<'a> {
+    entries: Vec<Entry<'a>>,
+    /// The [`mask`] of each entry, kept apart so that skipping entries is
+    /// cheap
+    masks: Vec<u64>,
+    /// The [`Masks`] of each entry's fields, in the order of [`FIELDS`],
+    /// likewise kept apart so that bounding a score is cheap
+    field_masks: Vec<[Masks; 4]>,
+    /// The text of every field, back to back, so that a search walks
+    /// through memory in order
+    chars: Vec<char>,
+    bonus: Vec<u8>,
+}
+
+impl<'a> Index<'a> {
+    /// Prepare every bookmark of `sbm` for matching
+    pub fn new(sbm: &'a Sbm) -> Index<'a> {
+        let mut index = Index {
+            entries: Vec::new(),
+            masks: Vec::new(),
+            field_masks: Vec::new(),
+            chars: Vec::new(),
+            bonus: Vec::new(),
+        };
+        let mut path: Vec<&Category> = Vec::new();
+        for (depth, category) in sbm.walk() {
+            path.truncate(depth - 1);
+            path.push(category);
+            for bookmark in &category.bookmarks {
+                index.push_entry(&path, bookmark);
+            }
+        }
+        index
+    }
+
+    fn push_entry(&mut self, path: &[&'a Category], bookmark: &'a Bookmark) {
+        let category = path[path.len() - 1];
+        let pushed = [
+            &bookmark.name,
+            &category.header.name,
+            &bookmark.description,
+            &bookmark.url,
+        ]
+        .map(|text| self.push(text));
+        let fields = pushed.map(|(span, _)| span);
+        let masks = pushed.map(|(_, masks)| masks);
+        self.masks.push(masks.iter().fold(0, |m, f| m | f.all));
+        self.field_masks.push(masks);
+        self.entries.push(Entry {
+            path: path.to_vec(),
+            bookmark,
+            name_len: fields[0].end - fields[0].start,
+            fields,
+        });
+    }
+
+    fn push(&mut self, text: &str) -> (Span, Masks) {
+        let start = self.chars.len();
+        let mut prev = None;
+        let (mut white, mut boundary) = (0, 0);
+        for c in text.chars() {
+            let lower = c.to_lowercase().next().unwrap_or(c);
+            let bonus = bonus_for(prev, c);
+            match bonus {
+                BONUS_BOUNDARY_WHITE => white |= bit(lower),
+                0 => {}
+                _ => boundary |= bit(lower),
+            }
+            self.chars.push(lower);
+            self.bonus.push(bonus);
+            prev = Some(c);
+        }
+        let masks = Masks {
+            all: mask(self.chars[start..].iter().copied()),
+            white,
+            boundary,
+        };
+        let end = self.chars.len();
+        (Span { start, end }, masks)
+    }
+
+    fn text(&self, span: Span) -> Text<'_> {
+        Text {
+            chars: &self.chars[span.start..span.end],
+            bonus: &self.bonus[span.start..span.end],
+        }
+    }
+
+    /// Number of bookmarks in the index
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Every bookmark matching `query`, best first
+    ///
+    /// Equal scores are ranked by shorter name, then file order. An empty
+    /// query matches every bookmark with a score of 0.
+    pub fn search(&self, query: &str) -> Vec<Match<'a>> {
+        self.search_top(query, usize::MAX)
+    }
+
+    /// The `limit` best bookmarks matching `query`, best first
+    ///
+    /// Once `limit` bookmarks have been found, a bookmark is only scored if
+    /// an upper bound on its score says it could still make the cut, so a
+    /// small `limit` keeps short queries that match nearly everything fast.
+    pub fn search_top(&self, query: &str, limit: usize) -> Vec<Match<'a>> {
+        let words: Vec<Word> = query.split_whitespace().map(Word::new).collect();
+        let query_mask = words.iter().fold(0, |m, w| m | w.mask);
+
+        // score everything that could make the cut first, keeping the worst
+        // result so far on top, and only work out positions for the results kept
+        let mut kept: BinaryHeap<(Reverse<i32>, usize, usize)> = BinaryHeap::new();
+        for (i, &mask) in self.masks.iter().enumerate() {
+            if limit == 0 || query_mask & !mask != 0 {
+                continue;
+            }
+            let entry = &self.entries[i];
+            if kept.len() == limit {
+                let Some(bound) = self.bound(i, &words) else {
+                    continue;
+                };
+                if (Reverse(bound), entry.name_len, i) >= *kept.peek().unwrap() {
+                    continue;
+                }
+            }
+            let Some(score) = words
+                .iter()
+                .map(|word| Some(self.best_field(i, word)?.1))
+                .sum::<Option<i32>>()
+            else {
+                continue;
+            };
+            let rank = (Reverse(score), entry.name_len, i);
+            if kept.len() < limit {
+                kept.push(rank);
+            } else if let Some(mut worst) = kept.peek_mut() {
+                if rank < *worst {
+                    *worst = rank;
+                }
+            }
+        }
+
+        kept.into_sorted_vec()
+            .into_iter()
+            .map(|(Reverse(score), _, i)| {
+                let entry = &self.entries[i];
+                let mut positions = Positions::default();
+                for word in &words {
+                    if let Some((field, _)) = self.best_field(i, word) {
+                        let f = FIELDS.iter().position(|(f, _)| *f == field).unwrap();
+                        let text = self.text(entry.fields[f]);
+                        let matched = positions.get_mut(field);
+                        text.score(&word.chars, Some(matched));
+                        matched.sort_unstable();
+                        matched.dedup();
+                    }
+                }
+                Match {
+                    category: entry.path[entry.path.len() - 1],
+                    path: entry.path.clone(),
+                    bookmark: entry.bookmark,
+                    score,
+                    positions,
+                }
+            })
+            .collect()
+    }
+
+    /// The most entry `i` can score for `words`, or `None` if one of them
+    /// can't match any of its fields
+    fn bound(&self, i: usize, words: &[Word]) -> Option<i32> {
+        words
+            .iter()
+            .map(|word| {
+                FIELDS
+                    .iter()
+                    .zip(&self.field_masks[i])
+                    .filter(|(_, masks)| word.fits(masks))
+                    .map(|(&(_, bonus), masks)| word.bound(masks) + bonus)
+                    .max()
+            })
+            .sum()
+    }
+
+    /// The field `word` matches best in, with its score including the field
+    /// bonus
+    ///
+    /// Of fields that score the same, the last one wins.
+    fn best_field(&self, i: usize, word: &Word) -> Option<(Field, i32)> {
+        let mut best: Option<(Field, i32)> = None;
+        let fields = self.entries[i].fields.iter().zip(&self.field_masks[i]);
+        for (&(field, bonus), (&span, masks)) in FIELDS.iter().zip(fields) {
+            if !word.fits(masks) {
+                continue;
+            }
+            let Some(score) = self.text(span).score(&word.chars, None) else {
+                continue;
+            };
+            if best.is_none_or(|(_, best)| score + bonus >= best) {
+                best = Some((field, score + bonus));
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = "#Languages\nRust|The Rust Programming Language|https://www.rust-lang.org/\nRuby|A dynamic language|https://www.ruby-lang.org/\nTrust and Safety|Policies|https://example.com/trust\n#Tools\nripgrep|Recursive search|https://github.com/BurntSushi/ripgrep\nGrafana|Dashboards|https://grafana.example.com/\n";
+
+    fn names(index: &Index, query: &str) -> Vec<String> {
+        index
+            .search(query)
+            .into_iter()
+            .map(|m| m.bookmark.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_ranking() {
+        let sbm: Sbm = DATA.parse().unwrap();
+        let index = Index::new(&sbm);
+        assert_eq!(index.len(), 5);
+
+        // a word start beats a match inside a word
+        assert_eq!(names(&index, "rust")[..2], ["Rust", "Trust and Safety"]);
+        // consecutive characters beat scattered ones
+        assert_eq!(names(&index, "rg")[0], "ripgrep");
+        // all words must match, in any field
+        assert_eq!(names(&index, "tools burnt"), vec!["ripgrep"]);
+        assert_eq!(names(&index, "LANG dyn"), vec!["Ruby"]);
+        assert!(names(&index, "xyzzy").is_empty());
+        assert_eq!(names(&index, "").len(), 5);
+
+        let top = index.search_top("r", 2);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top, index.search("r")[..2]);
+    }
+
+    #[test]
+    fn test_search_top_skips_nothing_it_should_keep() {
+        let sbm: Sbm = DATA.parse().unwrap();
+        let index = Index::new(&sbm);
+        for query in ["", "e", "r", "a s", "rust", "ps", "ÿ", "Şe", "com"] {
+            let all = index.search(query);
+            for limit in 0..=all.len() {
+                assert_eq!(index.search_top(query, limit), all[..limit], "{query:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_positions() {
+        let sbm: Sbm = DATA.parse().unwrap();
+        let index = Index::new(&sbm);
+        let grafana = &index.search("gfn dash")[0];
+        assert_eq!(grafana.bookmark.name, "Grafana");
+        assert_eq!(grafana.positions.name, vec![0, 3, 5]);
+        assert_eq!(grafana.positions.description, vec![0, 1, 2, 3]);
+        assert!(grafana.positions.get(Field::Url).is_empty());
+
+        let ripgrep = &index.search("tools")[0];
+        assert_eq!(ripgrep.positions.category, vec![0, 1, 2, 3, 4]);
+        assert!(ripgrep.positions.name.is_empty());
+    }
+
+    #[test]
+    fn test_path() {
+        let sbm: Sbm = "#Work\n##Infra\nGrafana||https://grafana.example.com/\n#Home\nGrafana||https://grafana.home/\n"
+            .parse()
+            .unwrap();
+        let index = Index::new(&sbm);
+        let paths: Vec<Vec<&str>> = index
+            .search("grafana")
+            .iter()
+            .map(|m| m.path.iter().map(|c| c.header.name.as_str()).collect())
+            .collect();
+        assert_eq!(paths, vec![vec!["Work", "Infra"], vec!["Home"]]);
+        let found = &index.search("grafana home")[0];
+        assert!(std::ptr::eq(found.category, found.path[0]));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let sbm: Sbm = "#Ünïcödé\nİstanbul|Şehir|https://example.com/\n"
+            .parse()
+            .unwrap();
+        let index = Index::new(&sbm);
+        let found = &index.search("istan ünï")[0];
+        // `İ` lowercases to `i` and a combining dot; only the `i` is kept
+        assert_eq!(found.positions.name, vec![0, 1, 2, 3, 4]);
+        assert_eq!(found.positions.category, vec![0, 1, 2]);
+    }
+}
@@ -1,13 +1,127 @@
+pub mod borrowed;
+#[cfg(feature = "serde")]
+pub mod convert;
+pub mod dedupe;
+pub mod diff;
+pub mod document;
+pub mod format;
+pub mod formats;
+pub mod fuzzy;
+pub mod merge;
 pub mod parser;
+pub mod query;
+pub mod stream;
+pub mod url;
+
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Error
+///
+/// Errors that can occur while reading or writing SBM data
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Parse(parser::ParseError),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Parse(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<parser::ParseError> for Error {
+    fn from(e: parser::ParseError) -> Error {
+        Error::Parse(e)
+    }
+}
+
+/// Edit error
+///
+/// Errors returned by the methods that modify an [`Sbm`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EditError {
+    /// No category has this name
+    NoSuchCategory(String),
+    /// More than one category has this name, so it doesn't say which one
+    AmbiguousCategory(String),
+    /// A category with this name already exists
+    DuplicateCategory(String),
+    /// No bookmark has this URL
+    NoSuchBookmark(String),
+    /// The category already has a bookmark with this URL
+    DuplicateBookmark { category: String, url: String },
+    /// A bookmark index past the end of the category
+    IndexOutOfRange { index: usize, len: usize },
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EditError::NoSuchCategory(name) => write!(f, "no category named {}", name),
+            EditError::AmbiguousCategory(name) => {
+                write!(f, "more than one category is named {}", name)
+            }
+            EditError::DuplicateCategory(name) => {
+                write!(f, "a category named {} already exists", name)
+            }
+            EditError::NoSuchBookmark(url) => write!(f, "no bookmark with URL {}", url),
+            EditError::DuplicateBookmark { category, url } => {
+                write!(f, "{} already has a bookmark with URL {}", category, url)
+            }
+            EditError::IndexOutOfRange { index, len } => write!(
+                f,
+                "index {} is out of range for a category with {} bookmarks",
+                index, len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
 
 /// Bookmark
 ///
-/// A bookmark is a link to a website with a name and a description
+/// A bookmark is a link to a website with a name, a description, any
+/// number of tags and free-form `key: value` metadata
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bookmark {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub description: String,
     pub url: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    pub tags: Vec<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Bookmark {
@@ -16,23 +130,91 @@ impl Bookmark {
             name: name.to_string(),
             description: description.to_string(),
             url: url.to_string(),
+            tags: Vec::new(),
+            metadata: BTreeMap::new(),
         }
     }
+
+    /// Replace the bookmark's tags
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Bookmark;
+    /// let bookmark = Bookmark::new("Rust", "", "https://www.rust-lang.org/").with_tags(["lang", "systems"]);
+    /// assert_eq!(bookmark.to_string(), "Rust||https://www.rust-lang.org/|lang,systems");
+    /// ```
+    pub fn with_tags<I, S>(mut self, tags: I) -> Bookmark
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Whether the bookmark has the given tag; tags are case-sensitive
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Set a metadata entry, written as a `//@ key: value` line above the bookmark
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Bookmark;
+    /// let bookmark = Bookmark::new("Rust", "", "https://www.rust-lang.org/").with_metadata("added", "2024-01-31");
+    /// assert_eq!(bookmark.to_string(), "//@ added: 2024-01-31\nRust||https://www.rust-lang.org/");
+    /// assert_eq!(bookmark.to_string().parse::<Bookmark>().unwrap(), bookmark);
+    /// ```
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Bookmark {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
 }
 
 impl std::fmt::Display for Bookmark {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}|{}|{}", self.name, self.description, self.url)
+        borrowed::BookmarkRef::from(self).fmt(f)
+    }
+}
+
+/// Parses a bookmark line, along with any annotation lines above it
+impl FromStr for Bookmark {
+    type Err = parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Bookmark, Self::Err> {
+        let (metadata, line) = parser::split_annotations(s);
+        Ok(Bookmark {
+            metadata,
+            ..parser::parse_bookmark(line)?
+        })
+    }
+}
+
+impl TryFrom<&str> for Bookmark {
+    type Error = parser::ParseError;
+
+    fn try_from(s: &str) -> Result<Bookmark, Self::Error> {
+        s.parse()
     }
 }
 
 /// Category Header
 ///
-/// A header is a name and an optional icon
+/// A header is a name, an optional icon and free-form `key: value` metadata
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Header {
     pub name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub icon: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "BTreeMap::is_empty")
+    )]
+    pub metadata: BTreeMap<String, String>,
 }
 
 impl Header {
@@ -40,26 +222,57 @@ impl Header {
         Header {
             name: name.to_string(),
             icon: icon.map(|s| s.to_string()),
+            metadata: BTreeMap::new(),
         }
     }
+
+    /// Set a metadata entry, written as a `//@ key: value` line above the header
+    pub fn with_metadata(mut self, key: &str, value: &str) -> Header {
+        self.metadata.insert(key.to_string(), value.to_string());
+        self
+    }
 }
 
 impl std::fmt::Display for Header {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match &self.icon {
-            Some(icon) => write!(f, "#{}|{}", self.name, icon),
-            None => write!(f, "#{}", self.name),
-        }
+        borrowed::HeaderRef::from(self).fmt(f)
+    }
+}
+
+/// Parses a header line, along with any annotation lines above it; the
+/// leading `#` is optional
+impl FromStr for Header {
+    type Err = parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Header, Self::Err> {
+        let (metadata, line) = parser::split_annotations(s);
+        Ok(Header {
+            metadata,
+            ..parser::parse_header(line.strip_prefix('#').unwrap_or(line).trim())?
+        })
+    }
+}
+
+impl TryFrom<&str> for Header {
+    type Error = parser::ParseError;
+
+    fn try_from(s: &str) -> Result<Header, Self::Error> {
+        s.parse()
     }
 }
 
 /// Category
 ///
-/// A category is a header with a list of bookmarks
+/// A category is a header with a list of bookmarks, and any number of
+/// subcategories
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Category {
     pub header: Header,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub bookmarks: Vec<Bookmark>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub children: Vec<Category>,
 }
 
 impl Category {
@@ -67,26 +280,102 @@ impl Category {
         Category {
             header,
             bookmarks: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Iterate over this category and all of its subcategories, depth first
+    ///
+    /// The category itself has depth 1, its children depth 2, and so on.
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: vec![(1, self)],
+        }
+    }
+
+    /// Iterate over the bookmarks of this category and all of its subcategories
+    pub fn all_bookmarks(&self) -> impl Iterator<Item = &Bookmark> {
+        self.walk().flat_map(|(_, c)| &c.bookmarks)
+    }
+
+    /// Write the category with a header of `depth` `#`s, its children one deeper
+    fn fmt_at(&self, f: &mut std::fmt::Formatter, depth: usize) -> std::fmt::Result {
+        borrowed::HeaderRef::from(&self.header).fmt_at(f, depth)?;
+        for bookmark in &self.bookmarks {
+            write!(f, "\n{}", bookmark)?;
         }
+        for child in &self.children {
+            writeln!(f)?;
+            child.fmt_at(f, depth + 1)?;
+        }
+        // an empty top-level category has always been written with a newline
+        if depth == 1 && self.bookmarks.is_empty() && self.children.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
 
 impl std::fmt::Display for Category {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}\n{}",
-            self.header,
-            self.bookmarks
-                .iter()
-                .map(|b| b.to_string())
-                .collect::<Vec<String>>()
-                .join("\n")
-        )
+        self.fmt_at(f, 1)
+    }
+}
+
+/// Depth-first iterator over categories and their subcategories
+///
+/// Yields each category along with its depth, 1 being the top level.
+#[derive(Debug, Clone)]
+pub struct Walk<'a> {
+    stack: Vec<(usize, &'a Category)>,
+}
+
+impl<'a> Iterator for Walk<'a> {
+    type Item = (usize, &'a Category);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (depth, category) = self.stack.pop()?;
+        self.stack
+            .extend(category.children.iter().rev().map(|c| (depth + 1, c)));
+        Some((depth, category))
+    }
+}
+
+/// Parses a header line followed by its bookmarks
+impl FromStr for Category {
+    type Err = parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Category, Self::Err> {
+        parser::parse_category(s)
+    }
+}
+
+impl TryFrom<&str> for Category {
+    type Error = parser::ParseError;
+
+    fn try_from(s: &str) -> Result<Category, Self::Error> {
+        s.parse()
     }
 }
 
+/// Simple Bookmark file
+///
+/// An SBM file is a list of categories, each of which may hold
+/// subcategories. Parsing the output of `to_string()` gives back an equal
+/// value.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::Sbm;
+/// let sbm: Sbm = "#Languages\nRust|Systems programming language|https://www.rust-lang.org/"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(sbm.categories()[0].bookmarks[0].name, "Rust");
+/// assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+/// ```
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sbm(pub Vec<Category>);
 
 impl Sbm {
@@ -94,9 +383,438 @@ impl Sbm {
         Sbm(categories)
     }
 
+    /// The top-level categories
     pub fn categories(&self) -> &Vec<Category> {
         &self.0
     }
+
+    /// Iterate over every category, depth first
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Work\n##Infra\n###Monitoring\n#Home".parse().unwrap();
+    /// let names: Vec<(usize, &str)> = sbm.walk().map(|(depth, c)| (depth, c.header.name.as_str())).collect();
+    /// assert_eq!(names, vec![(1, "Work"), (2, "Infra"), (3, "Monitoring"), (1, "Home")]);
+    /// ```
+    pub fn walk(&self) -> Walk<'_> {
+        Walk {
+            stack: self.0.iter().rev().map(|c| (1, c)).collect(),
+        }
+    }
+
+    /// Iterate over every bookmark, in file order
+    pub fn bookmarks(&self) -> impl Iterator<Item = &Bookmark> {
+        self.walk().flat_map(|(_, c)| &c.bookmarks)
+    }
+
+    /// Iterate over every bookmark along with the category it is in, in file order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Work\nJira|Issues|https://jira.example.com/\n##Infra\nGrafana|Dashboards|https://grafana.example.com/".parse().unwrap();
+    /// let entries: Vec<(&str, &str)> = sbm
+    ///     .entries()
+    ///     .map(|(c, b)| (c.header.name.as_str(), b.name.as_str()))
+    ///     .collect();
+    /// assert_eq!(entries, vec![("Work", "Jira"), ("Infra", "Grafana")]);
+    /// ```
+    pub fn entries(&self) -> impl Iterator<Item = (&Category, &Bookmark)> {
+        self.walk()
+            .flat_map(|(_, c)| c.bookmarks.iter().map(move |b| (c, b)))
+    }
+
+    /// The first bookmark with exactly this URL, along with its category
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Languages\nRust|Rust|https://www.rust-lang.org/".parse().unwrap();
+    /// let (category, bookmark) = sbm.bookmark_by_url("https://www.rust-lang.org/").unwrap();
+    /// assert_eq!((category.header.name.as_str(), bookmark.name.as_str()), ("Languages", "Rust"));
+    /// assert!(sbm.bookmark_by_url("https://www.rust-lang.org").is_none());
+    /// ```
+    pub fn bookmark_by_url(&self, url: &str) -> Option<(&Category, &Bookmark)> {
+        self.entries().find(|(_, b)| b.url == url)
+    }
+
+    /// Iterate over every bookmark with exactly this name, along with its category
+    pub fn bookmarks_named<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = (&'a Category, &'a Bookmark)> {
+        self.entries().filter(move |(_, b)| b.name == name)
+    }
+
+    /// Iterate over every bookmark matching a query, along with its category
+    ///
+    /// See the [`query`] module for the query language.
+    pub fn query<'a>(
+        &'a self,
+        query: &'a query::Query,
+    ) -> impl Iterator<Item = (&'a Category, &'a Bookmark)> {
+        self.walk()
+            .scan(
+                Vec::new(),
+                |path: &mut Vec<&'a Category>, (depth, category)| {
+                    path.truncate(depth - 1);
+                    path.push(category);
+                    Some(path.clone())
+                },
+            )
+            .flat_map(move |path| {
+                let category = path[path.len() - 1];
+                category
+                    .bookmarks
+                    .iter()
+                    .filter(move |b| query.matches(&path, b))
+                    .map(move |b| (category, b))
+            })
+    }
+
+    /// Every group of bookmarks that share a URL, with where each copy is
+    /// filed
+    ///
+    /// URLs are compared by their [`url::key`].
+    pub fn duplicates(&self) -> Vec<dedupe::Duplicates<'_>> {
+        dedupe::find(self)
+    }
+
+    /// Remove duplicated bookmarks, keeping one copy of each as `policy`
+    /// says, and return how many were removed
+    pub fn dedupe(&mut self, policy: dedupe::Policy) -> usize {
+        let plan = dedupe::resolve(self.bookmarks(), policy);
+        let removed = plan.iter().filter(|b| b.is_none()).count();
+        dedupe::apply(&mut self.0, &mut plan.into_iter());
+        removed
+    }
+
+    /// Merge `other` into a copy of this file, with a list of the conflicts
+    /// between them
+    ///
+    /// Categories are matched by name and bookmarks by [`url::key`]. The
+    /// merged file keeps this file's side of every conflict until resolved
+    /// otherwise, see the [`merge`] module.
+    pub fn merge(&self, other: &Sbm) -> merge::Merge {
+        merge::merge(self, other)
+    }
+
+    /// Every change from this file to `new`: added, removed and renamed
+    /// categories, and added, removed, moved, renamed and re-described
+    /// bookmarks
+    ///
+    /// Bookmarks are matched by [`url::key`], so reordering them is not a
+    /// change. See the [`diff`] module.
+    pub fn diff<'a>(&'a self, new: &'a Sbm) -> Vec<diff::Change<'a>> {
+        diff::diff(self, new)
+    }
+
+    /// Iterate over every bookmark with the given tag, in file order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Languages\nRust|Rust|https://www.rust-lang.org/|systems,docs\n#Web\nMDN|MDN|https://developer.mozilla.org/|docs"
+    ///     .parse()
+    ///     .unwrap();
+    /// let docs: Vec<&str> = sbm.bookmarks_with_tag("docs").map(|b| b.name.as_str()).collect();
+    /// assert_eq!(docs, vec!["Rust", "MDN"]);
+    /// ```
+    pub fn bookmarks_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Bookmark> {
+        self.bookmarks().filter(move |b| b.has_tag(tag))
+    }
+
+    /// Every tag in use, with the number of bookmarks that have it
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Languages\nRust|Rust|https://www.rust-lang.org/|systems,docs\n#Web\nMDN|MDN|https://developer.mozilla.org/|docs"
+    ///     .parse()
+    ///     .unwrap();
+    /// let tags: Vec<(&str, usize)> = sbm.all_tags().into_iter().collect();
+    /// assert_eq!(tags, vec![("docs", 2), ("systems", 1)]);
+    /// ```
+    pub fn all_tags(&self) -> BTreeMap<&str, usize> {
+        let mut tags = BTreeMap::new();
+        for bookmark in self.bookmarks() {
+            // a tag repeated on one bookmark still counts once
+            let mut seen = Vec::new();
+            for tag in &bookmark.tags {
+                if !seen.contains(&tag) {
+                    seen.push(tag);
+                    *tags.entry(tag.as_str()).or_insert(0) += 1;
+                }
+            }
+        }
+        tags
+    }
+
+    /// Find a category by the names along its path from the top level
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::Sbm;
+    /// let sbm: Sbm = "#Work\n##Infra\nGrafana|Dashboards|https://grafana.example.com/".parse().unwrap();
+    /// let infra = sbm.find(&["Work", "Infra"]).unwrap();
+    /// assert_eq!(infra.bookmarks[0].name, "Grafana");
+    /// assert!(sbm.find(&["Infra"]).is_none());
+    /// ```
+    pub fn find(&self, path: &[&str]) -> Option<&Category> {
+        let (first, rest) = path.split_first()?;
+        let mut category = self.0.iter().find(|c| c.header.name == *first)?;
+        for name in rest {
+            category = category.children.iter().find(|c| c.header.name == *name)?;
+        }
+        Some(category)
+    }
+
+    /// Find a category by its path, for modification
+    pub fn find_mut(&mut self, path: &[&str]) -> Option<&mut Category> {
+        let (first, rest) = path.split_first()?;
+        let mut category = self.0.iter_mut().find(|c| c.header.name == *first)?;
+        for name in rest {
+            category = category
+                .children
+                .iter_mut()
+                .find(|c| c.header.name == *name)?;
+        }
+        Some(category)
+    }
+
+    /// Find the category with the given name, at any depth, for modification
+    ///
+    /// The editing methods below look categories up this way. A file may
+    /// have several categories with the same name, such as `Docs` under both
+    /// `Work` and `Home`; naming one of those is an
+    /// [`EditError::AmbiguousCategory`], and [`Sbm::find_mut`] with the full
+    /// path reaches them instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::{EditError, Sbm};
+    /// let mut sbm: Sbm = "#Work\n##Docs\n#Home\n##Docs".parse().unwrap();
+    /// assert_eq!(sbm.category_mut("Work").unwrap().children.len(), 1);
+    /// assert_eq!(
+    ///     sbm.category_mut("Docs"),
+    ///     Err(EditError::AmbiguousCategory("Docs".to_string()))
+    /// );
+    /// ```
+    pub fn category_mut(&mut self, name: &str) -> Result<&mut Category, EditError> {
+        let path = self.locate(name)?;
+        Ok(self.category_at_mut(&path))
+    }
+
+    /// Add an empty top-level category at the end
+    ///
+    /// Fails if a category with the same name already exists at any depth,
+    /// so that the name keeps naming a single category.
+    pub fn add_category(&mut self, header: Header) -> Result<&mut Category, EditError> {
+        if self.walk().any(|(_, c)| c.header.name == header.name) {
+            return Err(EditError::DuplicateCategory(header.name));
+        }
+        self.0.push(Category::new(header));
+        Ok(self.0.last_mut().unwrap())
+    }
+
+    /// Remove a category along with its bookmarks and subcategories
+    pub fn remove_category(&mut self, name: &str) -> Result<Category, EditError> {
+        let path = self.locate(name)?;
+        let (last, parent) = path.split_last().unwrap();
+        Ok(self.children_at_mut(parent).remove(*last))
+    }
+
+    /// Rename a category, keeping its icon, metadata and contents
+    ///
+    /// Fails if another category already has the new name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::{EditError, Sbm};
+    /// let mut sbm: Sbm = "#Work\n#Home".parse().unwrap();
+    /// sbm.rename_category("Work", "Office").unwrap();
+    /// assert_eq!(sbm.categories()[0].header.name, "Office");
+    /// assert_eq!(
+    ///     sbm.rename_category("Office", "Home"),
+    ///     Err(EditError::DuplicateCategory("Home".to_string()))
+    /// );
+    /// ```
+    pub fn rename_category(&mut self, name: &str, new_name: &str) -> Result<(), EditError> {
+        let path = self.locate(name)?;
+        if name != new_name && self.walk().any(|(_, c)| c.header.name == new_name) {
+            return Err(EditError::DuplicateCategory(new_name.to_string()));
+        }
+        self.category_at_mut(&path).header.name = new_name.to_string();
+        Ok(())
+    }
+
+    /// Insert a bookmark into a category at position `index`, shifting the
+    /// bookmarks after it
+    ///
+    /// `index` may be the number of bookmarks in the category, to add the
+    /// bookmark at the end. Fails if the category already has a bookmark with
+    /// the same URL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::{Bookmark, Sbm};
+    /// let mut sbm: Sbm = "#Languages\nRust|Rust|https://www.rust-lang.org/".parse().unwrap();
+    /// let zig = Bookmark::new("Zig", "Zig", "https://ziglang.org/");
+    /// sbm.insert_bookmark("Languages", 0, zig).unwrap();
+    /// assert_eq!(sbm.categories()[0].bookmarks[0].name, "Zig");
+    /// ```
+    pub fn insert_bookmark(
+        &mut self,
+        category: &str,
+        index: usize,
+        bookmark: Bookmark,
+    ) -> Result<(), EditError> {
+        let target = self.category_mut(category)?;
+        let len = target.bookmarks.len();
+        if index > len {
+            return Err(EditError::IndexOutOfRange { index, len });
+        }
+        check_new_url(target, &bookmark.url)?;
+        target.bookmarks.insert(index, bookmark);
+        Ok(())
+    }
+
+    /// Move the first bookmark with exactly this URL to the end of another
+    /// category
+    ///
+    /// Fails, leaving the bookmark where it was, if the target category
+    /// already has a bookmark with this URL, including the bookmark itself.
+    pub fn move_bookmark(&mut self, url: &str, to: &str) -> Result<(), EditError> {
+        let target = self.locate(to)?;
+        check_new_url(self.category_at_mut(&target), url)?;
+        let bookmark = self.remove_bookmark_by_url(url)?;
+        self.category_at_mut(&target).bookmarks.push(bookmark);
+        Ok(())
+    }
+
+    /// Remove the first bookmark with exactly this URL, in file order
+    ///
+    /// Other bookmarks with the same URL are left alone, as with
+    /// [`Sbm::bookmark_by_url`].
+    pub fn remove_bookmark_by_url(&mut self, url: &str) -> Result<Bookmark, EditError> {
+        fn remove(categories: &mut [Category], url: &str) -> Option<Bookmark> {
+            categories.iter_mut().find_map(|category| {
+                match category.bookmarks.iter().position(|b| b.url == url) {
+                    Some(i) => Some(category.bookmarks.remove(i)),
+                    None => remove(&mut category.children, url),
+                }
+            })
+        }
+        remove(&mut self.0, url).ok_or_else(|| EditError::NoSuchBookmark(url.to_string()))
+    }
+
+    /// The indices leading to the one category named `name`, see
+    /// [`Sbm::category_mut`]
+    fn locate(&self, name: &str) -> Result<Vec<usize>, EditError> {
+        fn search(
+            categories: &[Category],
+            name: &str,
+            path: &mut Vec<usize>,
+            found: &mut Vec<Vec<usize>>,
+        ) {
+            for (i, category) in categories.iter().enumerate() {
+                path.push(i);
+                if category.header.name == name {
+                    found.push(path.clone());
+                }
+                search(&category.children, name, path, found);
+                path.pop();
+            }
+        }
+        let mut found = Vec::new();
+        search(&self.0, name, &mut Vec::new(), &mut found);
+        match found.len() {
+            0 => Err(EditError::NoSuchCategory(name.to_string())),
+            1 => Ok(found.pop().unwrap()),
+            _ => Err(EditError::AmbiguousCategory(name.to_string())),
+        }
+    }
+
+    /// The list of categories that `path` indexes into, the top level for an
+    /// empty path
+    fn children_at_mut(&mut self, path: &[usize]) -> &mut Vec<Category> {
+        path.iter()
+            .fold(&mut self.0, |children, &i| &mut children[i].children)
+    }
+
+    fn category_at_mut(&mut self, path: &[usize]) -> &mut Category {
+        let (last, parent) = path.split_last().unwrap();
+        &mut self.children_at_mut(parent)[*last]
+    }
+
+    /// Parse an SBM file from a string
+    pub fn parse(data: &str) -> Result<Sbm, parser::ParseError> {
+        parser::parse_categories(data).map(Sbm)
+    }
+
+    /// Read an SBM file from a reader
+    pub fn from_reader<R: Read>(reader: R) -> Result<Sbm, Error> {
+        stream::Categories::new(BufReader::new(reader))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Sbm)
+    }
+
+    /// Read an SBM file from disk
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Sbm, Error> {
+        Sbm::from_reader(std::fs::File::open(path)?)
+    }
+
+    /// Write the file to a writer, followed by a trailing newline
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        writeln!(writer, "{}", self)
+    }
+
+    /// Write the file to disk, replacing any existing file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Fail if `category` already has a bookmark with this URL
+fn check_new_url(category: &Category, url: &str) -> Result<(), EditError> {
+    if category.bookmarks.iter().any(|b| b.url == url) {
+        return Err(EditError::DuplicateBookmark {
+            category: category.header.name.clone(),
+            url: url.to_string(),
+        });
+    }
+    Ok(())
+}
+
+impl FromStr for Sbm {
+    type Err = parser::ParseError;
+
+    fn from_str(s: &str) -> Result<Sbm, Self::Err> {
+        Sbm::parse(s)
+    }
+}
+
+impl TryFrom<&str> for Sbm {
+    type Error = parser::ParseError;
+
+    fn try_from(s: &str) -> Result<Sbm, Self::Error> {
+        s.parse()
+    }
 }
 
 impl std::fmt::Display for Sbm {
@@ -116,6 +834,97 @@ impl std::fmt::Display for Sbm {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    /// Any field the format can hold: whitespace around a field is not
+    /// significant, so the roundtrips below only hold for fields without
+    /// it; the parser's roundtrip properties cover what happens to it
+    fn field() -> impl Strategy<Value = String> {
+        r"([#/|\\ ]|[^\r\n])*".prop_map(|s| s.trim().to_string())
+    }
+
+    fn metadata() -> impl Strategy<Value = BTreeMap<String, String>> {
+        // anything goes, biased towards characters that need escaping
+        let text = r"([:\\ \t\r\n\u{a0}]|\\[nrtsu:]|.)*";
+        proptest::collection::btree_map(text, text, 0..3)
+    }
+
+    fn bookmark() -> impl Strategy<Value = Bookmark> {
+        let tag = field().prop_filter("tags are never empty", |t| !t.is_empty());
+        (
+            field(),
+            field(),
+            field(),
+            proptest::collection::vec(tag, 0..3),
+            metadata(),
+        )
+            .prop_map(|(name, description, url, tags, metadata)| Bookmark {
+                name,
+                description,
+                url,
+                tags,
+                metadata,
+            })
+    }
+
+    fn header() -> impl Strategy<Value = Header> {
+        (field(), proptest::option::of(field()), metadata()).prop_map(|(name, icon, metadata)| {
+            Header {
+                name,
+                icon,
+                metadata,
+            }
+        })
+    }
+
+    fn category() -> impl Strategy<Value = Category> {
+        let leaf = (header(), proptest::collection::vec(bookmark(), 0..4)).prop_map(
+            |(header, bookmarks)| Category {
+                header,
+                bookmarks,
+                children: Vec::new(),
+            },
+        );
+        leaf.prop_recursive(3, 12, 3, |inner| {
+            (
+                header(),
+                proptest::collection::vec(bookmark(), 0..3),
+                proptest::collection::vec(inner, 0..3),
+            )
+                .prop_map(|(header, bookmarks, children)| Category {
+                    header,
+                    bookmarks,
+                    children,
+                })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sbm_roundtrip(categories in proptest::collection::vec(category(), 0..4)) {
+            let sbm = Sbm(categories);
+            prop_assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+        }
+
+        #[test]
+        fn prop_metadata_roundtrip(metadata in metadata()) {
+            let bookmark = Bookmark {
+                metadata: metadata.clone(),
+                ..Bookmark::new("Rust", "", "https://www.rust-lang.org/")
+            };
+            prop_assert_eq!(bookmark.to_string().parse::<Bookmark>().unwrap(), bookmark);
+            let header = Header {
+                metadata,
+                ..Header::new("Languages", None)
+            };
+            prop_assert_eq!(header.to_string().parse::<Header>().unwrap(), header);
+        }
+
+        #[test]
+        fn prop_category_roundtrip(category in category()) {
+            prop_assert_eq!(category.to_string().parse::<Category>().unwrap(), category);
+        }
+    }
 
     #[test]
     fn test_bookmark_new() {
@@ -167,6 +976,7 @@ mod tests {
                     "Systems programming language",
                     "https://www.rust-lang.org/",
                 )],
+                children: Vec::new(),
             },
             Category {
                 header: Header::new("Web Development", Some("🌐")),
@@ -175,6 +985,7 @@ mod tests {
                     "Web documentation",
                     "https://developer.mozilla.org/",
                 )],
+                children: Vec::new(),
             },
         ]);
         assert_eq!(
@@ -182,4 +993,227 @@ mod tests {
             "#Programming Languages\nRust|Systems programming language|https://www.rust-lang.org/\n#Web Development|🌐\nMDN|Web documentation|https://developer.mozilla.org/"
         );
     }
+
+    #[test]
+    fn test_nested_display() {
+        let mut work = Category::new(Header::new("Work", Some("💼")));
+        let mut infra = Category::new(Header::new("Infra", None));
+        infra.bookmarks.push(Bookmark::new(
+            "Grafana",
+            "Dashboards",
+            "https://grafana.example.com/",
+        ));
+        infra
+            .children
+            .push(Category::new(Header::new("#Alerts", None)));
+        work.children.push(infra);
+        work.children.push(Category::new(Header::new("Docs", None)));
+        let sbm = Sbm(vec![work, Category::new(Header::new("Home", None))]);
+        assert_eq!(
+            sbm.to_string(),
+            "#Work|💼\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\n###\\#Alerts\n##Docs\n#Home\n"
+        );
+        assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+
+        assert_eq!(sbm.walk().count(), 5);
+        assert_eq!(sbm.bookmarks().count(), 1);
+        assert_eq!(sbm.0[0].all_bookmarks().count(), 1);
+        assert_eq!(
+            sbm.find(&["Work", "Infra", "#Alerts"]).unwrap().header.name,
+            "#Alerts"
+        );
+        let mut sbm = sbm;
+        sbm.find_mut(&["Work", "Docs"])
+            .unwrap()
+            .bookmarks
+            .push(Bookmark::new("Wiki", "", "https://wiki.example.com/"));
+        assert_eq!(sbm.0[0].children[1].bookmarks.len(), 1);
+    }
+
+    #[test]
+    fn test_tags() {
+        let data = "#Languages\nRust|Rust|https://www.rust-lang.org/| systems , docs,docs\nGo|Go|https://go.dev/|\n##Web\nMDN|MDN|https://developer.mozilla.org/|docs,a\\,b\n";
+        let sbm: Sbm = data.parse().unwrap();
+        let rust = &sbm.0[0].bookmarks[0];
+        assert_eq!(rust.tags, vec!["systems", "docs", "docs"]);
+        assert!(sbm.0[0].bookmarks[1].tags.is_empty());
+        assert_eq!(sbm.0[0].children[0].bookmarks[0].tags, vec!["docs", "a,b"]);
+
+        let tags: Vec<(&str, usize)> = sbm.all_tags().into_iter().collect();
+        assert_eq!(tags, vec![("a,b", 1), ("docs", 2), ("systems", 1)]);
+        assert_eq!(sbm.bookmarks_with_tag("docs").count(), 2);
+        assert_eq!(sbm.bookmarks_with_tag("Docs").count(), 0);
+        assert_eq!(
+            sbm.0[0].children[0].bookmarks[0].to_string(),
+            "MDN|MDN|https://developer.mozilla.org/|docs,a\\,b"
+        );
+        assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let data = "//@ owner: ops\n#Work\n//@ added: 2024-01-31\n//@ expires:\nJira|Issues|https://jira.example.com/\n// a comment\n//@ ignored: the comment above cuts it off\n\nWiki|Wiki|https://wiki.example.com/\n//@ dangling: nothing below\n";
+        let sbm: Sbm = data.parse().unwrap();
+        let work = &sbm.0[0];
+        assert_eq!(work.header.metadata["owner"], "ops");
+        let jira = &work.bookmarks[0];
+        assert_eq!(
+            jira.metadata,
+            BTreeMap::from([
+                ("added".to_string(), "2024-01-31".to_string()),
+                ("expires".to_string(), String::new()),
+            ])
+        );
+        assert!(work.bookmarks[1].metadata.is_empty());
+        assert_eq!(
+            sbm.to_string(),
+            "//@ owner: ops\n#Work\n//@ added: 2024-01-31\n//@ expires:\nJira|Issues|https://jira.example.com/\nWiki|Wiki|https://wiki.example.com/"
+        );
+        assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+        assert_eq!(
+            "//@ owner: ops\n# Work".parse::<Header>().unwrap(),
+            Header::new("Work", None).with_metadata("owner", "ops")
+        );
+
+        // lines that only look like annotations stay comments
+        let sbm: Sbm = "#A\n//@ not an annotation\n//@todo: fix later\nx|y|z\n"
+            .parse()
+            .unwrap();
+        assert!(sbm.0[0].bookmarks[0].metadata.is_empty());
+
+        // anything can be stored, escaped as needed
+        let bookmark = Bookmark::new("Rust", "", "https://www.rust-lang.org/")
+            .with_metadata("note", "line1\nline2")
+            .with_metadata("a:b", " padded\t")
+            .with_metadata("path", r"C:\Users");
+        assert_eq!(
+            bookmark.to_string(),
+            "//@ a\\:b: \\spadded\\t\n//@ note: line1\\nline2\n//@ path: C:\\\\Users\nRust||https://www.rust-lang.org/"
+        );
+        assert_eq!(bookmark.to_string().parse::<Bookmark>().unwrap(), bookmark);
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut sbm: Sbm = "#Work\nJira|Issues|https://jira.example.com/\n##Docs\n#Home\n##Docs\n"
+            .parse()
+            .unwrap();
+        let wiki = Bookmark::new("Wiki", "Team wiki", "https://wiki.example.com/");
+
+        assert_eq!(
+            sbm.add_category(Header::new("Docs", None)).unwrap_err(),
+            EditError::DuplicateCategory("Docs".to_string())
+        );
+        sbm.add_category(Header::new("Archive", Some("📦")))
+            .unwrap()
+            .bookmarks
+            .push(wiki.clone());
+        assert_eq!(
+            sbm.insert_bookmark("Docs", 0, wiki.clone()),
+            Err(EditError::AmbiguousCategory("Docs".to_string()))
+        );
+        assert_eq!(
+            sbm.insert_bookmark("Work", 2, wiki.clone()),
+            Err(EditError::IndexOutOfRange { index: 2, len: 1 })
+        );
+        assert_eq!(
+            sbm.insert_bookmark("Archive", 0, wiki.clone()),
+            Err(EditError::DuplicateBookmark {
+                category: "Archive".to_string(),
+                url: wiki.url.clone()
+            })
+        );
+        sbm.insert_bookmark("Work", 1, wiki.clone()).unwrap();
+        sbm.remove_category("Archive").unwrap();
+        assert_eq!(
+            sbm.category_mut("Archive"),
+            Err(EditError::NoSuchCategory("Archive".to_string()))
+        );
+
+        // renaming one of the duplicates makes both names usable again
+        sbm.find_mut(&["Home", "Docs"]).unwrap().header.name = "Manuals".to_string();
+        sbm.move_bookmark("https://jira.example.com/", "Docs")
+            .unwrap();
+        assert_eq!(
+            sbm.move_bookmark("https://jira.example.com/", "Docs"),
+            Err(EditError::DuplicateBookmark {
+                category: "Docs".to_string(),
+                url: "https://jira.example.com/".to_string()
+            })
+        );
+        assert_eq!(
+            sbm.move_bookmark("https://nowhere.example.com/", "Docs"),
+            Err(EditError::NoSuchBookmark(
+                "https://nowhere.example.com/".to_string()
+            ))
+        );
+        sbm.rename_category("Manuals", "Guides").unwrap();
+        assert_eq!(sbm.remove_bookmark_by_url(&wiki.url), Ok(wiki));
+        assert_eq!(
+            sbm.to_string(),
+            "#Work\n##Docs\nJira|Issues|https://jira.example.com/\n#Home\n##Guides"
+        );
+    }
+
+    #[test]
+    fn test_flat_files_unchanged() {
+        let data = "#\\#Hash\nRust|Rust|https://www.rust-lang.org/\n#Web\n";
+        let sbm: Sbm = data.parse().unwrap();
+        assert_eq!(sbm.0.len(), 2);
+        assert_eq!(sbm.0[0].header.name, "#Hash");
+        assert!(sbm.walk().all(|(depth, _)| depth == 1));
+
+        // `##` headers with no `#` header before them have no parent, and
+        // keep their extra `#`s as they did before subcategories
+        let sbm: Sbm = "##x\nA|b|c\n##y\nB|b|c\n".parse().unwrap();
+        assert_eq!(sbm.0.len(), 2);
+        assert_eq!(sbm.0[0].header.name, "#x");
+        assert_eq!(sbm.0[1].header.name, "#y");
+        assert!(sbm.walk().all(|(depth, _)| depth == 1));
+    }
+
+    #[test]
+    fn test_from_str() {
+        let bookmark: Bookmark = "Rust | Rust | https://www.rust-lang.org/".parse().unwrap();
+        assert_eq!(
+            bookmark,
+            Bookmark::new("Rust", "Rust", "https://www.rust-lang.org/")
+        );
+        assert_eq!(
+            Header::try_from("# Web | 🌐").unwrap(),
+            Header::new("Web", Some("🌐"))
+        );
+        assert_eq!(Header::try_from("Web").unwrap(), Header::new("Web", None));
+        assert!("Rust|Rust".parse::<Bookmark>().is_err());
+
+        let err = "#A\nx|y|z\n\n#B\n".parse::<Category>().unwrap_err();
+        assert!(matches!(err, parser::ParseError::Category { found: 2, .. }));
+        assert_eq!(err.location().line, 4);
+        let err = "".parse::<Category>().unwrap_err();
+        assert!(matches!(err, parser::ParseError::Category { found: 0, .. }));
+
+        assert_eq!(Sbm::try_from("").unwrap(), Sbm::new(Vec::new()));
+    }
+
+    #[test]
+    fn test_read_write() {
+        let sbm: Sbm = "#A|🌐\nx|y \\| z|https://example.com/\n#B\n"
+            .parse()
+            .unwrap();
+        let mut out = Vec::new();
+        sbm.write_to(&mut out).unwrap();
+        assert_eq!(Sbm::from_reader(out.as_slice()).unwrap(), sbm);
+
+        let path = std::env::temp_dir().join(format!("sbm-test-{}.sbm", std::process::id()));
+        sbm.save(&path).unwrap();
+        let loaded = Sbm::from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), sbm);
+
+        assert!(matches!(
+            Sbm::from_reader("bad".as_bytes()),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(Sbm::from_path(&path), Err(Error::Io(_))));
+    }
 }
@@ -0,0 +1,652 @@
+//! `sbm` command-line tool
+//!
+//! Every command reads the file given with `-f`/`--file`, or standard input
+//! if there is none. Commands that change the file write it back in place,
+//! or print the result to standard output when reading from standard input.
+//! Edits go through [`Document`], so untouched lines keep their layout.
+//!
+//! Exit codes: 0 on success, 1 when the command failed or found nothing,
+//! 2 on usage errors.
+
+#[cfg(feature = "serde")]
+use sbm::convert;
+use sbm::dedupe::{self, Policy};
+use sbm::document::Document;
+use sbm::format::{self, FormatOptions};
+use sbm::formats::{chromium, firefox, netscape, xbel, ImportError};
+use sbm::fuzzy::Index;
+use sbm::merge::Resolution;
+use sbm::parser::{self, ParseOptions, Severity};
+use sbm::query::Query;
+use sbm::{Bookmark, Category, Header, Sbm};
+use std::io::{Read, Write};
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: sbm <command> [-f FILE] [args]
+
+commands:
+  list [--categories]                   list bookmarks, or only category names
+  add CATEGORY NAME DESCRIPTION URL     add a bookmark, creating the category if needed
+  remove URL                            remove every bookmark with this URL
+  move URL CATEGORY                     move bookmarks with this URL to another category
+  search QUERY...                       find bookmarks matching a query; plain words match
+                                        the name, description, URL or tags, and
+                                        name:, desc:, url:, domain:, in:, tag:, OR, NOT,
+                                        -TERM and (...) narrow it down
+  search --fuzzy WORDS...               find bookmarks fuzzily, best match first
+  tags [TAG]                            list tags with their counts, or the bookmarks with TAG
+  dupes                                 list bookmarks filed more than once under similar URLs
+  dedupe [--longest | --merge]          remove duplicates, keeping the first copy, the one
+                                        with the longest description, or the first with
+                                        all descriptions merged
+  diff [--tsv] OLD                      list what changed since OLD: categories added,
+                                        removed or renamed, bookmarks added, removed,
+                                        moved, renamed or re-described
+  fmt [--check] [--align] [--compact] [--no-comments]
+                                        format the file, or check that it is formatted
+  check [--urls]                        report every problem in the file, including
+                                        malformed URLs with --urls
+  import [--format FORMAT] SOURCE...    merge bookmarks from other files, reporting
+                                        conflicting names, descriptions and icons
+  export [--format FORMAT]              write the bookmarks to standard output
+
+formats: sbm, html (Netscape bookmark file), chromium (Chrome, Edge, Brave),
+         firefox (places.sqlite, import only), xbel,
+         json, yaml, toml (when built with the serde feature)
+
+CATEGORY is a name that only one category has, or the full path to one,
+such as \"Work / Infra\".
+
+options:
+  -f, --file FILE                       operate on FILE instead of standard input";
+
+/// A failed command: the message to print and the exit code to use
+struct Failure(String, u8);
+
+impl Failure {
+    fn usage(message: &str) -> Failure {
+        Failure(format!("{}\n\n{}", message, USAGE), 2)
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(e: std::io::Error) -> Failure {
+        Failure(e.to_string(), 1)
+    }
+}
+
+impl From<parser::ParseError> for Failure {
+    fn from(e: parser::ParseError) -> Failure {
+        Failure(e.to_string(), 1)
+    }
+}
+
+impl From<ImportError> for Failure {
+    fn from(e: ImportError) -> Failure {
+        Failure(e.to_string(), 1)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<convert::Error> for Failure {
+    fn from(e: convert::Error) -> Failure {
+        Failure(e.to_string(), 1)
+    }
+}
+
+impl From<sbm::Error> for Failure {
+    fn from(e: sbm::Error) -> Failure {
+        Failure(e.to_string(), 1)
+    }
+}
+
+type CommandResult = Result<(), Failure>;
+
+/// Parsed command line
+struct Args {
+    file: Option<String>,
+    positional: Vec<String>,
+    flags: Vec<String>,
+    format: Option<String>,
+}
+
+impl Args {
+    fn parse(args: impl Iterator<Item = String>) -> Result<Args, Failure> {
+        let mut parsed = Args {
+            file: None,
+            positional: Vec::new(),
+            flags: Vec::new(),
+            format: None,
+        };
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-f" | "--file" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| Failure::usage("--file needs a value"))?;
+                    parsed.file = Some(value);
+                }
+                "--format" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| Failure::usage("--format needs a value"))?;
+                    parsed.format = Some(value);
+                }
+                "--" => parsed.positional.extend(args.by_ref()),
+                flag if flag.starts_with("--") => parsed.flags.push(flag.to_string()),
+                _ => parsed.positional.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+
+    /// Fail unless every flag given is one of `allowed`
+    fn allow_flags(&self, allowed: &[&str]) -> CommandResult {
+        match self.flags.iter().find(|f| !allowed.contains(&f.as_str())) {
+            Some(flag) => Err(Failure::usage(&format!("unknown option {}", flag))),
+            None => Ok(()),
+        }
+    }
+
+    /// The positional arguments after the command, which must number exactly `N`
+    fn exactly<const N: usize>(&self) -> Result<&[String; N], Failure> {
+        let rest = &self.positional[1..];
+        rest.try_into().map_err(|_| {
+            Failure::usage(&format!(
+                "{} expects {} argument(s), got {}",
+                self.positional[0],
+                N,
+                rest.len()
+            ))
+        })
+    }
+
+    /// Read the input file, or standard input
+    fn read(&self) -> Result<String, Failure> {
+        match &self.file {
+            Some(path) => Ok(std::fs::read_to_string(path)?),
+            None => {
+                let mut data = String::new();
+                std::io::stdin().read_to_string(&mut data)?;
+                Ok(data)
+            }
+        }
+    }
+
+    /// Write the output back to the input file, or to standard output
+    fn write(&self, data: &str) -> CommandResult {
+        match &self.file {
+            Some(path) => std::fs::write(path, data)?,
+            None => std::io::stdout().write_all(data.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    fn format(&self) -> Result<Format, Failure> {
+        match self.format.as_deref().unwrap_or("sbm") {
+            "sbm" => Ok(Format::Sbm),
+            "html" => Ok(Format::Html),
+            "chromium" => Ok(Format::Chromium),
+            "firefox" => Ok(Format::Firefox),
+            "xbel" => Ok(Format::Xbel),
+            #[cfg(feature = "serde")]
+            "json" => Ok(Format::Json),
+            #[cfg(feature = "serde")]
+            "yaml" => Ok(Format::Yaml),
+            #[cfg(feature = "serde")]
+            "toml" => Ok(Format::Toml),
+            other => Err(Failure::usage(&format!("unknown format {}", other))),
+        }
+    }
+}
+
+/// File formats supported by `import` and `export`
+enum Format {
+    Sbm,
+    Html,
+    Chromium,
+    Firefox,
+    Xbel,
+    #[cfg(feature = "serde")]
+    Json,
+    #[cfg(feature = "serde")]
+    Yaml,
+    #[cfg(feature = "serde")]
+    Toml,
+}
+
+impl Format {
+    fn read(&self, data: &[u8]) -> Result<Sbm, Failure> {
+        let text = || std::str::from_utf8(data).map_err(|e| Failure(e.to_string(), 1));
+        match self {
+            Format::Sbm => Ok(Sbm::parse(text()?)?),
+            Format::Html => Ok(netscape::import(text()?)?),
+            Format::Chromium => Ok(chromium::import(text()?)?),
+            Format::Firefox => Ok(firefox::import(data)?),
+            Format::Xbel => Ok(xbel::import(text()?)?),
+            #[cfg(feature = "serde")]
+            Format::Json => Ok(convert::from_json(text()?)?),
+            #[cfg(feature = "serde")]
+            Format::Yaml => Ok(convert::from_yaml(text()?)?),
+            #[cfg(feature = "serde")]
+            Format::Toml => Ok(convert::from_toml(text()?)?),
+        }
+    }
+
+    fn write(&self, sbm: &Sbm) -> Result<String, Failure> {
+        match self {
+            Format::Sbm => Ok(format::format(
+                &Document::from(sbm),
+                &FormatOptions::default(),
+            )),
+            Format::Html => Ok(netscape::export(sbm)),
+            Format::Chromium => Ok(chromium::export(sbm)),
+            Format::Firefox => Err(Failure::usage("cannot export to firefox")),
+            Format::Xbel => Ok(xbel::export(sbm)),
+            #[cfg(feature = "serde")]
+            Format::Json => Ok(convert::to_json(sbm) + "\n"),
+            #[cfg(feature = "serde")]
+            Format::Yaml => Ok(convert::to_yaml(sbm)),
+            #[cfg(feature = "serde")]
+            Format::Toml => Ok(convert::to_toml(sbm)),
+        }
+    }
+}
+
+fn list(args: &Args) -> CommandResult {
+    args.allow_flags(&["--categories"])?;
+    args.exactly::<0>()?;
+    let sbm = Sbm::parse(&args.read()?)?;
+    let mut out = String::new();
+    for (depth, category) in sbm.walk() {
+        let indent = "  ".repeat(depth - 1);
+        match &category.header.icon {
+            Some(icon) => out.push_str(&format!("{}{} {}\n", indent, icon, category.header.name)),
+            None => out.push_str(&format!("{}{}\n", indent, category.header.name)),
+        }
+        if args.flag("--categories") {
+            continue;
+        }
+        for bookmark in &category.bookmarks {
+            out.push_str(&format!(
+                "{}  {}  {}\n",
+                indent, bookmark.name, bookmark.url
+            ));
+        }
+    }
+    std::io::stdout().write_all(out.as_bytes())?;
+    Ok(())
+}
+
+/// The index of the category named by `target`: either the full path to it,
+/// joined with ` / `, or a name that only one category has, at any depth
+fn locate(doc: &Document, target: &str) -> Result<Option<usize>, Failure> {
+    let path: Vec<&str> = target.split(" / ").map(str::trim).collect();
+    if path.len() > 1 {
+        return Ok(doc.find(&path));
+    }
+    let mut found =
+        (0..doc.categories.len()).filter(|&i| doc.categories[i].header.value.name == target);
+    match (found.next(), found.next()) {
+        (Some(_), Some(_)) => Err(Failure(
+            format!(
+                "more than one category is named {}; give its full path instead",
+                target
+            ),
+            1,
+        )),
+        (index, _) => Ok(index),
+    }
+}
+
+fn add(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    let [category, name, description, url] = args.exactly::<4>()?;
+    let mut doc = Document::parse(&args.read()?)?;
+    let bookmark = Bookmark::new(name, description, url);
+    let index = match locate(&doc, category)? {
+        Some(index) => index,
+        None => {
+            // create the missing categories along the path
+            let path: Vec<&str> = category.split(" / ").map(str::trim).collect();
+            let mut parent = None;
+            for end in 1..=path.len() {
+                if doc.find(&path[..end]).is_none() {
+                    let header = Header::new(path[end - 1], None);
+                    match parent {
+                        Some(parent) => doc.push_subcategory(parent, header),
+                        None => doc.push_category(header),
+                    };
+                }
+                parent = doc.find(&path[..end]);
+            }
+            parent.unwrap()
+        }
+    };
+    doc.categories[index].push_bookmark(bookmark);
+    args.write(&doc.to_string())
+}
+
+fn remove(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    let [url] = args.exactly::<1>()?;
+    let mut doc = Document::parse(&args.read()?)?;
+    let removed: usize = doc
+        .categories
+        .iter_mut()
+        .map(|c| c.remove_bookmarks(|b| b.url == *url))
+        .sum();
+    if removed == 0 {
+        return Err(Failure(format!("no bookmark with URL {}", url), 1));
+    }
+    args.write(&doc.to_string())
+}
+
+fn move_bookmark(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    let [url, target] = args.exactly::<2>()?;
+    let mut doc = Document::parse(&args.read()?)?;
+    let Some(index) = locate(&doc, target)? else {
+        return Err(Failure(format!("no category named {}", target), 1));
+    };
+    let mut moved = Vec::new();
+    for (i, category) in doc.categories.iter_mut().enumerate() {
+        if i == index {
+            continue;
+        }
+        category.remove_bookmarks(|b| {
+            let matches = b.url == *url;
+            if matches {
+                moved.push(b.clone());
+            }
+            matches
+        });
+    }
+    if moved.is_empty() {
+        return Err(Failure(
+            format!("no bookmark with URL {} outside {}", url, target),
+            1,
+        ));
+    }
+    for bookmark in moved {
+        doc.categories[index].push_bookmark(bookmark);
+    }
+    args.write(&doc.to_string())
+}
+
+fn search(args: &Args) -> CommandResult {
+    args.allow_flags(&["--fuzzy"])?;
+    if args.positional.len() < 2 {
+        return Err(Failure::usage("search expects a query"));
+    }
+    if args.flag("--fuzzy") {
+        return fuzzy_search(args);
+    }
+    let query = Query::parse(&args.positional[1..].join(" "))
+        .map_err(|e| Failure(format!("invalid query: {}", e), 2))?;
+    let sbm = Sbm::parse(&args.read()?)?;
+    print_matches(&sbm, |path, bookmark| query.matches(path, bookmark))
+}
+
+fn fuzzy_search(args: &Args) -> CommandResult {
+    let sbm = Sbm::parse(&args.read()?)?;
+    let index = Index::new(&sbm);
+    let mut out = String::new();
+    for m in index.search(&args.positional[1..].join(" ")) {
+        let names: Vec<&str> = m.path.iter().map(|c| c.header.name.as_str()).collect();
+        out.push_str(&format!(
+            "{}\t{}\t{}\n",
+            names.join(" / "),
+            m.bookmark.name,
+            m.bookmark.url
+        ));
+    }
+    std::io::stdout().write_all(out.as_bytes())?;
+    if out.is_empty() {
+        return Err(Failure(String::new(), 1));
+    }
+    Ok(())
+}
+
+fn tags(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    let sbm = Sbm::parse(&args.read()?)?;
+    if args.positional.len() > 1 {
+        let [tag] = args.exactly::<1>()?;
+        return print_matches(&sbm, |_, bookmark| bookmark.has_tag(tag));
+    }
+    let mut out = String::new();
+    for (tag, count) in sbm.all_tags() {
+        out.push_str(&format!("{}\t{}\n", tag, count));
+    }
+    std::io::stdout().write_all(out.as_bytes())?;
+    Ok(())
+}
+
+fn dupes(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    args.exactly::<0>()?;
+    let sbm = Sbm::parse(&args.read()?)?;
+    let groups: Vec<String> = sbm
+        .duplicates()
+        .iter()
+        .map(|group| {
+            group
+                .copies
+                .iter()
+                .map(|copy| {
+                    format!(
+                        "{}\t{}\t{}\n",
+                        copy.path.join(" / "),
+                        copy.bookmark.name,
+                        copy.bookmark.url
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    std::io::stdout().write_all(groups.join("\n").as_bytes())?;
+    if groups.is_empty() {
+        return Err(Failure(String::new(), 1));
+    }
+    Ok(())
+}
+
+fn dedupe(args: &Args) -> CommandResult {
+    args.allow_flags(&["--longest", "--merge"])?;
+    args.exactly::<0>()?;
+    let policy = match (args.flag("--longest"), args.flag("--merge")) {
+        (false, false) => Policy::KeepFirst,
+        (true, false) => Policy::LongestDescription,
+        (false, true) => Policy::MergeDescriptions,
+        (true, true) => return Err(Failure::usage("--longest and --merge don't go together")),
+    };
+    let mut doc = Document::parse(&args.read()?)?;
+    let plan = dedupe::resolve(doc.categories.iter().flat_map(|c| c.bookmarks()), policy);
+    let mut plan = plan.into_iter();
+    for category in &mut doc.categories {
+        let outcomes: Vec<Option<Bookmark>> =
+            plan.by_ref().take(category.bookmarks().count()).collect();
+        for (bookmark, outcome) in category.bookmarks_mut().zip(&outcomes) {
+            if let Some(outcome) = outcome {
+                *bookmark = outcome.clone();
+            }
+        }
+        let mut outcomes = outcomes.iter();
+        category.remove_bookmarks(|_| outcomes.next().is_some_and(Option::is_none));
+    }
+    args.write(&doc.to_string())
+}
+
+/// Print the path, name and URL of every bookmark matching `f`, failing if
+/// there are none
+///
+/// `f` gets the categories the bookmark is filed under along with the bookmark.
+fn print_matches<F: Fn(&[&Category], &Bookmark) -> bool>(sbm: &Sbm, f: F) -> CommandResult {
+    let mut out = String::new();
+    let mut path: Vec<&Category> = Vec::new();
+    for (depth, category) in sbm.walk() {
+        path.truncate(depth - 1);
+        path.push(category);
+        for bookmark in category.bookmarks.iter().filter(|b| f(&path, b)) {
+            let names: Vec<&str> = path.iter().map(|c| c.header.name.as_str()).collect();
+            out.push_str(&format!(
+                "{}\t{}\t{}\n",
+                names.join(" / "),
+                bookmark.name,
+                bookmark.url
+            ));
+        }
+    }
+    std::io::stdout().write_all(out.as_bytes())?;
+    if out.is_empty() {
+        return Err(Failure(String::new(), 1));
+    }
+    Ok(())
+}
+
+fn fmt(args: &Args) -> CommandResult {
+    args.allow_flags(&["--check", "--align", "--compact", "--no-comments"])?;
+    args.exactly::<0>()?;
+    let mut options = if args.flag("--compact") {
+        FormatOptions::compact()
+    } else {
+        FormatOptions::default()
+    };
+    options.align |= args.flag("--align");
+    options.keep_comments &= !args.flag("--no-comments");
+
+    let data = args.read()?;
+    if args.flag("--check") {
+        return match format::check(&data, &options)? {
+            true => Ok(()),
+            false => Err(Failure(
+                format!(
+                    "{} is not formatted",
+                    args.file.as_deref().unwrap_or("input")
+                ),
+                1,
+            )),
+        };
+    }
+    args.write(&format::format_str(&data, &options)?)
+}
+
+fn check(args: &Args) -> CommandResult {
+    args.allow_flags(&["--urls"])?;
+    args.exactly::<0>()?;
+    let options = ParseOptions {
+        validate_urls: args.flag("--urls"),
+        ..ParseOptions::default()
+    };
+    let (_, diagnostics) = parser::parse_recovering(&args.read()?, &options);
+    let name = args.file.as_deref().unwrap_or("<stdin>");
+    for diagnostic in &diagnostics {
+        eprintln!("{}: {}", name, diagnostic);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(Failure(format!("{}: {} error(s)", name, errors), 1));
+    }
+    Ok(())
+}
+
+fn diff(args: &Args) -> CommandResult {
+    args.allow_flags(&["--tsv"])?;
+    let [old] = args.exactly::<1>()?;
+    let old = Sbm::parse(&std::fs::read_to_string(old)?)?;
+    let new = Sbm::parse(&args.read()?)?;
+    let changes = old.diff(&new);
+    let mut out = String::new();
+    for change in &changes {
+        match args.flag("--tsv") {
+            true => out += &change.tsv(),
+            false => out += &change.to_string(),
+        }
+        out.push('\n');
+    }
+    std::io::stdout().write_all(out.as_bytes())?;
+    // Like diff(1): 0 when the files are the same, 1 when they differ
+    if !changes.is_empty() {
+        return Err(Failure(String::new(), 1));
+    }
+    Ok(())
+}
+
+fn import(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    let sources = &args.positional[1..];
+    if sources.is_empty() {
+        return Err(Failure::usage("import expects at least one SOURCE"));
+    }
+    let format = args.format()?;
+    let mut doc = Document::parse(&args.read()?)?;
+    for source in sources {
+        let imported = format.read(&std::fs::read(source)?)?;
+        for conflict in doc.merge(&imported, Resolution::Ours) {
+            eprintln!("{}: {}", source, conflict);
+        }
+    }
+    args.write(&doc.to_string())
+}
+
+fn export(args: &Args) -> CommandResult {
+    args.allow_flags(&[])?;
+    args.exactly::<0>()?;
+    let sbm = Sbm::parse(&args.read()?)?;
+    let out = args.format()?.write(&sbm)?;
+    std::io::stdout().write_all(out.as_bytes())?;
+    Ok(())
+}
+
+fn run() -> CommandResult {
+    let args = Args::parse(std::env::args().skip(1))?;
+    let Some(command) = args.positional.first() else {
+        if args.flag("--help") {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        return Err(Failure::usage("missing command"));
+    };
+    match command.as_str() {
+        "list" => list(&args),
+        "add" => add(&args),
+        "remove" => remove(&args),
+        "move" => move_bookmark(&args),
+        "search" => search(&args),
+        "tags" => tags(&args),
+        "dupes" => dupes(&args),
+        "dedupe" => dedupe(&args),
+        "diff" => diff(&args),
+        "fmt" => fmt(&args),
+        "check" => check(&args),
+        "import" => import(&args),
+        "export" => export(&args),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(Failure::usage(&format!("unknown command {}", other))),
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure(message, code)) => {
+            if !message.is_empty() {
+                eprintln!("sbm: {}", message);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
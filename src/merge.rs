@@ -0,0 +1,299 @@
+//! Merging files
+//!
+//! [`Sbm::merge`] lays one file over another, such as a personal overlay over
+//! a shared file. Categories with the same name are merged, at the top level
+//! and then among the subcategories of merged categories; the others are
+//! added at the end. Within a merged category, bookmarks with the same
+//! [`url::key`] are merged and the others are added at the end. A URL filed
+//! under different categories in the two files ends up in both.
+//!
+//! Where the two sides disagree, the merge records a [`Conflict`]: a bookmark
+//! with a different name or description, or a category with a different
+//! icon. An empty description or a missing icon on one side is not a
+//! conflict; the other side's is used. Tags and metadata never conflict: the
+//! merged bookmark or category gets the tags and metadata keys of both.
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::merge::Resolution;
+//! use sbm::Sbm;
+//! let shared: Sbm = "#Languages|👨‍💻\nRust|Home page|https://www.rust-lang.org/".parse().unwrap();
+//! let mine: Sbm = "#Languages\nRust|The Rust Programming Language|https://www.rust-lang.org/\n#Reading\nBlog|Blog|https://blog.example.com/"
+//!     .parse()
+//!     .unwrap();
+//! let merge = shared.merge(&mine);
+//! assert_eq!(merge.conflicts.len(), 1);
+//! let merged = merge.resolve(Resolution::Theirs);
+//! assert_eq!(merged.to_string(), "#Languages|👨‍💻\nRust|The Rust Programming Language|https://www.rust-lang.org/\n#Reading\nBlog|Blog|https://blog.example.com/");
+//! ```
+
+use crate::{url, Bookmark, Category, Sbm};
+use std::collections::BTreeMap;
+
+/// Where the two sides of a merge disagree
+#[derive(Debug, PartialEq, Clone)]
+pub enum Conflict {
+    /// Both sides have a bookmark with this URL in the category at `path`,
+    /// with different names or descriptions
+    Bookmark {
+        /// Names of the categories from the top level down
+        path: Vec<String>,
+        ours: Bookmark,
+        theirs: Bookmark,
+    },
+    /// Both sides have the category at `path`, with different icons
+    Icon {
+        path: Vec<String>,
+        ours: String,
+        theirs: String,
+    },
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Conflict::Bookmark { path, ours, theirs } => {
+                write!(f, "{}: {}:", path.join(" / "), ours.url)?;
+                let mut separator = " ";
+                if ours.name != theirs.name {
+                    write!(f, " name {:?} vs {:?}", ours.name, theirs.name)?;
+                    separator = ", ";
+                }
+                if ours.description != theirs.description {
+                    write!(
+                        f,
+                        "{}description {:?} vs {:?}",
+                        separator, ours.description, theirs.description
+                    )?;
+                }
+                Ok(())
+            }
+            Conflict::Icon { path, ours, theirs } => {
+                write!(f, "{}: icon {:?} vs {:?}", path.join(" / "), ours, theirs)
+            }
+        }
+    }
+}
+
+/// Which side of a [`Conflict`] wins
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Resolution {
+    /// The file [`Sbm::merge`] was called on
+    #[default]
+    Ours,
+    /// The file passed to [`Sbm::merge`]
+    Theirs,
+}
+
+/// The result of [`Sbm::merge`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Merge {
+    /// The merged file, with every conflict resolved as [`Resolution::Ours`]
+    pub sbm: Sbm,
+    /// Every conflict, in the order the other file was read
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    /// The merged file, with every conflict resolved the same way
+    pub fn resolve(self, resolution: Resolution) -> Sbm {
+        self.resolve_with(|_| resolution)
+    }
+
+    /// The merged file, with each conflict resolved as `choose` says
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use sbm::merge::{Conflict, Resolution};
+    /// use sbm::Sbm;
+    /// let ours: Sbm = "#Web|🌐\nMDN|Docs|https://developer.mozilla.org/".parse().unwrap();
+    /// let theirs: Sbm = "#Web|🕸\nMDN Web Docs|Docs|https://developer.mozilla.org/".parse().unwrap();
+    /// let merged = ours.merge(&theirs).resolve_with(|conflict| match conflict {
+    ///     Conflict::Icon { .. } => Resolution::Ours,
+    ///     Conflict::Bookmark { .. } => Resolution::Theirs,
+    /// });
+    /// assert_eq!(merged.to_string(), "#Web|🌐\nMDN Web Docs|Docs|https://developer.mozilla.org/");
+    /// ```
+    pub fn resolve_with<F: FnMut(&Conflict) -> Resolution>(self, mut choose: F) -> Sbm {
+        let mut sbm = self.sbm;
+        for conflict in &self.conflicts {
+            if choose(conflict) == Resolution::Ours {
+                continue;
+            }
+            match conflict {
+                Conflict::Bookmark { path, ours, theirs } => {
+                    let bookmark = category_at(&mut sbm, path)
+                        .bookmarks
+                        .iter_mut()
+                        .find(|b| b.url == ours.url)
+                        .unwrap();
+                    bookmark.name.clone_from(&theirs.name);
+                    bookmark.description.clone_from(&theirs.description);
+                }
+                Conflict::Icon { path, theirs, .. } => {
+                    category_at(&mut sbm, path).header.icon = Some(theirs.clone());
+                }
+            }
+        }
+        sbm
+    }
+}
+
+/// The category a conflict was found in, which the merge has made sure exists
+fn category_at<'s>(sbm: &'s mut Sbm, path: &[String]) -> &'s mut Category {
+    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+    sbm.find_mut(&path).unwrap()
+}
+
+/// Merge `theirs` into a copy of `ours`, see [`Sbm::merge`]
+pub fn merge(ours: &Sbm, theirs: &Sbm) -> Merge {
+    let mut merge = Merge {
+        sbm: ours.clone(),
+        conflicts: Vec::new(),
+    };
+    merge_categories(
+        &mut merge.sbm.0,
+        &theirs.0,
+        &mut Vec::new(),
+        &mut merge.conflicts,
+    );
+    merge
+}
+
+fn merge_categories(
+    ours: &mut Vec<Category>,
+    theirs: &[Category],
+    path: &mut Vec<String>,
+    conflicts: &mut Vec<Conflict>,
+) {
+    for category in theirs {
+        let Some(target) = ours
+            .iter_mut()
+            .find(|c| c.header.name == category.header.name)
+        else {
+            ours.push(category.clone());
+            continue;
+        };
+        path.push(category.header.name.clone());
+        match (&target.header.icon, &category.header.icon) {
+            (Some(ours), Some(theirs)) if ours != theirs => conflicts.push(Conflict::Icon {
+                path: path.clone(),
+                ours: ours.clone(),
+                theirs: theirs.clone(),
+            }),
+            (None, Some(theirs)) => target.header.icon = Some(theirs.clone()),
+            _ => {}
+        }
+        merge_metadata(&mut target.header.metadata, &category.header.metadata);
+        for bookmark in &category.bookmarks {
+            merge_bookmark(target, bookmark, path, conflicts);
+        }
+        merge_categories(&mut target.children, &category.children, path, conflicts);
+        path.pop();
+    }
+}
+
+fn merge_bookmark(
+    target: &mut Category,
+    bookmark: &Bookmark,
+    path: &[String],
+    conflicts: &mut Vec<Conflict>,
+) {
+    let key = url::key(&bookmark.url);
+    let Some(existing) = target
+        .bookmarks
+        .iter_mut()
+        .find(|b| url::key(&b.url) == key)
+    else {
+        target.bookmarks.push(bookmark.clone());
+        return;
+    };
+    if existing.description.is_empty() {
+        existing.description.clone_from(&bookmark.description);
+    }
+    if existing.name != bookmark.name
+        || (existing.description != bookmark.description && !bookmark.description.is_empty())
+    {
+        conflicts.push(Conflict::Bookmark {
+            path: path.to_vec(),
+            ours: existing.clone(),
+            theirs: Bookmark {
+                description: match bookmark.description.is_empty() {
+                    true => existing.description.clone(),
+                    false => bookmark.description.clone(),
+                },
+                ..bookmark.clone()
+            },
+        });
+    }
+    for tag in &bookmark.tags {
+        if !existing.tags.contains(tag) {
+            existing.tags.push(tag.clone());
+        }
+    }
+    merge_metadata(&mut existing.metadata, &bookmark.metadata);
+}
+
+/// Add the keys of `theirs` that `ours` doesn't have
+fn merge_metadata(ours: &mut BTreeMap<String, String>, theirs: &BTreeMap<String, String>) {
+    for (key, value) in theirs {
+        ours.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARED: &str = "#Work|💼\nJira|Issues|https://jira.example.com/|work\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\n#Languages\nRust||https://www.rust-lang.org/\n";
+    const MINE: &str = "#Work|🏢\nJira|My issues|https://jira.example.com|mine\nWiki|Team wiki|https://wiki.example.com/\n##Infra\nGrafana|Dashboards|http://grafana.example.com\n##Docs\nBook|The book|https://doc.rust-lang.org/book/\n#Languages|👨‍💻\nRust|The Rust Programming Language|https://rust-lang.org/\n#Reading\n";
+
+    #[test]
+    fn test_merge() {
+        let shared: Sbm = SHARED.parse().unwrap();
+        let mine: Sbm = MINE.parse().unwrap();
+        let merge = shared.merge(&mine);
+        assert_eq!(
+            merge.conflicts,
+            vec![
+                Conflict::Icon {
+                    path: vec!["Work".to_string()],
+                    ours: "💼".to_string(),
+                    theirs: "🏢".to_string(),
+                },
+                Conflict::Bookmark {
+                    path: vec!["Work".to_string()],
+                    ours: Bookmark::new("Jira", "Issues", "https://jira.example.com/")
+                        .with_tags(["work"]),
+                    theirs: Bookmark::new("Jira", "My issues", "https://jira.example.com")
+                        .with_tags(["mine"]),
+                },
+            ]
+        );
+        assert_eq!(
+            merge.conflicts[1].to_string(),
+            r#"Work: https://jira.example.com/: description "Issues" vs "My issues""#
+        );
+
+        let expected = "#Work|💼\nJira|Issues|https://jira.example.com/|work,mine\nWiki|Team wiki|https://wiki.example.com/\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\n##Docs\nBook|The book|https://doc.rust-lang.org/book/\n#Languages|👨‍💻\nRust|The Rust Programming Language|https://www.rust-lang.org/\n#Reading\n";
+        assert_eq!(merge.sbm.to_string(), expected);
+        assert_eq!(merge.clone().resolve(Resolution::Ours), merge.sbm);
+
+        let theirs = merge.resolve(Resolution::Theirs);
+        let work = theirs.find(&["Work"]).unwrap();
+        assert_eq!(work.header.icon.as_deref(), Some("🏢"));
+        assert_eq!(work.bookmarks[0].description, "My issues");
+        assert_eq!(work.bookmarks[0].url, "https://jira.example.com/");
+        assert_eq!(work.bookmarks[0].tags, vec!["work", "mine"]);
+    }
+
+    #[test]
+    fn test_merge_is_idempotent() {
+        let shared: Sbm = SHARED.parse().unwrap();
+        let merge = shared.merge(&shared);
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(merge.sbm, shared);
+    }
+}
@@ -1,6 +1,321 @@
-use crate::{Bookmark, Category, Header};
+use crate::borrowed::{BookmarkRef, CategoryRef, HeaderRef};
+use crate::url::{Url, UrlError};
+use crate::{Bookmark, Category, Header, Sbm};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::{Range, RangeInclusive};
+
+/// Where in the input a parse error occurred
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Location {
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based column, counted in characters
+    pub column: usize,
+    /// Byte range of the offending text within the whole input
+    pub span: Range<usize>,
+    /// The full text of the offending line
+    pub text: String,
+}
+
+impl Location {
+    /// Location covering a whole line that starts at byte `offset`
+    fn of_line(line: usize, offset: usize, text: &str) -> Location {
+        Location {
+            line,
+            column: 1,
+            span: offset..offset + text.len(),
+            text: text.to_string(),
+        }
+    }
+
+    /// Location of the bytes `part` of a line that starts at byte `offset`
+    fn of_part(line: usize, offset: usize, text: &str, part: Range<usize>) -> Location {
+        Location {
+            line,
+            column: text[..part.start].chars().count() + 1,
+            span: offset + part.start..offset + part.end,
+            text: text.to_string(),
+        }
+    }
+}
+
+/// Parse error
+///
+/// Every variant carries the [`Location`] of the line that failed to parse.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// A bookmark line has the wrong number of fields
+    Bookmark {
+        location: Location,
+        expected: RangeInclusive<usize>,
+        found: usize,
+    },
+    /// A header line has the wrong number of fields
+    Header {
+        location: Location,
+        expected: RangeInclusive<usize>,
+        found: usize,
+    },
+    /// A bookmark appears before the first category header
+    OrphanBookmark { location: Location },
+    /// A header with several `#`s has no header with fewer `#`s before it
+    ///
+    /// It is read the way files were before subcategories existed: as a
+    /// top-level category whose name keeps all but the first `#`.
+    OrphanSubcategory { location: Location },
+    /// Input expected to hold a single category holds none or several
+    ///
+    /// The location is that of the second top-level header, or the first line
+    /// if there is no header at all.
+    Category { location: Location, found: usize },
+    /// A bookmark's URL is malformed, see [`ParseOptions::validate_urls`]
+    Url { location: Location, error: UrlError },
+}
+
+impl ParseError {
+    /// Where the error occurred
+    pub fn location(&self) -> &Location {
+        match self {
+            ParseError::Bookmark { location, .. }
+            | ParseError::Header { location, .. }
+            | ParseError::OrphanBookmark { location }
+            | ParseError::OrphanSubcategory { location }
+            | ParseError::Category { location, .. }
+            | ParseError::Url { location, .. } => location,
+        }
+    }
+
+    /// Describe the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::Bookmark {
+                expected, found, ..
+            } => format!(
+                "bookmark has wrong number of parts (expected {}, found {})",
+                fmt_expected(expected),
+                found
+            ),
+            ParseError::Header {
+                expected, found, ..
+            } => format!(
+                "header has wrong number of parts (expected {}, found {})",
+                fmt_expected(expected),
+                found
+            ),
+            ParseError::OrphanBookmark { .. } => {
+                "bookmark appears before the first category header".to_string()
+            }
+            ParseError::OrphanSubcategory { .. } => {
+                "subcategory has no parent, read as a top-level category".to_string()
+            }
+            ParseError::Category { found, .. } => {
+                format!("expected a single category, found {}", found)
+            }
+            ParseError::Url { error, .. } => format!("bookmark has an invalid URL: {}", error),
+        }
+    }
+
+    /// Rebase the location onto `line`, which starts at byte `offset` of the input
+    pub(crate) fn at(mut self, line: usize, offset: usize, text: &str) -> ParseError {
+        match &mut self {
+            ParseError::Bookmark { location, .. }
+            | ParseError::Header { location, .. }
+            | ParseError::OrphanBookmark { location }
+            | ParseError::OrphanSubcategory { location }
+            | ParseError::Category { location, .. }
+            | ParseError::Url { location, .. } => *location = Location::of_line(line, offset, text),
+        }
+        self
+    }
+}
+
+fn fmt_expected(expected: &RangeInclusive<usize>) -> String {
+    if expected.start() == expected.end() {
+        expected.start().to_string()
+    } else {
+        format!("{} to {}", expected.start(), expected.end())
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let location = self.location();
+        write!(
+            f,
+            "line {}, column {}: {}: `{}`",
+            location.line,
+            location.column,
+            self.message(),
+            location.text
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something looks off, but the data was kept
+    Warning,
+    /// The line is invalid; data on it may have been lost
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found while parsing with [`parse_recovering`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub location: Location,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, error: ParseError) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message: error.message(),
+            location: error.location().clone(),
+        }
+    }
+
+    /// 1-based line number the diagnostic refers to
+    pub fn line(&self) -> usize {
+        self.location.line
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: line {}, column {}: {}",
+            self.severity, self.location.line, self.location.column, self.message
+        )
+    }
+}
+
+/// Name of the category that collects bookmarks found before the first header
+/// when parsing with [`Orphans::Collect`]
+///
+/// If the file has a top-level category of this name, they go at the front of
+/// it instead.
+pub const UNCATEGORIZED: &str = "Uncategorized";
+
+/// What to do with bookmarks that appear before the first category header
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Orphans {
+    /// Fail with [`ParseError::OrphanBookmark`]
+    #[default]
+    Reject,
+    /// Put them in an implicit [`UNCATEGORIZED`] category at the front, and
+    /// report each with a warning
+    Collect,
+}
+
+/// Options controlling how an SBM file is parsed
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ParseOptions {
+    pub orphans: Orphans,
+    /// Check bookmark URLs with [`Url::parse`], failing with
+    /// [`ParseError::Url`] on malformed ones
+    pub validate_urls: bool,
+}
+
+impl ParseOptions {
+    /// Strict parsing: every bookmark must belong to a category
+    pub fn strict() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Lenient parsing: orphaned bookmarks are collected into [`UNCATEGORIZED`]
+    pub fn lenient() -> ParseOptions {
+        ParseOptions {
+            orphans: Orphans::Collect,
+            ..ParseOptions::default()
+        }
+    }
+
+    /// Check the URL of the bookmark on line `number`, if these options ask for it
+    ///
+    /// The error points at the URL field of the line.
+    pub(crate) fn check_url(
+        &self,
+        url: &str,
+        number: usize,
+        offset: usize,
+        line: &str,
+    ) -> Result<(), ParseError> {
+        if !self.validate_urls {
+            return Ok(());
+        }
+        Url::parse(url).map(drop).map_err(|error| {
+            let mut fields = split_raw(line, '|');
+            let start: usize = fields.by_ref().take(2).map(|f| f.len() + 1).sum();
+            let raw = fields.next().unwrap_or("");
+            let start = start + raw.len() - raw.trim_start().len();
+            ParseError::Url {
+                location: Location::of_part(number, offset, line, start..start + raw.trim().len()),
+                error,
+            }
+        })
+    }
+}
+
+/// Characters that may follow a `\` to stand for themselves
+const ESCAPABLE: [char; 4] = ['\\', '|', '#', '/'];
+
+/// Inside the tags field, `\,` also stands for a comma
+const TAG_ESCAPABLE: [char; 5] = ['\\', '|', '#', '/', ','];
+
+/// Iterator over the raw fields of a line, see [`split_raw`]
+pub(crate) struct RawFields<'a> {
+    rest: Option<&'a str>,
+    separator: char,
+}
+
+impl<'a> Iterator for RawFields<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest?;
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == self.separator {
+                self.rest = Some(&rest[i + c.len_utf8()..]);
+                return Some(&rest[..i]);
+            }
+        }
+        self.rest = None;
+        Some(rest)
+    }
+}
+
+/// Split a line on every `separator` that isn't escaped, without unescaping the parts
+pub(crate) fn split_raw(line: &str, separator: char) -> RawFields<'_> {
+    RawFields {
+        rest: Some(line),
+        separator,
+    }
+}
 
 /// Split a line by the pipe character
+///
+/// A pipe preceded by a backslash does not split the line, and escape
+/// sequences in the resulting parts are resolved with [`unescape`].
 /// # Examples
 ///
 /// ```
@@ -8,12 +323,261 @@ use crate::{Bookmark, Category, Header};
 /// let line = "Rust|Systems programming language|https://www.rust-lang.org/";
 /// let parts = parser::split_pipe(line);
 /// assert_eq!(parts, vec!["Rust", "Systems programming language", "https://www.rust-lang.org/"]);
+///
+/// let line = r"Bash|Unix pipes \| redirection|https://www.gnu.org/software/bash/";
+/// let parts = parser::split_pipe(line);
+/// assert_eq!(parts, vec!["Bash", "Unix pipes | redirection", "https://www.gnu.org/software/bash/"]);
 /// ```
-pub fn split_pipe(line: &str) -> Vec<&str> {
-    line.split('|').collect()
+pub fn split_pipe(line: &str) -> Vec<Cow<'_, str>> {
+    split_raw(line, '|').map(unescape).collect()
+}
+
+/// Resolve the escape sequences `\\`, `\|`, `\#` and `\/` in a field
+///
+/// A backslash followed by any other character is kept as-is.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// assert_eq!(parser::unescape(r"a \| b \\ c"), r"a | b \ c");
+/// assert_eq!(parser::unescape(r"C:\Users"), r"C:\Users");
+/// ```
+pub fn unescape(field: &str) -> Cow<'_, str> {
+    unescape_chars(field, &ESCAPABLE)
+}
+
+fn unescape_chars<'a>(field: &'a str, escapable: &[char]) -> Cow<'a, str> {
+    if !field.contains('\\') {
+        return Cow::Borrowed(field);
+    }
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && escapable.contains(&next) => {
+                out.push(next);
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Escape a field so that it can be written between pipes
+///
+/// Backslashes and pipes are escaped anywhere in the field. This is the
+/// inverse of [`unescape`].
+///
+/// Whitespace around a field is not significant in the format, so it is
+/// left as it is and lost when the field is read back, which trims it.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// assert_eq!(parser::escape("Unix pipes | redirection"), r"Unix pipes \| redirection");
+/// assert_eq!(parser::escape("plain"), "plain");
+/// let bookmark = parser::parse_bookmark(&format!("{}|x|y", parser::escape(" padded "))).unwrap();
+/// assert_eq!(bookmark.name, "padded");
+/// ```
+pub fn escape(field: &str) -> Cow<'_, str> {
+    if !field.contains(['\\', '|']) {
+        return Cow::Borrowed(field);
+    }
+    let mut out = String::with_capacity(field.len() + 2);
+    for c in field.chars() {
+        if c == '\\' || c == '|' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// Escape a tag so that it can be written in a comma-separated list
+pub(crate) fn escape_tag(tag: &str) -> Cow<'_, str> {
+    if !tag.contains(['\\', '|', ',']) {
+        return Cow::Borrowed(tag);
+    }
+    let mut out = String::with_capacity(tag.len() + 2);
+    for c in tag.chars() {
+        if matches!(c, '\\' | '|' | ',') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    Cow::Owned(out)
+}
+
+/// Split the tags field of a bookmark on unescaped commas
+///
+/// Tags are trimmed and empty ones are dropped.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// assert_eq!(parser::split_tags(r"rust, docs,, a\,b"), vec!["rust", "docs", "a,b"]);
+/// ```
+pub fn split_tags(field: &str) -> Vec<Cow<'_, str>> {
+    split_raw(field, ',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| unescape_chars(tag, &TAG_ESCAPABLE))
+        .collect()
+}
+
+/// Escape the first field of a line
+///
+/// On top of [`escape`], a leading `#` or `/` is escaped so that the line
+/// isn't mistaken for a header or a comment.
+pub(crate) fn escape_first(field: &str) -> Cow<'_, str> {
+    let escaped = escape(field);
+    if escaped.starts_with(['#', '/']) {
+        Cow::Owned(format!("\\{}", escaped))
+    } else {
+        escaped
+    }
+}
+
+/// Parse a `//@ key: value` annotation line
+///
+/// The `//@` has to be followed by whitespace. Returns the key and the
+/// value, trimmed and unescaped with [`unescape_annotation`], or `None` if
+/// the line isn't an annotation. The key is everything up to the first `:`
+/// that isn't escaped.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// let pair = |line| parser::parse_annotation(line).map(|(k, v)| (k.into_owned(), v.into_owned()));
+/// assert_eq!(pair("//@ added: 2024-01-31"), Some(("added".into(), "2024-01-31".into())));
+/// assert_eq!(pair("//@ note: a: b"), Some(("note".into(), "a: b".into())));
+/// assert_eq!(pair(r"//@ a\:b: two\nlines\s"), Some(("a:b".into(), "two\nlines ".into())));
+/// assert_eq!(pair("// added: 2024-01-31"), None);
+/// assert_eq!(pair("//@todo: fix later"), None);
+/// assert_eq!(pair("//@ no colon"), None);
+/// ```
+pub fn parse_annotation(line: &str) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+    let rest = line.strip_prefix("//@")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let key = split_raw(rest, ':').next()?;
+    let value = rest.get(key.len() + 1..)?;
+    Some((
+        unescape_annotation(key.trim()),
+        unescape_annotation(value.trim()),
+    ))
+}
+
+/// Resolve the escape sequences of an annotation key or value
+///
+/// `\\`, `\:`, `\n`, `\r`, `\t`, `\s` (a space) and `\u{…}` (any character
+/// by its hexadecimal code point) are escapes. A backslash followed by
+/// anything else is kept as-is.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// assert_eq!(parser::unescape_annotation(r"\sa\:b\\c\u{a0}"), " a:b\\c\u{a0}");
+/// assert_eq!(parser::unescape_annotation(r"C:\Users"), r"C:\Users");
+/// ```
+pub fn unescape_annotation(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let (c, len) = match rest[1..].chars().next() {
+            Some(c @ ('\\' | ':')) => (c, 2),
+            Some('n') => ('\n', 2),
+            Some('r') => ('\r', 2),
+            Some('t') => ('\t', 2),
+            Some('s') => (' ', 2),
+            Some('u') => match code_point(&rest[2..]) {
+                Some((c, len)) => (c, 2 + len),
+                None => ('\\', 1),
+            },
+            _ => ('\\', 1),
+        };
+        out.push(c);
+        rest = &rest[len..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Read a `{…}` hexadecimal code point, returning the character and the
+/// length of the braces
+fn code_point(text: &str) -> Option<(char, usize)> {
+    let (hex, _) = text.strip_prefix('{')?.split_once('}')?;
+    if hex.is_empty() || hex.len() > 6 {
+        return None;
+    }
+    let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+    Some((c, hex.len() + 2))
+}
+
+/// Escape an annotation key or value, the inverse of [`unescape_annotation`]
+///
+/// Backslashes and line breaks are escaped anywhere, `:` too if `key`, and
+/// whitespace at either end so that it isn't trimmed away.
+fn escape_annotation(text: &str, key: bool) -> Cow<'_, str> {
+    let colons = key && text.contains(':');
+    if text.trim().len() == text.len() && !colons && !text.contains(['\\', '\n', '\r']) {
+        return Cow::Borrowed(text);
+    }
+    // the bytes between leading and trailing whitespace
+    let inner = text.len() - text.trim_start().len()..text.trim_end().len();
+    let mut out = String::with_capacity(text.len() + 4);
+    for (i, c) in text.char_indices() {
+        match c {
+            '\\' => out.push_str(r"\\"),
+            ':' if key => out.push_str(r"\:"),
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            c if inner.contains(&i) || !c.is_whitespace() => out.push(c),
+            ' ' => out.push_str(r"\s"),
+            '\t' => out.push_str(r"\t"),
+            c => out.push_str(&format!("\\u{{{:x}}}", u32::from(c))),
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Write a metadata entry as an annotation line, the inverse of [`parse_annotation`]
+pub(crate) fn annotation(key: &str, value: &str) -> String {
+    let key = escape_annotation(key, true);
+    if value.is_empty() {
+        format!("//@ {}:", key)
+    } else {
+        format!("//@ {}: {}", key, escape_annotation(value, false))
+    }
+}
+
+/// Split the annotation lines off the front of `data`
+///
+/// Returns the metadata they hold and the rest of the input.
+pub(crate) fn split_annotations(data: &str) -> (BTreeMap<String, String>, &str) {
+    let mut metadata = BTreeMap::new();
+    let mut rest = data;
+    for (offset, line, _) in lines(data) {
+        let Some((key, value)) = parse_annotation(line) else {
+            rest = &data[offset..];
+            break;
+        };
+        metadata.insert(key.into_owned(), value.into_owned());
+        rest = "";
+    }
+    (metadata, rest)
 }
 
 /// Parse a bookmark from a line
+///
+/// An optional fourth field holds comma-separated tags.
 /// # Examples
 ///
 /// ```
@@ -23,16 +587,42 @@ pub fn split_pipe(line: &str) -> Vec<&str> {
 /// assert_eq!(bookmark.name, "Rust");
 /// assert_eq!(bookmark.description, "Systems programming language");
 /// assert_eq!(bookmark.url, "https://www.rust-lang.org/");
+/// assert!(bookmark.tags.is_empty());
+///
+/// let bookmark = parser::parse_bookmark("Rust|Rust|https://www.rust-lang.org/|lang, systems").unwrap();
+/// assert_eq!(bookmark.tags, vec!["lang", "systems"]);
 /// ```
-pub fn parse_bookmark(line: &str) -> Result<Bookmark, &'static str> {
-    let parts = split_pipe(line);
-    if parts.len() != 3 {
-        return Err("bookmark has wrong number of parts");
+pub fn parse_bookmark(line: &str) -> Result<Bookmark, ParseError> {
+    parse_bookmark_ref(line).map(BookmarkRef::into_owned)
+}
+
+/// Parse a bookmark from a line without copying its fields
+///
+/// Fields only allocate when they contain escape sequences.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// use std::borrow::Cow;
+/// let line = "Rust|Systems programming language|https://www.rust-lang.org/";
+/// let bookmark = parser::parse_bookmark_ref(line).unwrap();
+/// assert!(matches!(bookmark.name, Cow::Borrowed("Rust")));
+/// ```
+pub fn parse_bookmark_ref(line: &str) -> Result<BookmarkRef<'_>, ParseError> {
+    let (found, [name, description, url, tags]) = fields(line);
+    if found != 3 && found != 4 {
+        return Err(ParseError::Bookmark {
+            location: Location::of_line(1, 0, line),
+            expected: 3..=4,
+            found,
+        });
     }
-    Ok(Bookmark {
-        name: parts[0].trim().to_string(),
-        description: parts[1].trim().to_string(),
-        url: parts[2].trim().to_string(),
+    Ok(BookmarkRef {
+        name: field(name),
+        description: field(description),
+        url: field(url),
+        tags: split_tags(tags),
+        metadata: BTreeMap::new(),
     })
 }
 
@@ -46,17 +636,218 @@ pub fn parse_bookmark(line: &str) -> Result<Bookmark, &'static str> {
 /// assert_eq!(header.name, "Programming Languages");
 /// assert_eq!(header.icon, Some("👨‍💻".to_string()));
 /// ```
-pub fn parse_header(line: &str) -> Result<Header, &'static str> {
-    let parts = split_pipe(line);
-    if parts.len() != 1 && parts.len() != 2 {
-        return Err("header has wrong number of parts");
+pub fn parse_header(line: &str) -> Result<Header, ParseError> {
+    parse_header_ref(line).map(HeaderRef::into_owned)
+}
+
+/// Parse a header from a line without copying its fields
+pub fn parse_header_ref(line: &str) -> Result<HeaderRef<'_>, ParseError> {
+    let (found, [name, icon]) = fields(line);
+    if found != 1 && found != 2 {
+        return Err(ParseError::Header {
+            location: Location::of_line(1, 0, line),
+            expected: 1..=2,
+            found,
+        });
     }
-    Ok(Header {
-        name: parts[0].trim().to_string(),
-        icon: parts.get(1).map(|i| i.trim().to_string()),
+    Ok(HeaderRef {
+        name: field(name),
+        icon: (found == 2).then(|| field(icon)),
+        metadata: BTreeMap::new(),
     })
 }
 
+/// Split a line into its first `N` raw fields without allocating
+///
+/// Returns the total number of fields along with the first `N`; missing
+/// fields are left empty.
+fn fields<const N: usize>(line: &str) -> (usize, [&str; N]) {
+    let mut parts = [""; N];
+    let mut count = 0;
+    for part in split_raw(line, '|') {
+        if let Some(slot) = parts.get_mut(count) {
+            *slot = part;
+        }
+        count += 1;
+    }
+    (count, parts)
+}
+
+/// Trim and unescape a raw field
+fn field(raw: &str) -> Cow<'_, str> {
+    unescape(raw.trim())
+}
+
+/// Iterate over the lines of `data`
+///
+/// Yields the byte offset each line starts at, the line itself and its line
+/// ending (`"\n"`, `"\r\n"` or `""` for a final line without one). Line
+/// endings are recognised the same way as [`str::lines`].
+pub(crate) fn lines(data: &str) -> impl Iterator<Item = (usize, &str, &str)> {
+    data.split_inclusive('\n').scan(0, |offset, raw| {
+        let start = *offset;
+        *offset += raw.len();
+        let content = match raw.strip_suffix('\n') {
+            Some(line) => line.strip_suffix('\r').unwrap_or(line),
+            None => raw,
+        };
+        Some((start, content, &raw[content.len()..]))
+    })
+}
+
+/// What a single line of an SBM file holds
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum LineKind<'a> {
+    /// An empty or whitespace-only line
+    Blank,
+    /// A `//` comment
+    Comment,
+    /// A `//@ key: value` annotation on the entry below it
+    Annotation {
+        key: Cow<'a, str>,
+        value: Cow<'a, str>,
+    },
+    /// A category header, holding how many `#`s it starts with and the text
+    /// after them
+    Header { depth: usize, text: &'a str },
+    /// Anything else is a bookmark
+    Bookmark(&'a str),
+}
+
+/// Work out what a line holds without parsing its fields
+pub(crate) fn classify(line: &str) -> LineKind<'_> {
+    if line.starts_with("//") {
+        match parse_annotation(line) {
+            Some((key, value)) => LineKind::Annotation { key, value },
+            None => LineKind::Comment,
+        }
+    } else if line.trim().is_empty() {
+        LineKind::Blank
+    } else if line.starts_with('#') {
+        let text = line.trim_start_matches('#');
+        LineKind::Header {
+            depth: line.len() - text.len(),
+            text: text.trim(),
+        }
+    } else {
+        LineKind::Bookmark(line)
+    }
+}
+
+/// Tells subcategory headers from `##` headers in files written before
+/// subcategories existed
+///
+/// A header with several `#`s is a subcategory only if a header with fewer
+/// `#`s came before it. Otherwise it has no parent, and is read as a
+/// top-level category whose name keeps all but the first `#`, the way such
+/// a header has always been read. Since its `#`s are part of its name, it is
+/// no parent to the headers after it either.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Depths {
+    shallowest: usize,
+}
+
+impl Depths {
+    pub fn new() -> Depths {
+        Depths {
+            shallowest: usize::MAX,
+        }
+    }
+
+    /// Place the header on `line`, classified as `depth` `#`s followed by
+    /// `text`
+    ///
+    /// Returns the depth to nest it at, the text to parse its fields from,
+    /// and whether it is a subcategory with no parent.
+    pub fn place<'a>(
+        &mut self,
+        line: &'a str,
+        depth: usize,
+        text: &'a str,
+    ) -> (usize, &'a str, bool) {
+        if depth > 1 && self.shallowest >= depth {
+            return (1, line[1..].trim(), true);
+        }
+        self.shallowest = self.shallowest.min(depth);
+        (depth, text, false)
+    }
+}
+
+/// Builds the category tree from headers in file order
+///
+/// A header closes every open category at its depth or deeper, and becomes a
+/// child of the nearest shallower one. Skipped levels are not an error: a
+/// `###` header straight after a `#` one is still its child.
+#[derive(Debug)]
+pub(crate) struct Nesting<C> {
+    roots: Vec<C>,
+    open: Vec<(usize, C)>,
+    adopt: fn(&mut C, C),
+}
+
+impl<C> Nesting<C> {
+    /// `adopt` adds a finished category to its parent's children
+    pub fn new(adopt: fn(&mut C, C)) -> Nesting<C> {
+        Nesting {
+            roots: Vec::new(),
+            open: Vec::new(),
+            adopt,
+        }
+    }
+
+    /// Start a category with a header of `depth` `#`s
+    pub fn open(&mut self, depth: usize, category: C) {
+        self.close(depth);
+        self.open.push((depth, category));
+    }
+
+    /// Start the implicit category for bookmarks before the first header,
+    /// which any header closes
+    pub fn open_implicit(&mut self, category: C) {
+        self.open(usize::MAX, category);
+    }
+
+    /// The category bookmarks currently go into
+    pub fn current(&mut self) -> Option<&mut C> {
+        self.open.last_mut().map(|(_, c)| c)
+    }
+
+    /// Take the next top-level category that is complete
+    pub fn take_finished(&mut self) -> Option<C> {
+        (!self.roots.is_empty()).then(|| self.roots.remove(0))
+    }
+
+    /// Close every open category and return the top-level ones
+    pub fn finish(&mut self) -> Vec<C> {
+        self.close(0);
+        std::mem::take(&mut self.roots)
+    }
+
+    fn close(&mut self, depth: usize) {
+        while self.open.last().is_some_and(|(d, _)| *d >= depth) {
+            let (_, category) = self.open.pop().unwrap();
+            match self.open.last_mut() {
+                Some((_, parent)) => (self.adopt)(parent, category),
+                None => self.roots.push(category),
+            }
+        }
+    }
+}
+
+/// The warning for a subcategory header on line `number` that has no parent
+pub(crate) fn orphan_subcategory(number: usize, offset: usize, line: &str) -> ParseError {
+    ParseError::OrphanSubcategory {
+        location: Location::of_line(number, offset, line),
+    }
+}
+
+/// The error for a bookmark on line `number` that has no category
+pub(crate) fn orphan(number: usize, offset: usize, line: &str) -> ParseError {
+    ParseError::OrphanBookmark {
+        location: Location::of_line(number, offset, line),
+    }
+}
+
 /// Parse categories from a string
 /// # Examples
 ///
@@ -79,39 +870,265 @@ pub fn parse_header(line: &str) -> Result<Header, &'static str> {
 /// assert_eq!(categories[1].header.icon, Some("🌐".to_string()));
 /// assert_eq!(categories[1].bookmarks.len(), 2);
 /// ```
-pub fn parse_categories(data: &str) -> Result<Vec<Category>, &'static str> {
-    let mut categories = Vec::new();
-    let mut current: Option<Category> = None;
+///
+/// Bookmarks before the first header are rejected; use
+/// [`parse_categories_with`] to collect them instead.
+pub fn parse_categories(data: &str) -> Result<Vec<Category>, ParseError> {
+    parse_categories_with(data, &ParseOptions::default())
+}
+
+/// Parse categories from a string with the given options
+///
+/// Warnings, such as for bookmarks collected into [`UNCATEGORIZED`], are
+/// dropped; [`parse_categories_with_warnings`] returns them.
+/// # Examples
+///
+/// ```
+/// use sbm::parser::{self, ParseError, ParseOptions};
+/// let data = "Rust|The Rust Programming Language|https://www.rust-lang.org/\n#Web\n";
+/// let err = parser::parse_categories_with(data, &ParseOptions::strict()).unwrap_err();
+/// assert!(matches!(err, ParseError::OrphanBookmark { .. }));
+///
+/// let categories = parser::parse_categories_with(data, &ParseOptions::lenient()).unwrap();
+/// assert_eq!(categories[0].header.name, parser::UNCATEGORIZED);
+/// assert_eq!(categories[0].bookmarks[0].name, "Rust");
+/// assert_eq!(categories[1].header.name, "Web");
+/// ```
+pub fn parse_categories_with(
+    data: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Category>, ParseError> {
+    parse_categories_with_warnings(data, options).map(|(categories, _)| categories)
+}
+
+/// Parse categories from a string with the given options, along with every
+/// warning, in file order
+/// # Examples
+///
+/// ```
+/// use sbm::parser::{self, ParseError, ParseOptions};
+/// let data = "Rust|The Rust Programming Language|https://www.rust-lang.org/\n#Web\n";
+/// let (categories, warnings) =
+///     parser::parse_categories_with_warnings(data, &ParseOptions::lenient()).unwrap();
+/// assert_eq!(categories[0].header.name, parser::UNCATEGORIZED);
+/// assert!(matches!(warnings[..], [ParseError::OrphanBookmark { .. }]));
+/// assert_eq!(warnings[0].location().line, 1);
+/// ```
+pub fn parse_categories_with_warnings(
+    data: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Category>, Vec<ParseError>), ParseError> {
+    let mut warnings = Vec::new();
+    let categories = parse_lines(data, options, |severity, error| match severity {
+        Severity::Warning => {
+            warnings.push(error);
+            Ok(())
+        }
+        Severity::Error => Err(error),
+    })?;
+    let categories = categories
+        .into_iter()
+        .map(CategoryRef::into_owned)
+        .collect();
+    Ok((categories, warnings))
+}
+
+/// Parse categories from a string without copying any fields
+///
+/// The result borrows from `data`; fields only allocate when they contain
+/// escape sequences.
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// let data = "#Languages\nRust|Systems programming language|https://www.rust-lang.org/\n";
+/// let categories = parser::parse_categories_ref(data).unwrap();
+/// assert_eq!(categories[0].bookmarks[0].url, "https://www.rust-lang.org/");
+/// assert_eq!(categories, parser::parse_categories(data).unwrap());
+/// ```
+pub fn parse_categories_ref(data: &str) -> Result<Vec<CategoryRef<'_>>, ParseError> {
+    parse_categories_ref_with(data, &ParseOptions::default())
+}
 
-    for line in data.lines() {
-        if line.starts_with("//") || line.trim().is_empty() {
-            continue;
+/// Parse categories from a string without copying any fields, with the given options
+pub fn parse_categories_ref_with<'a>(
+    data: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<CategoryRef<'a>>, ParseError> {
+    parse_lines(data, options, |severity, error| match severity {
+        Severity::Warning => Ok(()),
+        Severity::Error => Err(error),
+    })
+}
+
+/// Parse a string holding exactly one category
+/// # Examples
+///
+/// ```
+/// use sbm::parser;
+/// let category = parser::parse_category("#Languages\nRust|Rust|https://www.rust-lang.org/").unwrap();
+/// assert_eq!(category.header.name, "Languages");
+/// assert!(parser::parse_category("#A\n##A1").is_ok());
+/// assert!(parser::parse_category("#A\n#B").is_err());
+/// ```
+pub fn parse_category(data: &str) -> Result<Category, ParseError> {
+    let mut categories = parse_categories(data)?;
+    if categories.len() == 1 {
+        return Ok(categories.remove(0));
+    }
+    // the second top-level header is the first one no deeper than the first
+    let mut first_depth = None;
+    let second_header = lines(data).enumerate().find(|(_, (_, line, _))| {
+        let LineKind::Header { depth, .. } = classify(line) else {
+            return false;
+        };
+        match first_depth {
+            None => {
+                first_depth = Some(depth);
+                false
+            }
+            Some(first) => depth <= first,
         }
+    });
+    let (index, (offset, line, _)) = second_header
+        .or_else(|| lines(data).enumerate().next())
+        .unwrap_or((0, (0, "", "")));
+    Err(ParseError::Category {
+        location: Location::of_line(index + 1, offset, line),
+        found: categories.len(),
+    })
+}
 
-        if let Some(stripped) = line.strip_prefix('#') {
-            if let Some(c) = current.take() {
-                categories.push(c);
+/// Parse as much of a string as possible, collecting every problem found
+///
+/// Malformed bookmark lines are skipped. A header with too many fields is
+/// kept using its first two fields, so the bookmarks after it still land in
+/// the right category. Bookmarks before the first header are always collected
+/// into [`UNCATEGORIZED`]; this is a warning if `options` allows it and an
+/// error otherwise.
+/// # Examples
+///
+/// ```
+/// use sbm::parser::{self, ParseOptions, Severity};
+/// let data = "#Languages\nRust|https://www.rust-lang.org/\nPython|Python|https://www.python.org/\n#Web|🌐|extra\n";
+/// let (sbm, diagnostics) = parser::parse_recovering(data, &ParseOptions::default());
+/// assert_eq!(sbm.0.len(), 2);
+/// assert_eq!(sbm.0[0].bookmarks[0].name, "Python");
+/// assert_eq!(sbm.0[1].header.name, "Web");
+/// assert_eq!(diagnostics.len(), 2);
+/// assert_eq!(diagnostics[0].line(), 2);
+/// assert_eq!(diagnostics[1].severity, Severity::Error);
+/// ```
+pub fn parse_recovering(data: &str, options: &ParseOptions) -> (Sbm, Vec<Diagnostic>) {
+    let mut diagnostics = Vec::new();
+    let result = parse_lines(data, options, |severity, error| {
+        diagnostics.push(Diagnostic::new(severity, error));
+        Ok(())
+    });
+    // the callback never fails, so neither does parsing
+    let categories = result.unwrap_or_default();
+    (
+        Sbm(categories
+            .into_iter()
+            .map(CategoryRef::into_owned)
+            .collect()),
+        diagnostics,
+    )
+}
+
+/// Parse every line of `data`, handing problems to `report`
+///
+/// Parsing stops as soon as `report` returns an error; otherwise it recovers
+/// as described in [`parse_recovering`].
+fn parse_lines<'a, F>(
+    data: &'a str,
+    options: &ParseOptions,
+    mut report: F,
+) -> Result<Vec<CategoryRef<'a>>, ParseError>
+where
+    F: FnMut(Severity, ParseError) -> Result<(), ParseError>,
+{
+    let mut nesting =
+        Nesting::new(|parent: &mut CategoryRef<'a>, child| parent.children.push(child));
+    let mut depths = Depths::new();
+    // annotations waiting for the entry they belong to
+    let mut metadata = BTreeMap::new();
+    let mut orphans = false;
+
+    for (index, (offset, line, _)) in lines(data).enumerate() {
+        let number = index + 1;
+        match classify(line) {
+            LineKind::Blank | LineKind::Comment => metadata.clear(),
+            LineKind::Annotation { key, value } => {
+                metadata.insert(key, value);
+            }
+            LineKind::Header { depth, text } => {
+                let (depth, text, orphaned) = depths.place(line, depth, text);
+                if orphaned {
+                    report(Severity::Warning, orphan_subcategory(number, offset, line))?;
+                }
+                let mut header = match parse_header_ref(text) {
+                    Ok(header) => header,
+                    Err(e) => {
+                        report(Severity::Error, e.at(number, offset, line))?;
+                        let (_, [name, icon]) = fields(text);
+                        HeaderRef {
+                            name: field(name),
+                            icon: Some(field(icon)),
+                            metadata: BTreeMap::new(),
+                        }
+                    }
+                };
+                header.metadata = std::mem::take(&mut metadata);
+                nesting.open(depth, CategoryRef::new(header));
+            }
+            LineKind::Bookmark(text) => {
+                let mut bookmark = match parse_bookmark_ref(text) {
+                    Ok(bookmark) => bookmark,
+                    Err(e) => {
+                        report(Severity::Error, e.at(number, offset, line))?;
+                        metadata.clear();
+                        continue;
+                    }
+                };
+                bookmark.metadata = std::mem::take(&mut metadata);
+                if let Err(e) = options.check_url(&bookmark.url, number, offset, line) {
+                    report(Severity::Error, e)?;
+                }
+                if nesting.current().is_none() {
+                    let severity = match options.orphans {
+                        Orphans::Reject => Severity::Error,
+                        Orphans::Collect => Severity::Warning,
+                    };
+                    report(severity, orphan(number, offset, line))?;
+                    nesting.open_implicit(CategoryRef::new(HeaderRef::new(UNCATEGORIZED, None)));
+                    orphans = true;
+                }
+                nesting.current().unwrap().bookmarks.push(bookmark);
             }
-            let header = parse_header(stripped.trim())?;
-            current = Some(Category {
-                header,
-                bookmarks: Vec::new(),
-            });
-        } else if let Some(c) = current.as_mut() {
-            let bookmark = parse_bookmark(line)?;
-            c.bookmarks.push(bookmark);
         }
     }
-    if let Some(c) = current.take() {
-        categories.push(c);
-    }
 
+    let mut categories = nesting.finish();
+    if orphans {
+        if let Some(i) = categories[1..]
+            .iter()
+            .position(|c| c.header.name == UNCATEGORIZED)
+        {
+            let collected = categories.remove(0).bookmarks;
+            categories[i].bookmarks.splice(0..0, collected);
+        }
+    }
     Ok(categories)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
+
+    /// Any single line of text, biased towards characters with special meaning
+    const FIELD: &str = r"([#/|\\ ]|[^\r\n])*";
 
     #[test]
     fn test_parse_bookmark() {
@@ -155,6 +1172,163 @@ CSS|Cascading Style Sheets|https://developer.mozilla.org/en-US/docs/Web/CSS
         assert_eq!(categories[1].bookmarks.len(), 2);
     }
 
+    #[test]
+    fn test_validate_urls() {
+        let data = "#Web\nMDN|Docs|https://developer.mozilla.org/\nEmpty|Nothing here|\nTypo|Oops|htps:/example.com\n";
+        assert!(parse_categories(data).is_ok());
+        let options = ParseOptions {
+            validate_urls: true,
+            ..ParseOptions::default()
+        };
+        let err = parse_categories_with(data, &options).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Url {
+                location: Location {
+                    line: 3,
+                    column: 20,
+                    span: 64..64,
+                    text: "Empty|Nothing here|".to_string(),
+                },
+                error: UrlError::Empty,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 20: bookmark has an invalid URL: URL is empty: `Empty|Nothing here|`"
+        );
+
+        // the column and span are those of the URL field, counted in characters
+        let padded = "#Web\nTypo 🦀 | Oops \\| again |  htps:/example.com | tag\n";
+        let err = parse_categories_with(padded, &options).unwrap_err();
+        let location = err.location();
+        assert_eq!(location.column, 27);
+        assert_eq!(&padded[location.span.clone()], "htps:/example.com");
+
+        // recovering keeps the bookmarks, reporting each URL
+        let (sbm, diagnostics) = parse_recovering(data, &options);
+        assert_eq!(sbm.0[0].bookmarks.len(), 3);
+        let lines: Vec<usize> = diagnostics.iter().map(Diagnostic::line).collect();
+        assert_eq!(lines, vec![3, 4]);
+        assert!(crate::document::Document::parse_with(data, &options).is_err());
+    }
+
+    #[test]
+    fn test_escaped_fields() {
+        let line = r"\#1 \\ tool|pipes \| more|https://example.com/?a=1\|2";
+        let bookmark = parse_bookmark(line).unwrap();
+        assert_eq!(bookmark.name, r"#1 \ tool");
+        assert_eq!(bookmark.description, "pipes | more");
+        assert_eq!(bookmark.url, "https://example.com/?a=1|2");
+        assert_eq!(bookmark.to_string(), line);
+
+        let header = parse_header(r"a\|b|c\\").unwrap();
+        assert_eq!(header, Header::new("a|b", Some(r"c\")));
+        assert_eq!(header.to_string(), r"#a\|b|c\\");
+
+        let data = "#\\#Hash\n\\//slashes|x|y\n";
+        let categories = parse_categories(data).unwrap();
+        assert_eq!(categories[0].header.name, "#Hash");
+        assert_eq!(categories[0].bookmarks[0].name, "//slashes");
+    }
+
+    proptest! {
+        /// Fields come back as written, except for the whitespace around
+        /// them, which isn't significant (see [`escape`])
+        #[test]
+        fn prop_bookmark_roundtrip(name in FIELD, description in FIELD, url in FIELD) {
+            let data = Bookmark::new(&name, &description, &url).to_string();
+            let bookmark = Bookmark::new(name.trim(), description.trim(), url.trim());
+            prop_assert_eq!(parse_bookmark(&data).unwrap(), bookmark.clone());
+            let categories = parse_categories(&format!("#A\n{}", data)).unwrap();
+            prop_assert_eq!(&categories[0].bookmarks, &vec![bookmark]);
+        }
+
+        /// Like [`prop_bookmark_roundtrip`], for headers
+        #[test]
+        fn prop_header_roundtrip(name in FIELD, icon in proptest::option::of(FIELD)) {
+            let data = Header::new(&name, icon.as_deref()).to_string();
+            let header = Header::new(name.trim(), icon.as_deref().map(str::trim));
+            prop_assert_eq!(classify(&data), LineKind::Header { depth: 1, text: data[1..].trim() });
+            prop_assert_eq!(parse_header(data[1..].trim()).unwrap(), header);
+        }
+    }
+
+    #[test]
+    fn test_nested_categories() {
+        let data = "#Work|💼\nJira|Issues|https://jira.example.com/\n##Infra\n###Monitoring\nGrafana|Dashboards|https://grafana.example.com/\n##Docs\n#Home\n###Skipped\n";
+        let sbm = Sbm(parse_categories(data).unwrap());
+        let outline: Vec<(usize, &str, usize)> = sbm
+            .walk()
+            .map(|(depth, c)| (depth, c.header.name.as_str(), c.bookmarks.len()))
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                (1, "Work", 1),
+                (2, "Infra", 0),
+                (3, "Monitoring", 1),
+                (2, "Docs", 0),
+                (1, "Home", 0),
+                (2, "Skipped", 0),
+            ]
+        );
+
+        // a subcategory before any top-level header is top-level itself
+        let categories = parse_categories("##A\n#B\n").unwrap();
+        assert_eq!(categories.len(), 2);
+        assert_eq!(categories[0].header.name, "#A");
+
+        // and so is one after bookmarks with no header
+        let categories = parse_categories_with("a|b|c\n##A\n", &ParseOptions::lenient()).unwrap();
+        assert_eq!(categories[0].header.name, UNCATEGORIZED);
+        assert!(categories[0].children.is_empty());
+        assert_eq!(categories[1].header.name, "#A");
+
+        let err = parse_category("#A\n##A1\n#B\n").unwrap_err();
+        assert!(matches!(err, ParseError::Category { found: 2, .. }));
+        assert_eq!(err.location().line, 3);
+    }
+
+    #[test]
+    fn test_flat_file_with_hashes() {
+        // a flat file from before subcategories, with `#` in category names
+        let data = "##x\nA|b|c\n##y|🌐\n#z\n##w\nB|b|c\n";
+        let sbm = Sbm(parse_categories(data).unwrap());
+        let outline: Vec<(&str, usize)> = sbm
+            .walk()
+            .map(|(depth, c)| (c.header.name.as_str(), depth))
+            .collect();
+        assert_eq!(outline, vec![("#x", 1), ("#y", 1), ("z", 1), ("w", 2)]);
+        assert_eq!(sbm.0[0].bookmarks.len(), 1);
+        assert_eq!(sbm.0[1].header.icon.as_deref(), Some("🌐"));
+
+        let (recovered, diagnostics) = parse_recovering(data, &ParseOptions::default());
+        assert_eq!(recovered, sbm);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.line(), d.severity))
+                .collect::<Vec<_>>(),
+            vec![(1, Severity::Warning), (3, Severity::Warning)]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "warning: line 1, column 1: subcategory has no parent, read as a top-level category"
+        );
+
+        // written back, the names are escaped and read the same
+        assert_eq!(sbm.to_string().parse::<Sbm>().unwrap(), sbm);
+
+        // a header with no parent is no parent to deeper ones either
+        let sbm = Sbm(parse_categories("##a\n###b\n").unwrap());
+        let outline: Vec<(&str, usize)> = sbm
+            .walk()
+            .map(|(depth, c)| (c.header.name.as_str(), depth))
+            .collect();
+        assert_eq!(outline, vec![("#a", 1), ("##b", 1)]);
+    }
+
     #[test]
     fn test_bad_bookmark() {
         let line = "Rust|Systems programming language";
@@ -168,4 +1342,98 @@ CSS|Cascading Style Sheets|https://developer.mozilla.org/en-US/docs/Web/CSS
         let header = parse_header(line);
         assert!(header.is_err());
     }
+
+    #[test]
+    fn test_orphan_bookmarks() {
+        let data = "// links\nRust|Rust|https://www.rust-lang.org/\nGo|Go|https://go.dev/\n#Web|🌐\nMDN|Docs|https://developer.mozilla.org/\n";
+        let err = parse_categories(data).unwrap_err();
+        assert!(matches!(err, ParseError::OrphanBookmark { .. }));
+        assert_eq!(err.location().line, 2);
+
+        let categories = parse_categories_with(data, &ParseOptions::lenient()).unwrap();
+        assert_eq!(categories.len(), 2);
+        assert_eq!(categories[0].header, Header::new(UNCATEGORIZED, None));
+        assert_eq!(categories[0].bookmarks.len(), 2);
+        assert_eq!(categories[1].bookmarks.len(), 1);
+
+        let (_, warnings) = parse_categories_with_warnings(data, &ParseOptions::lenient()).unwrap();
+        // one for the first bookmark without a category
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(warnings[0], ParseError::OrphanBookmark { .. }));
+        assert_eq!(warnings[0].location().line, 2);
+
+        // an explicit category of the same name takes them in
+        let data = "Rust|Rust|https://www.rust-lang.org/\n#Web\n#Uncategorized|📥\nGo|Go|https://go.dev/\n##Old\n";
+        let categories = parse_categories_with(data, &ParseOptions::lenient()).unwrap();
+        let uncategorized = &categories[1];
+        assert_eq!(categories.len(), 2);
+        assert_eq!(uncategorized.header, Header::new(UNCATEGORIZED, Some("📥")));
+        assert_eq!(uncategorized.bookmarks[0].name, "Rust");
+        assert_eq!(uncategorized.bookmarks[1].name, "Go");
+        assert_eq!(uncategorized.children.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_recovering() {
+        let data = "Orphan|x|https://example.com/\n#A|1|2\nbad\nRust|Rust|https://www.rust-lang.org/\n#B\na|b|c|d|e\n";
+        let (sbm, diagnostics) = parse_recovering(data, &ParseOptions::default());
+        assert_eq!(
+            sbm.0
+                .iter()
+                .map(|c| (c.header.name.as_str(), c.bookmarks.len()))
+                .collect::<Vec<_>>(),
+            vec![(UNCATEGORIZED, 1), ("A", 1), ("B", 0)]
+        );
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|d| (d.line(), d.severity))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Severity::Error),
+                (2, Severity::Error),
+                (3, Severity::Error),
+                (6, Severity::Error)
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].to_string(),
+            "error: line 3, column 1: bookmark has wrong number of parts (expected 3 to 4, found 1)"
+        );
+
+        let (_, diagnostics) = parse_recovering(data, &ParseOptions::lenient());
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+
+        let data = "#A|🌐\n// comment\nRust|Rust|https://www.rust-lang.org/\n\n#B\n";
+        let (sbm, diagnostics) = parse_recovering(data, &ParseOptions::default());
+        assert!(diagnostics.is_empty());
+        assert_eq!(sbm.0, parse_categories(data).unwrap());
+    }
+
+    #[test]
+    fn test_error_location() {
+        let data = "#Programming Languages\r\nRust|https://www.rust-lang.org/\r\n";
+        let err = parse_categories(data).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::Bookmark {
+                location: Location {
+                    line: 2,
+                    column: 1,
+                    span: 24..55,
+                    text: "Rust|https://www.rust-lang.org/".to_string(),
+                },
+                expected: 3..=4,
+                found: 2,
+            }
+        );
+        assert_eq!(&data[err.location().span.clone()], err.location().text);
+
+        let err = parse_categories("\n#Web|🌐|Extra\n").unwrap_err();
+        assert_eq!(err.location().line, 2);
+        assert_eq!(
+            err.to_string(),
+            "line 2, column 1: header has wrong number of parts (expected 1 to 2, found 3): `#Web|🌐|Extra`"
+        );
+    }
 }
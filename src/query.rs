@@ -0,0 +1,542 @@
+//! Queries over bookmarks
+//!
+//! A [`Query`] is a condition on a bookmark and the categories it is filed
+//! under. Queries can be built in code, or parsed from a small query language:
+//!
+//! - a bare word or a `"quoted phrase"` matches the name, description, URL or
+//!   tags of a bookmark, ignoring case
+//! - `name:`, `desc:` and `url:` match a single field
+//! - `domain:example.com` matches URLs on that host or any of its subdomains
+//! - `category:Work` (or `in:Work`) matches bookmarks in a category of that
+//!   name, or in any of its subcategories
+//! - `tag:docs` matches bookmarks with that exact tag
+//! - `/regex/` in place of a word or phrase matches a regular expression, with
+//!   the `regex` feature
+//!
+//! Terms next to each other must all match. `OR` between terms matches
+//! either, `NOT` or a leading `-` negates a term, and parentheses group terms.
+//! `NOT` binds tighter than `AND`, which binds tighter than `OR`.
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::query::Query;
+//! use sbm::Sbm;
+//! let sbm: Sbm = "#Languages\nRust|The Rust Programming Language|https://www.rust-lang.org/\nPython|Python|https://www.python.org/\n#Web\nMDN|Web docs|https://developer.mozilla.org/"
+//!     .parse()
+//!     .unwrap();
+//! let query: Query = "category:languages -python OR domain:mozilla.org".parse().unwrap();
+//! let names: Vec<&str> = sbm.query(&query).map(|(_, b)| b.name.as_str()).collect();
+//! assert_eq!(names, vec!["Rust", "MDN"]);
+//! ```
+
+use crate::url::{self, Url};
+use crate::{Bookmark, Category};
+use std::str::FromStr;
+
+/// A bookmark field a text condition can be limited to
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Field {
+    Name,
+    Description,
+    Url,
+}
+
+/// How a text condition matches a field
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// The field contains the text, ignoring case
+    Contains(String),
+    /// The field matches the regular expression
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    /// Match fields containing `text`, ignoring case
+    pub fn contains(text: &str) -> Matcher {
+        Matcher::Contains(text.to_lowercase())
+    }
+
+    pub fn is_match(&self, field: &str) -> bool {
+        match self {
+            Matcher::Contains(needle) => field.to_lowercase().contains(needle),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.is_match(field),
+        }
+    }
+}
+
+impl PartialEq for Matcher {
+    fn eq(&self, other: &Matcher) -> bool {
+        match (self, other) {
+            (Matcher::Contains(a), Matcher::Contains(b)) => a == b,
+            #[cfg(feature = "regex")]
+            (Matcher::Regex(a), Matcher::Regex(b)) => a.as_str() == b.as_str(),
+            #[cfg(feature = "regex")]
+            _ => false,
+        }
+    }
+}
+
+/// A condition on a bookmark
+#[derive(Debug, PartialEq, Clone)]
+pub enum Query {
+    /// Text in the given field, or in the name, description, URL or tags if
+    /// there is none
+    Text(Option<Field>, Matcher),
+    /// The URL's host is this domain or one of its subdomains, ignoring case
+    /// and whether either is written in punycode
+    Domain(String),
+    /// The bookmark's category, or one of that category's parents, has this
+    /// name, ignoring case
+    Category(String),
+    /// The bookmark has this tag
+    Tag(String),
+    /// Every query matches; an empty list matches every bookmark
+    And(Vec<Query>),
+    /// At least one query matches
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    /// A query that matches every bookmark
+    pub fn all() -> Query {
+        Query::And(Vec::new())
+    }
+
+    /// Parse a query written in the query language
+    pub fn parse(text: &str) -> Result<Query, QueryError> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Ok(Query::all());
+        }
+        let mut parser = Parser {
+            tokens: &tokens,
+            next: 0,
+            end: text.chars().count(),
+        };
+        let query = parser.or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some((position, _)) => Err(QueryError::new(*position, "unmatched `)`")),
+        }
+    }
+
+    /// Whether a bookmark matches
+    ///
+    /// `path` holds the categories the bookmark is filed under, from the top
+    /// level down to its own category.
+    pub fn matches(&self, path: &[&Category], bookmark: &Bookmark) -> bool {
+        match self {
+            Query::Text(None, matcher) => [&bookmark.name, &bookmark.description, &bookmark.url]
+                .into_iter()
+                .chain(&bookmark.tags)
+                .any(|field| matcher.is_match(field)),
+            Query::Text(Some(field), matcher) => matcher.is_match(match field {
+                Field::Name => &bookmark.name,
+                Field::Description => &bookmark.description,
+                Field::Url => &bookmark.url,
+            }),
+            Query::Domain(domain) => host(&bookmark.url).is_some_and(|host| {
+                let host = url::to_ascii(host);
+                let domain = url::to_ascii(domain);
+                host == domain
+                    || host
+                        .strip_suffix(&domain)
+                        .is_some_and(|sub| sub.ends_with('.'))
+            }),
+            Query::Category(name) => path
+                .iter()
+                .any(|c| c.header.name.to_lowercase() == name.to_lowercase()),
+            Query::Tag(tag) => bookmark.has_tag(tag),
+            Query::And(queries) => queries.iter().all(|q| q.matches(path, bookmark)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches(path, bookmark)),
+            Query::Not(query) => !query.matches(path, bookmark),
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Query, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+/// The host of a URL, without any user info or port
+fn host(url: &str) -> Option<&str> {
+    Url::split(url)?.host.filter(|host| !host.is_empty())
+}
+
+/// Error from [`Query::parse`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct QueryError {
+    /// 1-based column the error was found at, counted in characters
+    pub column: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(position: usize, message: &str) -> QueryError {
+        QueryError {
+            column: position + 1,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Term(Query),
+}
+
+/// Split a query into tokens, each with the character position it starts at
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => {
+                i += 1;
+                Token::Open
+            }
+            ')' => {
+                i += 1;
+                Token::Close
+            }
+            '-' if chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) => {
+                i += 1;
+                Token::Not
+            }
+            _ => {
+                let word = |j: usize| {
+                    chars[j..]
+                        .iter()
+                        .take_while(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+                        .collect::<String>()
+                };
+                match word(i).as_str() {
+                    keyword @ ("AND" | "OR" | "NOT") => {
+                        i += keyword.len();
+                        match keyword {
+                            "AND" => Token::And,
+                            "OR" => Token::Or,
+                            _ => Token::Not,
+                        }
+                    }
+                    _ => Token::Term(term(&chars, &mut i)?),
+                }
+            }
+        };
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+/// Read a term starting at `i`, leaving `i` just past it
+fn term(chars: &[char], i: &mut usize) -> Result<Query, QueryError> {
+    let prefix: String = chars[*i..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    if chars.get(*i + prefix.len()) == Some(&':') {
+        let field = match prefix.as_str() {
+            "name" => Some(Some(Field::Name)),
+            "desc" | "description" => Some(Some(Field::Description)),
+            "url" => Some(Some(Field::Url)),
+            "domain" | "category" | "in" | "tag" => Some(None),
+            _ => None,
+        };
+        if let Some(field) = field {
+            let start = *i;
+            *i += prefix.len() + 1;
+            let value = value(chars, i)?;
+            return match (prefix.as_str(), value) {
+                (_, Value::Text(text)) if text.is_empty() => Err(QueryError::new(
+                    start,
+                    &format!("`{}:` needs a value", prefix),
+                )),
+                ("domain", Value::Text(text)) => Ok(Query::Domain(text)),
+                ("category" | "in", Value::Text(text)) => Ok(Query::Category(text)),
+                ("tag", Value::Text(text)) => Ok(Query::Tag(text)),
+                (_, Value::Text(text)) => Ok(Query::Text(field, Matcher::contains(&text))),
+                #[cfg(feature = "regex")]
+                (_, Value::Regex(matcher)) if field.is_some() => Ok(Query::Text(field, matcher)),
+                #[cfg(feature = "regex")]
+                _ => Err(QueryError::new(
+                    start,
+                    &format!("`{}:` can't take a regex", prefix),
+                )),
+            };
+        }
+    }
+    Ok(match value(chars, i)? {
+        Value::Text(text) => Query::Text(None, Matcher::contains(&text)),
+        #[cfg(feature = "regex")]
+        Value::Regex(matcher) => Query::Text(None, matcher),
+    })
+}
+
+enum Value {
+    Text(String),
+    #[cfg(feature = "regex")]
+    Regex(Matcher),
+}
+
+/// Read a word, a quoted phrase or a regex starting at `i`
+fn value(chars: &[char], i: &mut usize) -> Result<Value, QueryError> {
+    let start = *i;
+    match chars.get(start) {
+        Some(&quote @ ('"' | '/')) => {
+            let mut text = String::new();
+            *i += 1;
+            loop {
+                match chars.get(*i) {
+                    None => {
+                        let what = if quote == '"' { "quote" } else { "regex" };
+                        return Err(QueryError::new(start, &format!("unterminated {}", what)));
+                    }
+                    Some(&c) if c == quote => break,
+                    // a backslash escapes the delimiter; regexes keep their own escapes
+                    Some('\\') if chars.get(*i + 1) == Some(&quote) => {
+                        text.push(quote);
+                        *i += 1;
+                    }
+                    Some('\\') if quote == '"' && chars.get(*i + 1) == Some(&'\\') => {
+                        text.push('\\');
+                        *i += 1;
+                    }
+                    Some(&c) => text.push(c),
+                }
+                *i += 1;
+            }
+            *i += 1;
+            match quote {
+                '"' => Ok(Value::Text(text)),
+                _ => regex(&text).map_err(|message| QueryError::new(start, &message)),
+            }
+        }
+        _ => {
+            let text: String = chars[start..]
+                .iter()
+                .take_while(|c| !c.is_whitespace() && !matches!(c, '(' | ')'))
+                .collect();
+            *i += text.chars().count();
+            Ok(Value::Text(text))
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+fn regex(pattern: &str) -> Result<Value, String> {
+    regex::Regex::new(pattern)
+        .map(|regex| Value::Regex(Matcher::Regex(regex)))
+        .map_err(|e| format!("invalid regex: {}", e))
+}
+
+#[cfg(not(feature = "regex"))]
+fn regex(_: &str) -> Result<Value, String> {
+    Err("regexes need the `regex` feature".to_string())
+}
+
+/// Recursive descent over the tokens, one method per precedence level
+struct Parser<'t> {
+    tokens: &'t [(usize, Token)],
+    next: usize,
+    /// Position just past the end of the query, for errors at the end
+    end: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self.peek().is_some_and(|(_, t)| *t == token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.and()?];
+        while self.eat(Token::Or) {
+            queries.push(self.and()?);
+        }
+        Ok(flatten(queries, Query::Or))
+    }
+
+    fn and(&mut self) -> Result<Query, QueryError> {
+        let mut queries = vec![self.not()?];
+        loop {
+            match self.peek() {
+                None | Some((_, Token::Or | Token::Close)) => break,
+                Some((_, Token::And)) => {
+                    self.next += 1;
+                    queries.push(self.not()?);
+                }
+                _ => queries.push(self.not()?),
+            }
+        }
+        Ok(flatten(queries, Query::And))
+    }
+
+    fn not(&mut self) -> Result<Query, QueryError> {
+        let Some((position, token)) = self.tokens.get(self.next) else {
+            return Err(QueryError::new(self.end, "expected a term"));
+        };
+        self.next += 1;
+        match token {
+            Token::Not => Ok(Query::Not(Box::new(self.not()?))),
+            Token::Open => {
+                let query = self.or()?;
+                if !self.eat(Token::Close) {
+                    return Err(QueryError::new(*position, "unmatched `(`"));
+                }
+                Ok(query)
+            }
+            Token::Term(query) => Ok(query.clone()),
+            Token::And | Token::Or | Token::Close => {
+                Err(QueryError::new(*position, "expected a term"))
+            }
+        }
+    }
+}
+
+/// Combine queries with `combine`, unless there is only one
+fn flatten(mut queries: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if queries.len() == 1 {
+        queries.remove(0)
+    } else {
+        combine(queries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sbm;
+
+    const DATA: &str = "#Work\nJira|Issue tracker|https://jira.example.com/browse|tracking\n##Infra\nGrafana|Dashboards|https://grafana.example.com:3000/|monitoring\n#Home\nRecipes|Cooking, mostly|https://example.org/recipes\nNews|Hacker News|https://news.ycombinator.com/\n";
+
+    fn names(query: &str) -> Vec<String> {
+        let sbm: Sbm = DATA.parse().unwrap();
+        let query = Query::parse(query).unwrap();
+        sbm.query(&query).map(|(_, b)| b.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = |t: &str| Query::Text(None, Matcher::contains(t));
+        assert_eq!(Query::parse("").unwrap(), Query::all());
+        assert_eq!(Query::parse("Rust").unwrap(), text("rust"));
+        assert_eq!(
+            Query::parse("a b OR NOT c AND (d OR -e)").unwrap(),
+            Query::Or(vec![
+                Query::And(vec![text("a"), text("b")]),
+                Query::And(vec![
+                    Query::Not(Box::new(text("c"))),
+                    Query::Or(vec![text("d"), Query::Not(Box::new(text("e")))]),
+                ]),
+            ])
+        );
+        assert_eq!(
+            Query::parse(r#"name:"hacker \"news\"" in:Work url:https://x tag:a,b"#).unwrap(),
+            Query::And(vec![
+                Query::Text(Some(Field::Name), Matcher::contains("hacker \"news\"")),
+                Query::Category("Work".to_string()),
+                Query::Text(Some(Field::Url), Matcher::contains("https://x")),
+                Query::Tag("a,b".to_string()),
+            ])
+        );
+        // an unknown prefix is just text
+        assert_eq!(
+            Query::parse("https://example.com").unwrap(),
+            text("https://example.com")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |query: &str| Query::parse(query).unwrap_err().to_string();
+        assert_eq!(error("(a b"), "column 1: unmatched `(`");
+        assert_eq!(error("a)"), "column 2: unmatched `)`");
+        assert_eq!(error("a OR"), "column 5: expected a term");
+        assert_eq!(error("OR a"), "column 1: expected a term");
+        assert_eq!(error("a AND"), "column 6: expected a term");
+        assert_eq!(error("()"), "column 2: expected a term");
+        assert_eq!(error("x \"open"), "column 3: unterminated quote");
+        assert_eq!(error("domain: x"), "column 1: `domain:` needs a value");
+        #[cfg(not(feature = "regex"))]
+        assert_eq!(error("/a+/"), "column 1: regexes need the `regex` feature");
+        #[cfg(feature = "regex")]
+        assert_eq!(error("tag:/a/"), "column 1: `tag:` can't take a regex");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert_eq!(names(""), vec!["Jira", "Grafana", "Recipes", "News"]);
+        assert_eq!(names("NEWS"), vec!["News"]);
+        assert_eq!(names("monitoring"), vec!["Grafana"]);
+        assert_eq!(names("desc:news"), vec!["News"]);
+        assert_eq!(names("name:news OR url:jira"), vec!["Jira", "News"]);
+        assert_eq!(names("domain:example.com"), vec!["Jira", "Grafana"]);
+        assert_eq!(names("domain:ample.com"), Vec::<String>::new());
+        assert_eq!(names("domain:GRAFANA.example.com"), vec!["Grafana"]);
+        assert_eq!(names("category:work"), vec!["Jira", "Grafana"]);
+        assert_eq!(names("in:infra"), vec!["Grafana"]);
+        assert_eq!(names("-in:infra -in:home"), vec!["Jira"]);
+        assert_eq!(names("tag:tracking"), vec!["Jira"]);
+        assert_eq!(names("NOT (in:work OR cooking)"), vec!["News"]);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_regex() {
+        assert_eq!(names(r"/^[JN]/"), vec!["Jira", "News"]);
+        assert_eq!(names(r"url:/:\d+/"), vec!["Grafana"]);
+        assert_eq!(names(r"name:/\/x/"), Vec::<String>::new());
+        assert!(Query::parse("/(/")
+            .unwrap_err()
+            .message
+            .starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            host("https://user@Example.com:8080/a?b#c"),
+            Some("Example.com")
+        );
+        assert_eq!(host("http://[::1]:80/"), Some("::1"));
+        assert_eq!(host("mailto:someone@example.com"), None);
+        assert_eq!(host("file:///etc/hosts"), None);
+
+        let idn = Bookmark::new("Bücher", "Books", "https://xn--bcher-kva.example/");
+        assert!(Query::Domain("Bücher.example".to_string()).matches(&[], &idn));
+    }
+}
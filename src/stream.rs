@@ -0,0 +1,394 @@
+//! Streaming parser
+//!
+//! [`Events`] reads an SBM file line by line from any [`BufRead`] and yields
+//! one [`Event`] per line, so arbitrarily large files can be filtered or
+//! converted in constant memory. `//@` annotation lines don't get an event of
+//! their own: their metadata comes with the header or bookmark below them,
+//! and annotations with no entry below them are dropped. [`Categories`]
+//! builds on it and yields whole categories one at a time.
+
+use crate::parser::{self, Depths, LineKind, Nesting, Orphans, ParseError, ParseOptions};
+use crate::{Bookmark, Category, Error, Header};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+/// A single line of an SBM file
+#[derive(Debug, PartialEq, Clone)]
+pub enum Event {
+    /// A category header; `depth` is the number of `#`s, 1 for a top-level
+    /// category
+    Header {
+        header: Header,
+        depth: usize,
+    },
+    Bookmark(Bookmark),
+    /// A `//` comment, holding the text after the slashes
+    Comment(String),
+    Blank,
+}
+
+/// Iterator over the lines of an SBM file as [`Event`]s
+///
+/// A malformed line yields an error and iteration carries on with the next
+/// line, so callers can decide whether to stop or skip it. An I/O error ends
+/// the iteration.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::stream::{Event, Events};
+/// let data = "#Languages\nRust|Systems programming language|https://www.rust-lang.org/\n";
+/// let urls: Vec<String> = Events::new(data.as_bytes())
+///     .filter_map(|event| match event.unwrap() {
+///         Event::Bookmark(b) => Some(b.url),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(urls, vec!["https://www.rust-lang.org/"]);
+/// ```
+#[derive(Debug)]
+pub struct Events<R> {
+    reader: R,
+    options: ParseOptions,
+    buf: String,
+    line: usize,
+    offset: usize,
+    seen_header: bool,
+    depths: Depths,
+    pending: Option<Event>,
+    metadata: BTreeMap<String, String>,
+    done: bool,
+}
+
+impl<R: BufRead> Events<R> {
+    /// Stream events with the default (strict) options
+    pub fn new(reader: R) -> Events<R> {
+        Events::with_options(reader, ParseOptions::default())
+    }
+
+    /// Stream events with the given options
+    ///
+    /// With [`Orphans::Collect`], a header event for
+    /// [`parser::UNCATEGORIZED`] is emitted before the first bookmark that
+    /// has no category. Events can't look ahead, so a later header of that
+    /// name starts a second category rather than taking the bookmarks in
+    /// as [`parser::parse_categories_with`] does.
+    pub fn with_options(reader: R, options: ParseOptions) -> Events<R> {
+        Events {
+            reader,
+            options,
+            buf: String::new(),
+            line: 0,
+            offset: 0,
+            seen_header: false,
+            depths: Depths::new(),
+            pending: None,
+            metadata: BTreeMap::new(),
+            done: false,
+        }
+    }
+
+    /// 1-based number of the line the last event came from
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The event for the line in `buf`, or `None` for an annotation line
+    fn event(&mut self) -> Result<Option<Event>, Error> {
+        let raw = self.buf.strip_suffix('\n').unwrap_or(&self.buf);
+        let line = raw.strip_suffix('\r').unwrap_or(raw);
+        let located = |e: parser::ParseError| e.at(self.line, self.offset, line);
+        let kind = parser::classify(line);
+        if let LineKind::Annotation { key, value } = &kind {
+            self.metadata.insert(key.to_string(), value.to_string());
+            return Ok(None);
+        }
+        let metadata = std::mem::take(&mut self.metadata);
+        let event = match kind {
+            LineKind::Blank => Event::Blank,
+            LineKind::Comment | LineKind::Annotation { .. } => {
+                Event::Comment(line[2..].to_string())
+            }
+            LineKind::Header { depth, text } => {
+                self.seen_header = true;
+                let (depth, text, _) = self.depths.place(line, depth, text);
+                Event::Header {
+                    header: Header {
+                        metadata,
+                        ..parser::parse_header(text).map_err(located)?
+                    },
+                    depth,
+                }
+            }
+            LineKind::Bookmark(text) => {
+                let bookmark = Bookmark {
+                    metadata,
+                    ..parser::parse_bookmark(text).map_err(located)?
+                };
+                self.options
+                    .check_url(&bookmark.url, self.line, self.offset, line)?;
+                if !self.seen_header {
+                    if self.options.orphans == Orphans::Reject {
+                        return Err(parser::orphan(self.line, self.offset, line).into());
+                    }
+                    self.seen_header = true;
+                    self.pending = Some(Event::Bookmark(bookmark));
+                    return Ok(Some(Event::Header {
+                        header: Header::new(parser::UNCATEGORIZED, None),
+                        depth: 1,
+                    }));
+                }
+                Event::Bookmark(bookmark)
+            }
+        };
+        Ok(Some(event))
+    }
+}
+
+impl<R: BufRead> Iterator for Events<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.take() {
+            return Some(Ok(event));
+        }
+        while !self.done {
+            self.offset += self.buf.len();
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    if let Some(event) = self.event().transpose() {
+                        return Some(event);
+                    }
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the categories of an SBM file
+///
+/// Only the top-level category being built is held in memory; it is yielded
+/// with all of its subcategories. Errors are passed through
+/// from [`Events`]; the offending line is left out of the category. After a
+/// header that fails to parse, so are the bookmarks under it, rather than
+/// being filed under the category before it.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::stream::Categories;
+/// let data = "#Languages\nRust|Rust|https://www.rust-lang.org/\n#Web|🌐\nMDN|Docs|https://developer.mozilla.org/\n";
+/// let names: Vec<String> = Categories::new(data.as_bytes())
+///     .map(|c| c.unwrap().header.name)
+///     .collect();
+/// assert_eq!(names, vec!["Languages", "Web"]);
+/// ```
+#[derive(Debug)]
+pub struct Categories<R> {
+    events: Events<R>,
+    nesting: Nesting<Category>,
+    /// Whether the last header failed to parse
+    skipping: bool,
+}
+
+impl<R: BufRead> Categories<R> {
+    /// Stream categories with the default (strict) options
+    pub fn new(reader: R) -> Categories<R> {
+        Categories::with_options(reader, ParseOptions::default())
+    }
+
+    /// Stream categories with the given options
+    pub fn with_options(reader: R, options: ParseOptions) -> Categories<R> {
+        Categories {
+            events: Events::with_options(reader, options),
+            nesting: Nesting::new(|parent, child| parent.children.push(child)),
+            skipping: false,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Categories<R> {
+    type Item = Result<Category, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.events.next() {
+                None => return self.nesting.finish().pop().map(Ok),
+                Some(Err(e)) => {
+                    if matches!(e, Error::Parse(ParseError::Header { .. })) {
+                        self.skipping = true;
+                    }
+                    return Some(Err(e));
+                }
+                Some(Ok(Event::Header { header, depth })) => {
+                    self.skipping = false;
+                    // the header for orphans comes with its first bookmark pending
+                    if self.events.pending.is_some() {
+                        self.nesting.open_implicit(Category::new(header));
+                    } else {
+                        self.nesting.open(depth, Category::new(header));
+                    }
+                    if let Some(category) = self.nesting.take_finished() {
+                        return Some(Ok(category));
+                    }
+                }
+                Some(Ok(Event::Bookmark(_))) if self.skipping => {}
+                Some(Ok(Event::Bookmark(bookmark))) => {
+                    if let Some(category) = self.nesting.current() {
+                        category.bookmarks.push(bookmark);
+                    }
+                }
+                Some(Ok(Event::Comment(_) | Event::Blank)) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::UNCATEGORIZED;
+    use std::io::{BufReader, Read};
+
+    const DATA: &str = "// links\r\n#Programming Languages|👨‍💻\r\nRust|The Rust Programming Language|https://www.rust-lang.org/\r\n\r\nPython|Python Programming Language|https://www.python.org/\r\n#Web Development\r\nMDN|Web documentation|https://developer.mozilla.org/";
+
+    #[test]
+    fn test_events() {
+        let events: Vec<Event> = Events::new(DATA.as_bytes()).map(|e| e.unwrap()).collect();
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[0], Event::Comment(" links".to_string()));
+        assert_eq!(
+            events[1],
+            Event::Header {
+                header: Header::new("Programming Languages", Some("👨‍💻")),
+                depth: 1
+            }
+        );
+        assert_eq!(events[3], Event::Blank);
+        assert_eq!(
+            events[6],
+            Event::Bookmark(Bookmark::new(
+                "MDN",
+                "Web documentation",
+                "https://developer.mozilla.org/"
+            ))
+        );
+    }
+
+    #[test]
+    fn test_categories_match_parser() {
+        // a tiny buffer makes sure lines spanning several reads are handled
+        let reader = BufReader::with_capacity(4, DATA.as_bytes());
+        let categories: Vec<Category> = Categories::new(reader).map(|c| c.unwrap()).collect();
+        assert_eq!(categories, parser::parse_categories(DATA).unwrap());
+    }
+
+    #[test]
+    fn test_nested_categories() {
+        let data = "a|b|c\n##Sub\n#A\n##A1\nx|y|z\n###A1a\n##A2\n#B\n##B1\n";
+        let options = ParseOptions::lenient();
+        let categories: Vec<Category> = Categories::with_options(data.as_bytes(), options.clone())
+            .map(|c| c.unwrap())
+            .collect();
+        assert_eq!(
+            categories,
+            parser::parse_categories_with(data, &options).unwrap()
+        );
+        assert_eq!(categories.len(), 4);
+        assert_eq!(categories[1].header.name, "#Sub");
+        assert_eq!(categories[2].children[0].children[0].header.name, "A1a");
+    }
+
+    #[test]
+    fn test_metadata() {
+        let data = "//@ owner: ops\n#Work\n//@ added: 2024-01-31\nJira|Issues|https://jira.example.com/\n//@ dangling: x\n";
+        let events: Vec<Event> = Events::new(data.as_bytes()).map(|e| e.unwrap()).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[1],
+            Event::Bookmark(
+                Bookmark::new("Jira", "Issues", "https://jira.example.com/")
+                    .with_metadata("added", "2024-01-31")
+            )
+        );
+        let categories: Vec<Category> = Categories::new(data.as_bytes())
+            .map(|c| c.unwrap())
+            .collect();
+        assert_eq!(categories, parser::parse_categories(data).unwrap());
+    }
+
+    #[test]
+    fn test_errors_keep_going() {
+        let data = "Orphan|a|b\n#A\nbad\nok|ok|ok\n";
+        let mut events = Events::new(data.as_bytes());
+        match events.next() {
+            Some(Err(Error::Parse(ParseError::OrphanBookmark { location }))) => {
+                assert_eq!(location.line, 1)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(events.next(), Some(Ok(Event::Header { .. }))));
+        match events.next() {
+            Some(Err(Error::Parse(e))) => assert_eq!(e.location().span, 14..17),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(events.next(), Some(Ok(Event::Bookmark(_)))));
+        assert!(events.next().is_none());
+
+        let categories: Vec<Category> =
+            Categories::with_options(data.as_bytes(), ParseOptions::lenient())
+                .filter_map(Result::ok)
+                .collect();
+        assert_eq!(categories.len(), 2);
+        assert_eq!(categories[0].header.name, UNCATEGORIZED);
+        assert_eq!(categories[1].bookmarks.len(), 1);
+    }
+
+    #[test]
+    fn test_bad_header_skips_its_bookmarks() {
+        let data = "#Work\nA|a|a\n#Bad|icon|extra\nB|b|b\n#Next\nC|c|c\n";
+        let mut categories = Categories::new(data.as_bytes());
+        match categories.next() {
+            Some(Err(Error::Parse(ParseError::Header { location, .. }))) => {
+                assert_eq!(location.line, 3)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        let names = |c: Category| -> (String, Vec<String>) {
+            (
+                c.header.name,
+                c.bookmarks.into_iter().map(|b| b.name).collect(),
+            )
+        };
+        assert_eq!(
+            categories.next().map(|c| names(c.unwrap())),
+            Some(("Work".to_string(), vec!["A".to_string()]))
+        );
+        assert_eq!(
+            categories.next().map(|c| names(c.unwrap())),
+            Some(("Next".to_string(), vec!["C".to_string()]))
+        );
+        assert!(categories.next().is_none());
+    }
+
+    #[test]
+    fn test_io_error_ends_iteration() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("disk on fire"))
+            }
+        }
+        let mut events = Events::new(BufReader::new(Failing));
+        assert!(matches!(events.next(), Some(Err(Error::Io(_)))));
+        assert!(events.next().is_none());
+    }
+}
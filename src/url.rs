@@ -0,0 +1,685 @@
+//! URLs
+//!
+//! The `url` field of a bookmark is free text to the parser. This module
+//! checks whether it is a usable URL, with [`Url::parse`], and puts URLs into
+//! a canonical form, with [`normalize`], so that different spellings of the
+//! same address compare equal. [`key`] goes further and is what duplicate
+//! detection and diffs match bookmarks by.
+//!
+//! Validation can also run while parsing, see
+//! [`ParseOptions::validate_urls`](crate::parser::ParseOptions::validate_urls),
+//! or over a parsed file with [`lint`].
+//!
+//! # Examples
+//!
+//! ```
+//! use sbm::url::{self, Url, UrlError};
+//! let parsed = Url::parse("https://Example.com:443/docs").unwrap();
+//! assert_eq!(parsed.host, Some("Example.com"));
+//! assert_eq!(
+//!     Url::parse("htps:/example.com"),
+//!     Err(UrlError::MisspelledScheme("htps".to_string()))
+//! );
+//! assert_eq!(
+//!     url::normalize("HTTPS://Bücher.Example:443/?utm_source=x&q=1"),
+//!     "https://xn--bcher-kva.example/?q=1"
+//! );
+//! ```
+
+use crate::{Bookmark, Category, Sbm};
+
+/// Schemes whose URLs have to name a host, and the port each uses unless told
+/// otherwise
+const HOST_SCHEMES: [(&str, &str); 6] = [
+    ("http", "80"),
+    ("https", "443"),
+    ("ws", "80"),
+    ("wss", "443"),
+    ("ftp", "21"),
+    ("ftps", "990"),
+];
+
+/// Query parameters that only track where a visitor came from
+const TRACKING_PARAMETERS: [&str; 12] = [
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid",
+    "mc_eid", "_hsenc", "_hsmi",
+];
+
+/// A URL split into its parts, borrowed from the text
+///
+/// The parts are as written, neither decoded nor normalized.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Url<'a> {
+    pub scheme: &'a str,
+    /// The part before `@` in the authority
+    pub userinfo: Option<&'a str>,
+    /// The host, if the URL has an authority (`//` after the scheme); IPv6
+    /// addresses are given without their brackets
+    pub host: Option<&'a str>,
+    pub port: Option<&'a str>,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub fragment: Option<&'a str>,
+}
+
+impl<'a> Url<'a> {
+    /// Split a URL into its parts without checking them
+    ///
+    /// Only fails if the text doesn't start with a `scheme:`.
+    pub fn split(url: &'a str) -> Option<Url<'a>> {
+        let (scheme, rest) = url.split_once(':')?;
+        if !valid_scheme(scheme) {
+            return None;
+        }
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (rest, None),
+        };
+        let (rest, query) = match rest.split_once('?') {
+            Some((rest, query)) => (rest, Some(query)),
+            None => (rest, None),
+        };
+        let mut url = Url {
+            scheme,
+            userinfo: None,
+            host: None,
+            port: None,
+            path: rest,
+            query,
+            fragment,
+        };
+        let Some(rest) = rest.strip_prefix("//") else {
+            return Some(url);
+        };
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        url.path = path;
+        let host_port = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => {
+                url.userinfo = Some(userinfo);
+                host_port
+            }
+            None => authority,
+        };
+        let (host, port) = match host_port.strip_prefix('[') {
+            Some(ipv6) => match ipv6.split_once(']') {
+                Some((host, rest)) => (host, rest.strip_prefix(':')),
+                None => (host_port, None),
+            },
+            None => match host_port.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        url.host = Some(host);
+        url.port = port;
+        Some(url)
+    }
+
+    /// Split a URL into its parts, checking that it is well-formed
+    ///
+    /// Besides the syntax, this catches schemes that are one letter away
+    /// from `http` or `https`, and `http`, `https`, `ws`, `wss`, `ftp` and
+    /// `ftps` URLs without a host. Other schemes, such as `mailto:` or an
+    /// application's own, are accepted as long as they are well-formed.
+    pub fn parse(url: &'a str) -> Result<Url<'a>, UrlError> {
+        if url.is_empty() {
+            return Err(UrlError::Empty);
+        }
+        if let Some(c) = url.chars().find(|c| c.is_whitespace() || c.is_control()) {
+            return Err(UrlError::InvalidCharacter(c));
+        }
+        let parsed = Url::split(url).ok_or_else(|| match url.split_once(':') {
+            Some((scheme, _)) => UrlError::InvalidScheme(scheme.to_string()),
+            None => UrlError::MissingScheme,
+        })?;
+        let scheme = parsed.scheme.to_ascii_lowercase();
+        if parsed.default_port().is_none()
+            && ["http", "https"]
+                .iter()
+                .any(|known| edit_distance(&scheme, known) == 1)
+        {
+            return Err(UrlError::MisspelledScheme(parsed.scheme.to_string()));
+        }
+        match parsed.host {
+            Some("") | None if parsed.default_port().is_some() => {
+                return Err(UrlError::MissingHost)
+            }
+            Some(host) if !host.is_empty() && !valid_host(host) => {
+                return Err(UrlError::InvalidHost(host.to_string()))
+            }
+            _ => {}
+        }
+        if let Some(port) = parsed.port {
+            if !port.is_empty() && port.parse::<u16>().is_err() {
+                return Err(UrlError::InvalidPort(port.to_string()));
+            }
+        }
+        if let Some(i) = url.match_indices('%').map(|(i, _)| i).find(|&i| {
+            !url.get(i + 1..i + 3)
+                .is_some_and(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+        }) {
+            let end = url[i..]
+                .char_indices()
+                .nth(3)
+                .map_or(url.len(), |(j, _)| i + j);
+            return Err(UrlError::InvalidPercentEncoding(url[i..end].to_string()));
+        }
+        Ok(parsed)
+    }
+
+    /// The port used when none is given, for schemes that require a host
+    fn default_port(&self) -> Option<&'static str> {
+        HOST_SCHEMES
+            .iter()
+            .find(|(s, _)| s.eq_ignore_ascii_case(self.scheme))
+            .map(|(_, port)| *port)
+    }
+}
+
+/// What is wrong with a URL, see [`Url::parse`]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum UrlError {
+    Empty,
+    /// A character that has to be percent-encoded, such as a space
+    InvalidCharacter(char),
+    MissingScheme,
+    InvalidScheme(String),
+    /// A scheme one letter away from `http` or `https`, such as `htps`
+    MisspelledScheme(String),
+    MissingHost,
+    InvalidHost(String),
+    InvalidPort(String),
+    /// A `%` that isn't followed by two hex digits, with what follows it
+    InvalidPercentEncoding(String),
+}
+
+impl std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UrlError::Empty => write!(f, "URL is empty"),
+            UrlError::InvalidCharacter(c) => {
+                write!(f, "URL contains {:?}, which has to be percent-encoded", c)
+            }
+            UrlError::MissingScheme => write!(f, "URL has no scheme such as https:"),
+            UrlError::InvalidScheme(scheme) => write!(f, "invalid scheme {:?}", scheme),
+            UrlError::MisspelledScheme(scheme) => {
+                write!(
+                    f,
+                    "scheme {:?} looks like a misspelled http or https",
+                    scheme
+                )
+            }
+            UrlError::MissingHost => write!(f, "URL has no host"),
+            UrlError::InvalidHost(host) => write!(f, "invalid host {:?}", host),
+            UrlError::InvalidPort(port) => write!(f, "invalid port {:?}", port),
+            UrlError::InvalidPercentEncoding(text) => {
+                write!(f, "invalid percent-encoding {:?}", text)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+fn valid_scheme(scheme: &str) -> bool {
+    let mut chars = scheme.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+fn valid_host(host: &str) -> bool {
+    if host.contains(':') {
+        return host
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.');
+    }
+    let host = host.strip_suffix('.').unwrap_or(host);
+    host.split('.').all(|label| {
+        !label.is_empty()
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    })
+}
+
+/// Number of single-character insertions, deletions and substitutions
+/// between two short strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let next = (diagonal + usize::from(ca != cb))
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            diagonal = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// Put a URL into canonical form
+///
+/// The scheme and host are lowercased, hosts with non-ASCII characters are
+/// encoded as punycode, default ports such as `:443` for `https` are dropped,
+/// and so are tracking parameters such as `utm_source` or `fbclid` and empty
+/// queries and fragments. Percent-encoding is written with uppercase hex
+/// digits, and characters that never need it are decoded. An `http` or
+/// `https` URL with no path gets `/`. Text that doesn't start with a
+/// `scheme:` is only trimmed.
+///
+/// The result points to the same resource as the input, unlike [`key`].
+///
+/// # Examples
+///
+/// ```
+/// use sbm::url::normalize;
+/// assert_eq!(normalize("HTTP://Example.COM:80"), "http://example.com/");
+/// assert_eq!(normalize("https://example.com/%7euser/?fbclid=abc#"), "https://example.com/~user/");
+/// assert_eq!(normalize("mailto:Someone@Example.com"), "mailto:Someone@Example.com");
+/// ```
+pub fn normalize(url: &str) -> String {
+    let url = url.trim();
+    let Some(parsed) = Url::split(url) else {
+        return url.to_string();
+    };
+    let mut normalized = parsed.scheme.to_ascii_lowercase();
+    normalized.push(':');
+    if let Some(host) = parsed.host {
+        normalized.push_str("//");
+        if let Some(userinfo) = parsed.userinfo {
+            normalized.push_str(&normalize_percent(userinfo));
+            normalized.push('@');
+        }
+        if host.contains(':') {
+            normalized.push('[');
+            normalized.push_str(&host.to_ascii_lowercase());
+            normalized.push(']');
+        } else {
+            normalized.push_str(&to_ascii(host));
+        }
+        if let Some(port) = parsed.port {
+            if !port.is_empty() && Some(port) != parsed.default_port() {
+                normalized.push(':');
+                normalized.push_str(port);
+            }
+        }
+    }
+    match parsed.path {
+        "" if parsed.default_port().is_some() => normalized.push('/'),
+        path => normalized.push_str(&normalize_percent(path)),
+    }
+    let params: Vec<String> = parsed
+        .query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .filter(|param| !param.is_empty() && !is_tracking(param))
+        .map(normalize_percent)
+        .collect();
+    if !params.is_empty() {
+        normalized.push('?');
+        normalized.push_str(&params.join("&"));
+    }
+    if let Some(fragment) = parsed.fragment.filter(|f| !f.is_empty()) {
+        normalized.push('#');
+        normalized.push_str(&normalize_percent(fragment));
+    }
+    normalized
+}
+
+/// A looser form of [`normalize`], equal for URLs that very likely point to
+/// the same page
+///
+/// On top of [`normalize`], `http` and `https` are treated alike, as are
+/// hosts with and without a leading `www.`, and a trailing `/` is dropped
+/// from the path. The result is only good for comparing URLs.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::url::key;
+/// assert_eq!(
+///     key("https://www.Example.com/docs/?utm_source=mail&page=2"),
+///     key("http://example.com/docs?page=2")
+/// );
+/// assert_ne!(key("https://example.com/a"), key("https://example.com/b"));
+/// ```
+pub fn key(url: &str) -> String {
+    let normalized = normalize(url);
+    let Some(parsed) = Url::split(&normalized) else {
+        return normalized;
+    };
+    let mut key = match parsed.scheme {
+        "http" | "https" => String::new(),
+        scheme => format!("{}:", scheme),
+    };
+    if let Some(host) = parsed.host {
+        key.push_str("//");
+        if let Some(userinfo) = parsed.userinfo {
+            key.push_str(userinfo);
+            key.push('@');
+        }
+        match host.contains(':') {
+            true => key.push_str(&format!("[{}]", host)),
+            false => key.push_str(host.strip_prefix("www.").unwrap_or(host)),
+        }
+        if let Some(port) = parsed.port {
+            key.push(':');
+            key.push_str(port);
+        }
+    }
+    key.push_str(parsed.path.trim_end_matches('/'));
+    if let Some(query) = parsed.query {
+        key.push('?');
+        key.push_str(query);
+    }
+    if let Some(fragment) = parsed.fragment {
+        key.push('#');
+        key.push_str(fragment);
+    }
+    key
+}
+
+/// Whether a `name=value` query parameter only tracks the visitor
+fn is_tracking(param: &str) -> bool {
+    let name = param
+        .split('=')
+        .next()
+        .unwrap_or(param)
+        .to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMETERS.contains(&name.as_str())
+}
+
+/// Uppercase the hex digits of percent-encoding, and decode characters that
+/// never need it
+fn normalize_percent(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('%') {
+        normalized.push_str(&rest[..i]);
+        let encoded = rest
+            .get(i + 1..i + 3)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()));
+        match encoded.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(b) if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') => {
+                normalized.push(char::from(b));
+                rest = &rest[i + 3..];
+            }
+            Some(_) => {
+                normalized.push('%');
+                normalized.push_str(&encoded.unwrap().to_ascii_uppercase());
+                rest = &rest[i + 3..];
+            }
+            None => {
+                normalized.push('%');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    normalized.push_str(rest);
+    normalized
+}
+
+/// Lowercase a host name and encode its non-ASCII labels as punycode
+///
+/// This is the lowercasing and encoding half of IDNA; the Unicode mappings
+/// and checks it also describes are not applied.
+///
+/// # Examples
+///
+/// ```
+/// use sbm::url::to_ascii;
+/// assert_eq!(to_ascii("München.DE"), "xn--mnchen-3ya.de");
+/// ```
+pub fn to_ascii(host: &str) -> String {
+    host.to_lowercase()
+        .split('.')
+        .map(|label| match label.is_ascii() {
+            true => label.to_string(),
+            false => punycode(label).map_or_else(|| label.to_string(), |p| format!("xn--{}", p)),
+        })
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// Encode a label as punycode, as described in RFC 3492
+fn punycode(label: &str) -> Option<String> {
+    const BASE: u32 = 36;
+    const T_MIN: u32 = 1;
+    const T_MAX: u32 = 26;
+
+    fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+        let mut delta = delta / if first { 700 } else { 2 };
+        delta += delta / points;
+        let mut k = 0;
+        while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+            delta /= BASE - T_MIN;
+            k += BASE;
+        }
+        k + (BASE - T_MIN + 1) * delta / (delta + 38)
+    }
+
+    fn digit(d: u32) -> char {
+        let d = d as u8;
+        char::from(if d < 26 { b'a' + d } else { b'0' + d - 26 })
+    }
+
+    let code_points: Vec<u32> = label.chars().map(u32::from).collect();
+    let mut output: String = label.chars().filter(char::is_ascii).collect();
+    let basic = output.len() as u32;
+    if basic > 0 {
+        output.push('-');
+    }
+    let (mut n, mut delta, mut bias, mut handled) = (128, 0u32, 72, basic);
+    while (handled as usize) < code_points.len() {
+        let m = code_points.iter().copied().filter(|&c| c >= n).min()?;
+        delta = delta.checked_add((m - n).checked_mul(handled + 1)?)?;
+        n = m;
+        for &c in &code_points {
+            if c < n {
+                delta = delta.checked_add(1)?;
+            }
+            if c == n {
+                let mut q = delta;
+                let mut k = BASE;
+                loop {
+                    let t = if k <= bias {
+                        T_MIN
+                    } else if k >= bias + T_MAX {
+                        T_MAX
+                    } else {
+                        k - bias
+                    };
+                    if q < t {
+                        break;
+                    }
+                    output.push(digit(t + (q - t) % (BASE - t)));
+                    q = (q - t) / (BASE - t);
+                    k += BASE;
+                }
+                output.push(digit(q));
+                bias = adapt(delta, handled + 1, handled == basic);
+                delta = 0;
+                handled += 1;
+            }
+        }
+        delta += 1;
+        n += 1;
+    }
+    Some(output)
+}
+
+/// Every bookmark whose URL fails [`Url::parse`], with its category and the
+/// reason, in file order
+///
+/// # Examples
+///
+/// ```
+/// use sbm::url::{self, UrlError};
+/// use sbm::Sbm;
+/// let sbm: Sbm = "#Web\nMDN|Docs|https://developer.mozilla.org/\nTypo|Oops|htps:/example.com\n".parse().unwrap();
+/// let problems = url::lint(&sbm);
+/// assert_eq!(problems.len(), 1);
+/// assert_eq!(problems[0].1.name, "Typo");
+/// assert_eq!(problems[0].2, UrlError::MisspelledScheme("htps".to_string()));
+/// ```
+pub fn lint(sbm: &Sbm) -> Vec<(&Category, &Bookmark, UrlError)> {
+    sbm.entries()
+        .filter_map(|(category, bookmark)| {
+            let error = Url::parse(&bookmark.url).err()?;
+            Some((category, bookmark, error))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let url = Url::split("https://user:pw@[::1]:8080/a/b?x=1#top").unwrap();
+        assert_eq!(
+            url,
+            Url {
+                scheme: "https",
+                userinfo: Some("user:pw"),
+                host: Some("::1"),
+                port: Some("8080"),
+                path: "/a/b",
+                query: Some("x=1"),
+                fragment: Some("top"),
+            }
+        );
+        let url = Url::split("mailto:someone@example.com").unwrap();
+        assert_eq!((url.host, url.path), (None, "someone@example.com"));
+        assert_eq!(Url::split("example.com/a:b"), None);
+        assert_eq!(Url::split("no scheme"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        for url in [
+            "https://www.rust-lang.org/",
+            "http://localhost:8080",
+            "https://bücher.example/%C3%BC?q=a%20b",
+            "http://[2001:db8::1]/",
+            "mailto:someone@example.com",
+            "javascript:void(0)",
+            "file:///home/me/notes.txt",
+            "obsidian://open?vault=notes",
+        ] {
+            assert_eq!(Url::parse(url).map(|_| ()), Ok(()), "{}", url);
+        }
+        let error = |url| Url::parse(url).unwrap_err();
+        assert_eq!(error(""), UrlError::Empty);
+        assert_eq!(
+            error("https://example.com/a b"),
+            UrlError::InvalidCharacter(' ')
+        );
+        assert_eq!(error("example.com"), UrlError::MissingScheme);
+        assert_eq!(
+            error("1http://example.com"),
+            UrlError::InvalidScheme("1http".to_string())
+        );
+        for typo in [
+            "htps://example.com",
+            "htp://example.com",
+            "httpss://example.com",
+        ] {
+            assert!(
+                matches!(error(typo), UrlError::MisspelledScheme(_)),
+                "{}",
+                typo
+            );
+        }
+        assert_eq!(error("https:/example.com"), UrlError::MissingHost);
+        assert_eq!(error("https:///path"), UrlError::MissingHost);
+        assert_eq!(
+            error("https://exa mple.com"),
+            UrlError::InvalidCharacter(' ')
+        );
+        assert_eq!(
+            error("https://example..com/"),
+            UrlError::InvalidHost("example..com".to_string())
+        );
+        assert_eq!(
+            error("https://example.com:99999/"),
+            UrlError::InvalidPort("99999".to_string())
+        );
+        assert_eq!(
+            error("https://example.com/100%"),
+            UrlError::InvalidPercentEncoding("%".to_string())
+        );
+        assert_eq!(
+            error("https://example.com/?q=%zz1"),
+            UrlError::InvalidPercentEncoding("%zz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(" HTTPS://User@WWW.Example.com:443?utm_medium=x&b=%2f&a=%41 "),
+            "https://User@www.example.com/?b=%2F&a=A"
+        );
+        assert_eq!(
+            normalize("https://example.com:8443/"),
+            "https://example.com:8443/"
+        );
+        assert_eq!(
+            normalize("http://[2001:DB8::1]:80/"),
+            "http://[2001:db8::1]/"
+        );
+        assert_eq!(normalize("ftp://Example.com:21"), "ftp://example.com/");
+        assert_eq!(normalize("https://example.com/a?"), "https://example.com/a");
+        assert_eq!(
+            normalize("https://example.com/100%"),
+            "https://example.com/100%"
+        );
+        assert_eq!(normalize("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_key() {
+        let rust = key("https://www.rust-lang.org/");
+        assert_eq!(rust, "//rust-lang.org");
+        for url in [
+            "http://rust-lang.org",
+            " HTTPS://WWW.Rust-Lang.org ",
+            "https://rust-lang.org:443/?utm_source=a&utm_medium=b",
+            "https://rust-lang.org#",
+        ] {
+            assert_eq!(key(url), rust, "{}", url);
+        }
+        assert_eq!(
+            key("https://example.com/Path/?b=1&utm_campaign=x&a=2#top"),
+            "//example.com/Path?b=1&a=2#top"
+        );
+        assert_eq!(key("ftp://Example.com/"), "ftp://example.com");
+        assert_eq!(key("https://www2.example.com/"), "//www2.example.com");
+        assert_eq!(key("about:blank"), "about:blank");
+    }
+
+    #[test]
+    fn test_punycode() {
+        // examples from RFC 3492 and common IDNs
+        assert_eq!(punycode("bücher").unwrap(), "bcher-kva");
+        assert_eq!(punycode("ü").unwrap(), "tda");
+        assert_eq!(
+            punycode("他们为什么不说中文").unwrap(),
+            "ihqwcrb4cv8a8dqg056pqjye"
+        );
+        assert_eq!(to_ascii("Bücher.example"), "xn--bcher-kva.example");
+        assert_eq!(to_ascii("xn--bcher-kva.example"), "xn--bcher-kva.example");
+        assert_eq!(
+            normalize("https://Bücher.example/"),
+            normalize("https://xn--bcher-kva.example/")
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! End-to-end tests for the `sbm` binary
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const DATA: &str = "// team links\n\n# Languages | 👨‍💻\nRust | The Rust Programming Language | https://www.rust-lang.org/\n\n# Web\nMDN | Web documentation | https://developer.mozilla.org/\n";
+
+/// A scratch file that is removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &str) -> TempFile {
+        let path = std::env::temp_dir().join(format!("sbm-cli-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+
+    fn read(&self) -> String {
+        std::fs::read_to_string(&self.0).unwrap()
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn sbm(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_sbm"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // A command that exits before reading its input closes the pipe early
+    let _ = child.stdin.take().unwrap().write_all(stdin.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn test_list_and_search() {
+    let output = sbm(&["list", "--categories"], DATA);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "👨‍💻 Languages\nWeb\n");
+
+    let output = sbm(&["search", "mozilla"], DATA);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Web\tMDN\thttps://developer.mozilla.org/\n"
+    );
+
+    let output = sbm(&["search", "nothing like this"], DATA);
+    assert_eq!(output.status.code(), Some(1));
+
+    let nested = "#Work\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\n#Home\n";
+    assert_eq!(
+        stdout(&sbm(&["list"], nested)),
+        "Work\n  Infra\n    Grafana  https://grafana.example.com/\nHome\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["search", "dash"], nested)),
+        "Work / Infra\tGrafana\thttps://grafana.example.com/\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["search", "in:work", "domain:example.com"], nested)),
+        "Work / Infra\tGrafana\thttps://grafana.example.com/\n"
+    );
+    assert_eq!(sbm(&["search", "-in:work"], nested).status.code(), Some(1));
+    let output = sbm(&["search", "(dash"], nested);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unmatched `(`"));
+
+    assert_eq!(
+        stdout(&sbm(&["search", "--fuzzy", "mdn", "wdoc"], DATA)),
+        "Web\tMDN\thttps://developer.mozilla.org/\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["search", "--fuzzy", "r"], DATA)),
+        "Languages\tRust\thttps://www.rust-lang.org/\nWeb\tMDN\thttps://developer.mozilla.org/\n"
+    );
+    assert_eq!(
+        sbm(&["search", "--fuzzy", "xyzzy"], DATA).status.code(),
+        Some(1)
+    );
+    assert_eq!(
+        stdout(&sbm(&["search", "--fuzzy", "gfn"], nested)),
+        "Work / Infra\tGrafana\thttps://grafana.example.com/\n"
+    );
+
+    let tagged = "#Languages\nRust|Rust|https://www.rust-lang.org/|systems,docs\n#Web\nMDN|MDN|https://developer.mozilla.org/|docs\n";
+    assert_eq!(stdout(&sbm(&["tags"], tagged)), "docs\t2\nsystems\t1\n");
+    assert_eq!(
+        stdout(&sbm(&["tags", "docs"], tagged)),
+        "Languages\tRust\thttps://www.rust-lang.org/\nWeb\tMDN\thttps://developer.mozilla.org/\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["search", "systems"], tagged)),
+        "Languages\tRust\thttps://www.rust-lang.org/\n"
+    );
+    assert_eq!(sbm(&["tags", "nope"], tagged).status.code(), Some(1));
+}
+
+#[test]
+fn test_edit_file_in_place() {
+    let file = TempFile::new("edit.sbm", DATA);
+    let output = sbm(
+        &[
+            "add",
+            "-f",
+            file.path(),
+            "Web",
+            "HN",
+            "Hacker News",
+            "https://news.ycombinator.com/",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        file.read(),
+        DATA.to_string() + "HN|Hacker News|https://news.ycombinator.com/\n"
+    );
+
+    let output = sbm(
+        &[
+            "move",
+            "-f",
+            file.path(),
+            "https://news.ycombinator.com/",
+            "Languages",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let output = sbm(
+        &[
+            "remove",
+            "-f",
+            file.path(),
+            "https://developer.mozilla.org/",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        file.read(),
+        "// team links\n\n# Languages | 👨‍💻\nRust | The Rust Programming Language | https://www.rust-lang.org/\nHN|Hacker News|https://news.ycombinator.com/\n\n# Web\n"
+    );
+
+    let output = sbm(&["remove", "-f", file.path(), "https://example.com/"], "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_edit_by_path() {
+    let data = "#Work\n##Docs\n#Home\n##Docs\nWiki||https://wiki.example.com/\n";
+    let output = sbm(
+        &[
+            "add",
+            "Home / Docs",
+            "Recipes",
+            "",
+            "https://recipes.example.com/",
+        ],
+        data,
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Docs\n#Home\n##Docs\nWiki||https://wiki.example.com/\nRecipes||https://recipes.example.com/\n"
+    );
+    let output = sbm(&["move", "https://wiki.example.com/", "Work / Docs"], data);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Docs\nWiki||https://wiki.example.com/\n#Home\n##Docs\n"
+    );
+
+    // a name shared by several categories doesn't say which one
+    let output = sbm(
+        &["add", "Docs", "Recipes", "", "https://recipes.example.com/"],
+        data,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("more than one category is named Docs")
+    );
+    let output = sbm(&["move", "https://wiki.example.com/", "Docs"], data);
+    assert_eq!(output.status.code(), Some(1));
+
+    // a unique name is found at any depth, and missing categories are created
+    let output = sbm(
+        &[
+            "add",
+            "Work / Infra / Grafana",
+            "Grafana",
+            "",
+            "https://grafana.example.com/",
+        ],
+        "#Work\n#Home\n",
+    );
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Infra\n###Grafana\nGrafana||https://grafana.example.com/\n#Home\n"
+    );
+    let output = sbm(
+        &["move", "https://grafana.example.com/", "Home"],
+        stdout(&output),
+    );
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Infra\n###Grafana\n#Home\nGrafana||https://grafana.example.com/\n"
+    );
+    let output = sbm(
+        &["add", "Infra", "Loki", "", "https://loki.example.com/"],
+        "#Work\n##Infra\n#Home\n",
+    );
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Infra\nLoki||https://loki.example.com/\n#Home\n"
+    );
+}
+
+#[test]
+fn test_dupes_and_dedupe() {
+    let data = "# Languages\nRust | Home | https://www.rust-lang.org/\n\n# Work\n// copied from the wiki\nRust | The Rust Programming Language | http://rust-lang.org\nZig | Zig | https://ziglang.org/\n";
+    assert_eq!(
+        stdout(&sbm(&["dupes"], data)),
+        "Languages\tRust\thttps://www.rust-lang.org/\nWork\tRust\thttp://rust-lang.org\n"
+    );
+    assert_eq!(sbm(&["dupes"], DATA).status.code(), Some(1));
+
+    assert_eq!(
+        stdout(&sbm(&["dedupe"], data)),
+        "# Languages\nRust | Home | https://www.rust-lang.org/\n\n# Work\n// copied from the wiki\nZig | Zig | https://ziglang.org/\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["dedupe", "--merge"], data)),
+        "# Languages\nRust|Home; The Rust Programming Language|https://www.rust-lang.org/\n\n# Work\n// copied from the wiki\nZig | Zig | https://ziglang.org/\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["dedupe", "--longest"], data)),
+        "# Languages\n\n# Work\n// copied from the wiki\nRust | The Rust Programming Language | http://rust-lang.org\nZig | Zig | https://ziglang.org/\n"
+    );
+    assert_eq!(
+        sbm(&["dedupe", "--longest", "--merge"], data).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_fmt_and_check() {
+    let output = sbm(&["fmt", "--check"], DATA);
+    assert!(output.status.success());
+    let output = sbm(&["fmt", "--check", "--compact"], DATA);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = sbm(&["fmt", "--compact"], DATA);
+    assert_eq!(
+        stdout(&output),
+        "#Languages|👨‍💻\nRust|The Rust Programming Language|https://www.rust-lang.org/\n#Web\nMDN|Web documentation|https://developer.mozilla.org/"
+    );
+
+    let output = sbm(&["check"], "#A\nbad line\nok|ok|ok\n#B|x|y\n");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("<stdin>: error: line 2"));
+    assert!(stderr.contains("<stdin>: error: line 4"));
+    assert!(sbm(&["check"], DATA).status.success());
+
+    let typo = "#Web\nMDN|Docs|htps:/developer.mozilla.org/\n";
+    assert!(sbm(&["check"], typo).status.success());
+    let output = sbm(&["check", "--urls"], typo);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("line 2, column 10: bookmark has an invalid URL: scheme \"htps\""));
+    assert!(sbm(&["check", "--urls"], DATA).status.success());
+}
+
+#[test]
+fn test_diff() {
+    let old = TempFile::new("old.sbm", DATA);
+    let new = "# Web Development\nMDN | Web docs | https://developer.mozilla.org/\n# Languages | 👨‍💻\nRust | The Rust Programming Language | https://www.rust-lang.org/\nGo | Go | https://go.dev/\n";
+    assert_eq!(
+        stdout(&sbm(&["diff", old.path()], new)),
+        "~ category Web -> Web Development\n~ Web Development: https://developer.mozilla.org/: description \"Web documentation\" -> \"Web docs\"\n+ Languages: Go <https://go.dev/>\n"
+    );
+    assert_eq!(
+        stdout(&sbm(&["diff", "--tsv", old.path()], new))
+            .lines()
+            .nth(2),
+        Some("added\t\tLanguages\thttps://go.dev/\t\tGo")
+    );
+    assert_eq!(sbm(&["diff", old.path()], new).status.code(), Some(1));
+    assert_eq!(sbm(&["diff", old.path()], DATA).status.code(), Some(0));
+    assert_eq!(sbm(&["diff"], DATA).status.code(), Some(2));
+}
+
+#[test]
+fn test_import_export() {
+    let other = TempFile::new("import.sbm", "#Web\nMDN|Docs|https://developer.mozilla.org/\nGo|Go|https://go.dev/\n#News\nHN|Hacker News|https://news.ycombinator.com/\n");
+    let output = sbm(&["import", other.path()], DATA);
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        DATA.to_string()
+            + "Go|Go|https://go.dev/\n#News\nHN|Hacker News|https://news.ycombinator.com/\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "{}: Web: https://developer.mozilla.org/: description \"Web documentation\" vs \"Docs\"\n",
+            other.path()
+        )
+    );
+
+    // an empty description is filled in, tags are combined, no conflict
+    let other = TempFile::new(
+        "import-fill.sbm",
+        "#Web|🕸\nMDN|Web documentation|https://developer.mozilla.org|docs\n",
+    );
+    let data = "# Web\n// reference\nMDN | | https://developer.mozilla.org/ | web\n";
+    let output = sbm(&["import", other.path()], data);
+    assert_eq!(
+        stdout(&output),
+        "#Web|🕸\n// reference\nMDN|Web documentation|https://developer.mozilla.org/|web,docs\n"
+    );
+    assert!(output.stderr.is_empty());
+
+    let output = sbm(&["export", "--format", "sbm"], "#A\nx|y|z");
+    assert_eq!(stdout(&output), "# A\nx | y | z\n");
+
+    let output = sbm(&["export", "--format", "html"], DATA);
+    assert!(stdout(&output).starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+    let html = TempFile::new("export.html", stdout(&output));
+    let output = sbm(&["import", "--format", "html", html.path()], "");
+    assert_eq!(
+        stdout(&output).parse::<sbm::Sbm>().unwrap(),
+        DATA.parse::<sbm::Sbm>().unwrap()
+    );
+
+    let output = sbm(&["export", "--format", "chromium"], DATA);
+    let json = TempFile::new("Bookmarks", stdout(&output));
+    let output = sbm(&["import", "--format", "chromium", json.path()], "");
+    assert_eq!(
+        sbm(&["list", "--categories"], stdout(&output)).stdout,
+        "Bookmarks bar\n  👨‍💻 Languages\n  Web\n".as_bytes()
+    );
+
+    let places = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/firefox/places.sqlite"
+    );
+    let output = sbm(&["import", "--format", "firefox", places], "");
+    assert!(stdout(&output).contains("#Bookmarks Toolbar\n"));
+    assert!(stdout(&output).contains("##Work\n"));
+
+    // nested categories merge into the matching subcategory
+    let nested = TempFile::new(
+        "nested.sbm",
+        "#Work\n##Infra\nGrafana|Dashboards|https://grafana.example.com/\n##Docs\n",
+    );
+    let output = sbm(
+        &["import", nested.path()],
+        "#Work\n##Infra\nJenkins|CI|https://ci.example.com/\n#Home\n",
+    );
+    assert_eq!(
+        stdout(&output),
+        "#Work\n##Infra\nJenkins|CI|https://ci.example.com/\nGrafana|Dashboards|https://grafana.example.com/\n##Docs\n#Home\n"
+    );
+    assert_eq!(
+        sbm(&["export", "--format", "firefox"], DATA).status.code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_usage_errors() {
+    assert_eq!(sbm(&[], "").status.code(), Some(2));
+    assert_eq!(sbm(&["frobnicate"], "").status.code(), Some(2));
+    assert_eq!(sbm(&["add", "Web"], "").status.code(), Some(2));
+    assert_eq!(sbm(&["list", "--bogus"], "").status.code(), Some(2));
+    assert_eq!(
+        sbm(&["export", "--format", "docx"], "").status.code(),
+        Some(2)
+    );
+    assert_eq!(sbm(&["list"], "bad").status.code(), Some(1));
+}
@@ -0,0 +1,96 @@
+"""Generate places.sqlite, a small Firefox profile database for the tests.
+
+The schema is the one Firefox uses; the page size is kept small so that the
+tables span several b-tree levels and the long URL needs overflow pages.
+"""
+
+import os
+import sqlite3
+
+PATH = os.path.join(os.path.dirname(os.path.abspath(__file__)), "places.sqlite")
+
+SCHEMA = """
+CREATE TABLE moz_origins ( id INTEGER PRIMARY KEY, prefix TEXT NOT NULL, host TEXT NOT NULL, frecency INTEGER NOT NULL, recalc_frecency INTEGER NOT NULL DEFAULT 0, alt_frecency INTEGER, recalc_alt_frecency INTEGER NOT NULL DEFAULT 0, UNIQUE (prefix, host) );
+CREATE TABLE moz_places (   id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR, rev_host LONGVARCHAR, visit_count INTEGER DEFAULT 0, hidden INTEGER DEFAULT 0 NOT NULL, typed INTEGER DEFAULT 0 NOT NULL, frecency INTEGER DEFAULT -1 NOT NULL, last_visit_date INTEGER , guid TEXT, foreign_count INTEGER DEFAULT 0 NOT NULL, url_hash INTEGER DEFAULT 0 NOT NULL , description TEXT, preview_image_url TEXT, site_name TEXT, origin_id INTEGER REFERENCES moz_origins(id), recalc_frecency INTEGER NOT NULL DEFAULT 0, alt_frecency INTEGER, recalc_alt_frecency INTEGER NOT NULL DEFAULT 0);
+CREATE TABLE moz_bookmarks (  id INTEGER PRIMARY KEY, type INTEGER, fk INTEGER DEFAULT NULL, parent INTEGER, position INTEGER, title LONGVARCHAR, keyword_id INTEGER, folder_type TEXT, dateAdded INTEGER, lastModified INTEGER, guid TEXT, syncStatus INTEGER NOT NULL DEFAULT 0, syncChangeCounter INTEGER NOT NULL DEFAULT 1);
+CREATE TABLE moz_keywords (  id INTEGER PRIMARY KEY AUTOINCREMENT, keyword TEXT UNIQUE, place_id INTEGER, post_data TEXT);
+CREATE INDEX moz_places_url_hashindex ON moz_places (url_hash);
+CREATE UNIQUE INDEX moz_places_guid_uniqueindex ON moz_places (guid);
+CREATE INDEX moz_bookmarks_itemindex ON moz_bookmarks (fk, type);
+CREATE UNIQUE INDEX moz_bookmarks_guid_uniqueindex ON moz_bookmarks (guid);
+"""
+
+LONG_URL = "https://example.com/search?q=" + "x" * 3000
+
+PLACES = [
+    # id, url, title, description
+    (1, "https://www.mozilla.org/en-US/firefox/central/", "Getting Started", None),
+    (2, "https://support.mozilla.org/en-US/products/firefox", "Get Help", None),
+    (3, "https://developer.mozilla.org/", "MDN Web Docs", "Resources for Developers, by Developers"),
+    (4, "https://www.rust-lang.org/", "Rust Programming Language", None),
+    (5, "https://github.com/", "GitHub", None),
+    (6, LONG_URL, "Long search", None),
+    (7, "https://news.ycombinator.com/", "Hacker News", None),
+]
+
+ROOT, MENU, TOOLBAR, TAGS, UNFILED, MOBILE = 1, 2, 3, 4, 5, 6
+
+BOOKMARKS = [
+    # id, type, fk, parent, position, title, guid
+    (ROOT, 2, None, 0, 0, "", "root________"),
+    (MENU, 2, None, ROOT, 0, "menu", "menu________"),
+    (TOOLBAR, 2, None, ROOT, 1, "toolbar", "toolbar_____"),
+    (TAGS, 2, None, ROOT, 2, "tags", "tags________"),
+    (UNFILED, 2, None, ROOT, 3, "unfiled", "unfiled_____"),
+    (MOBILE, 2, None, ROOT, 4, "mobile", "mobile______"),
+    # the children of a folder are deliberately not stored in position order
+    (10, 2, None, MENU, 1, "Mozilla Firefox", "folder000001"),
+    (7, 1, 1, MENU, 0, "Getting Started", "bookmark0001"),
+    (11, 1, 2, 10, 0, "Get Help", "bookmark0002"),
+    (12, 1, 3, TOOLBAR, 0, "MDN Web Docs", "bookmark0003"),
+    (13, 1, 4, TOOLBAR, 1, "Rust 🦀", "bookmark0004"),
+    (14, 3, None, TOOLBAR, 2, None, "separator001"),
+    (15, 2, None, TOOLBAR, 3, "Work", "folder000002"),
+    (16, 1, 5, 15, 0, "GitHub", "bookmark0005"),
+    (17, 1, 6, 15, 1, "Long search", "bookmark0006"),
+    (18, 1, 7, UNFILED, 0, None, "bookmark0007"),
+    (19, 2, None, TAGS, 0, "docs", "tagfolder001"),
+    (20, 1, 3, 19, 0, None, "tagentry0001"),
+    (21, 2, None, TAGS, 1, "rust", "tagfolder002"),
+    (22, 1, 4, 21, 0, None, "tagentry0002"),
+    (23, 1, 5, 19, 1, None, "tagentry0003"),
+]
+
+
+def main():
+    if os.path.exists(PATH):
+        os.remove(PATH)
+    db = sqlite3.connect(PATH)
+    db.execute("PRAGMA page_size = 1024")
+    db.executescript(SCHEMA)
+    for id, url, title, description in PLACES:
+        db.execute(
+            "INSERT INTO moz_places (id, url, title, guid, description) VALUES (?, ?, ?, ?, ?)",
+            (id, url, title, "place%07d" % id, description),
+        )
+    # history without bookmarks, enough to need interior b-tree pages
+    for id in range(100, 400):
+        url = "https://history.example.com/page/%d" % id
+        db.execute(
+            "INSERT INTO moz_places (id, url, title, guid) VALUES (?, ?, ?, ?)",
+            (id, url, "Visited page %d" % id, "place%07d" % id),
+        )
+    for id, type, fk, parent, position, title, guid in BOOKMARKS:
+        db.execute(
+            "INSERT INTO moz_bookmarks (id, type, fk, parent, position, title, guid, dateAdded, lastModified)"
+            " VALUES (?, ?, ?, ?, ?, ?, ?, 1700000000000000, 1700000000000000)",
+            (id, type, fk, parent, position, title, guid),
+        )
+    db.execute("INSERT INTO moz_keywords (keyword, place_id) VALUES ('mdn', 3)")
+    db.commit()
+    db.execute("VACUUM")
+    db.close()
+
+
+if __name__ == "__main__":
+    main()